@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, RcLocal, SideEffects, Traverse};
+use crate::{formatter::Formatter, BinaryOperation, RcLocal, SideEffects, Traverse};
 
 use super::{LValue, LocalRw, RValue};
 
@@ -75,11 +75,79 @@ impl LocalRw for Assign {
 
 impl fmt::Display for Assign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
+        Formatter::new(f).format_assign(self)
+    }
+}
+
+// `left op= right`, only luau has these
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundAssign {
+    pub left: LValue,
+    pub operation: BinaryOperation,
+    pub right: RValue,
+}
+
+impl CompoundAssign {
+    pub fn new(left: LValue, operation: BinaryOperation, right: RValue) -> Self {
+        Self {
+            left,
+            operation,
+            right,
         }
-        .format_assign(self)
+    }
+}
+
+impl Traverse for CompoundAssign {
+    fn lvalues_mut(&mut self) -> Vec<&mut LValue> {
+        vec![&mut self.left]
+    }
+
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        vec![&mut self.right]
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        vec![&self.right]
+    }
+}
+
+impl SideEffects for CompoundAssign {
+    fn has_side_effects(&self) -> bool {
+        true
+    }
+}
+
+// the left side is read as well as written
+impl LocalRw for CompoundAssign {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.left
+            .values_read()
+            .into_iter()
+            .chain(self.left.values_written())
+            .chain(self.right.values_read())
+            .collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        // a local on the left is returned by `values_written_mut`
+        self.left
+            .values_read_mut()
+            .into_iter()
+            .chain(self.right.values_read_mut())
+            .collect()
+    }
+
+    fn values_written(&self) -> Vec<&RcLocal> {
+        self.left.values_written()
+    }
+
+    fn values_written_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.left.values_written_mut()
+    }
+}
+
+impl fmt::Display for CompoundAssign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter::new(f).format_compound_assign(self)
     }
 }
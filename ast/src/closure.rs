@@ -21,6 +21,9 @@ pub struct Function {
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
     pub is_variadic: bool,
+    // the first parameter is `self`, the function is written as `function a:b()` when it's
+    // assigned to `a.b`
+    pub is_method: bool,
     pub body: Block,
 }
 
@@ -56,12 +59,7 @@ impl Infer for Closure {
 
 impl fmt::Display for Closure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_closure(self)
+        Formatter::new(f).format_closure(self)
     }
 }
 
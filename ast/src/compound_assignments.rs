@@ -0,0 +1,79 @@
+use crate::{
+    Assign, BinaryOperation, Block, CompoundAssign, LValue, RValue, SideEffects, Statement,
+    Traverse,
+};
+
+// the value of an lvalue, as long as reading it in the compound assignment instead of in
+// the expression doesn't change anything
+fn value(lvalue: &LValue) -> Option<RValue> {
+    match lvalue {
+        LValue::Local(local) => Some(RValue::Local(local.clone())),
+        LValue::Global(global) => Some(RValue::Global(global.clone())),
+        LValue::Index(index)
+            if !index.left.has_side_effects() && !index.right.has_side_effects() =>
+        {
+            Some(RValue::Index(index.clone()))
+        }
+        _ => None,
+    }
+}
+
+fn contains(rvalue: &RValue, value: &RValue) -> bool {
+    rvalue == value || rvalue.rvalues().into_iter().any(|r| contains(r, value))
+}
+
+// `a = a op b` as `a op= b`, if `a` isn't read in `b`
+fn compound_assign(assign: &Assign) -> Option<CompoundAssign> {
+    if assign.prefix || assign.left.len() != 1 || assign.right.len() != 1 {
+        return None;
+    }
+    let RValue::Binary(binary) = &assign.right[0] else {
+        return None;
+    };
+    if binary.operation.is_comparator()
+        || matches!(binary.operation, BinaryOperation::And | BinaryOperation::Or)
+    {
+        return None;
+    }
+    let left = &assign.left[0];
+    let value = value(left)?;
+    if *binary.left != value || contains(&binary.right, &value) {
+        return None;
+    }
+    Some(CompoundAssign::new(
+        left.clone(),
+        binary.operation,
+        (*binary.right).clone(),
+    ))
+}
+
+// luau compiles `a += b` to `a = a + b`, this turns it back into a compound assignment.
+// closures are left alone, their bodies are handled on their own
+pub fn reconstruct_compound_assignments(block: &mut Block) {
+    for statement in &mut block.0 {
+        match statement {
+            Statement::Assign(assign) => {
+                if let Some(compound_assign) = compound_assign(assign) {
+                    *statement = compound_assign.into();
+                }
+            }
+            Statement::If(r#if) => {
+                reconstruct_compound_assignments(&mut r#if.then_block.lock());
+                reconstruct_compound_assignments(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                reconstruct_compound_assignments(&mut r#while.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                reconstruct_compound_assignments(&mut repeat.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                reconstruct_compound_assignments(&mut numeric_for.block.lock());
+            }
+            Statement::GenericFor(generic_for) => {
+                reconstruct_compound_assignments(&mut generic_for.block.lock());
+            }
+            _ => {}
+        }
+    }
+}
@@ -266,19 +266,28 @@ impl fmt::Display for NumericFor {
     }
 }
 
+// the second field is set for luau's generalized iteration, where the generator can also
+// be a table or a value with an `__iter` metamethod instead of a function
 #[derive(Debug, PartialEq, Clone)]
-pub struct GenericForInit(pub Assign);
+pub struct GenericForInit(pub Assign, pub bool);
 
 impl GenericForInit {
     pub fn new(generator: RcLocal, state: RcLocal, initial_control: RcLocal) -> Self {
-        Self(Assign::new(
-            vec![
-                generator.clone().into(),
-                state.clone().into(),
-                initial_control.clone().into(),
-            ],
-            vec![generator.into(), state.into(), initial_control.into()],
-        ))
+        Self(
+            Assign::new(
+                vec![
+                    generator.clone().into(),
+                    state.clone().into(),
+                    initial_control.clone().into(),
+                ],
+                vec![generator.into(), state.into(), initial_control.into()],
+            ),
+            false,
+        )
+    }
+
+    pub fn generalized(generator: RcLocal, state: RcLocal, initial_control: RcLocal) -> Self {
+        Self(Self::new(generator, state, initial_control).0, true)
     }
 }
 
@@ -8,10 +8,12 @@ use std::{
 use itertools::Itertools;
 
 use crate::{
-    Assign, Binary, BinaryOperation, Block, Call, Closure, GenericFor, If, Index, LValue, Literal,
-    MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table, Unary, While,
+    Assign, Binary, BinaryOperation, Block, Call, Closure, CompoundAssign, GenericFor, If,
+    IfExpression, Index, InterpolatedString, LValue, Literal, MethodCall, NumericFor, RValue,
+    Repeat, Return, Select, Statement, Table, Unary, While,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndentationMode {
     Spaces(u8),
     Tab,
@@ -42,6 +44,83 @@ impl Default for IndentationMode {
     }
 }
 
+// when table constructors are written over multiple lines, and how
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableLayout {
+    // tables with more entries than this are written over multiple lines
+    pub max_inline_entries: usize,
+    // tables that would be longer than this on one line are written over multiple lines
+    pub max_inline_width: Option<usize>,
+    // tables with keys are written over multiple lines, no matter how many entries they have
+    pub split_keyed: bool,
+    // array entries on each line of a table written over multiple lines
+    pub entries_per_line: usize,
+    // a comma after the last entry of a table written over multiple lines
+    pub trailing_comma: bool,
+}
+
+impl Default for TableLayout {
+    fn default() -> Self {
+        Self {
+            max_inline_entries: 3,
+            max_inline_width: None,
+            split_keyed: true,
+            entries_per_line: 1,
+            trailing_comma: false,
+        }
+    }
+}
+
+// columns a tab is counted as when measuring lines
+const TAB_WIDTH: usize = 4;
+
+fn width(string: &str) -> usize {
+    string
+        .chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+// the output of a formatter, keeping track of the column the next write starts at
+pub(crate) struct Output<'a, W: fmt::Write> {
+    inner: &'a mut W,
+    column: usize,
+}
+
+impl<'a, W: fmt::Write> Output<'a, W> {
+    pub(crate) fn new(inner: &'a mut W) -> Self {
+        Self { inner, column: 0 }
+    }
+}
+
+impl<W: fmt::Write> fmt::Write for Output<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match s.rsplit_once('\n') {
+            Some((_, last_line)) => self.column = width(last_line),
+            None => self.column += width(s),
+        }
+        self.inner.write_str(s)
+    }
+}
+
+// measures the first line written to it, failing once the line ends or is longer than
+// `max_width` so that the rest isn't formatted
+struct LineWidth {
+    width: usize,
+    max_width: usize,
+}
+
+impl fmt::Write for LineWidth {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let first_line = s.split('\n').next().unwrap();
+        self.width += width(first_line);
+        if self.width > self.max_width || first_line.len() != s.len() {
+            return Err(fmt::Error);
+        }
+        Ok(())
+    }
+}
+
 pub(crate) fn format_arg_list(list: &[RValue]) -> String {
     let mut s = String::new();
     for (index, rvalue) in list.iter().enumerate() {
@@ -61,19 +140,38 @@ pub(crate) fn format_arg_list(list: &[RValue]) -> String {
 pub struct Formatter<'a, W: fmt::Write> {
     pub(crate) indentation_level: usize,
     pub(crate) indentation_mode: IndentationMode,
-    pub(crate) output: &'a mut W,
+    pub(crate) table_layout: TableLayout,
+    // argument lists, binary chains and method chains that would make a line longer than
+    // this are written over multiple lines
+    pub(crate) max_line_width: Option<usize>,
+    pub(crate) output: Output<'a, W>,
 }
 
 impl<'a, W: fmt::Write> Formatter<'a, W> {
+    // a formatter with the default options, for `Display` implementations
+    pub(crate) fn new(output: &'a mut W) -> Self {
+        Self {
+            indentation_level: 0,
+            indentation_mode: Default::default(),
+            table_layout: Default::default(),
+            max_line_width: None,
+            output: Output::new(output),
+        }
+    }
+
     pub fn format(
         main: &Block,
         output: &'a mut W,
         indentation_mode: IndentationMode,
+        table_layout: TableLayout,
+        max_line_width: Option<usize>,
     ) -> fmt::Result {
         let mut formatter = Self {
             indentation_level: 0,
             indentation_mode,
-            output,
+            table_layout,
+            max_line_width,
+            output: Output::new(output),
         };
         formatter.format_block_no_indent(main)
     }
@@ -83,6 +181,28 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             .display(&mut self.output, self.indentation_level)
     }
 
+    // whether the first line of what `format` writes fits in `max_line_width` when it's
+    // written from the current column without wrapping
+    fn fits(&self, format: impl FnOnce(&mut Formatter<LineWidth>) -> fmt::Result) -> bool {
+        let Some(max_line_width) = self.max_line_width else {
+            return true;
+        };
+        let mut line = LineWidth {
+            width: self.output.column,
+            max_width: max_line_width,
+        };
+        let mut formatter = Formatter {
+            indentation_level: self.indentation_level,
+            indentation_mode: self.indentation_mode,
+            table_layout: self.table_layout,
+            max_line_width: None,
+            output: Output::new(&mut line),
+        };
+        // an error is either the end of the first line or it being too long
+        let _ = format(&mut formatter);
+        line.width <= max_line_width
+    }
+
     // (function() end)()
     // (function() end)[1]
     fn should_wrap_left_rvalue(value: &RValue) -> bool {
@@ -120,6 +240,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                         | RValue::MethodCall(_)
                         | RValue::Select(Select::Call(_) | Select::MethodCall(_)) => true,
                         RValue::Binary(binary) => is_ambiguous(&binary.right),
+                        RValue::IfExpression(if_expression) => {
+                            is_ambiguous(&if_expression.else_value)
+                        }
                         _ => false,
                     }
                 }
@@ -135,6 +258,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                             false
                         }
                     }
+                    Statement::CompoundAssign(compound_assign) => {
+                        is_ambiguous(&compound_assign.right)
+                    }
                     Statement::Goto(_) | Statement::Continue(_) | Statement::Break(_) => true,
                     _ => false,
                 };
@@ -198,25 +324,73 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         table.0.iter().any(|(_, v)| matches!(v, RValue::Table(_x)))
     }
 
-    pub(crate) fn format_table(&mut self, table: &Table) -> fmt::Result {
-        let sequential_keys = Self::are_table_keys_sequential(table);
-        let should_space = !table.0.is_empty();
-        let should_format = !table.0.is_empty() && (!sequential_keys || table.0.len() > 3)
-            || Self::contains_table(table);
-        write!(self.output, "{{")?;
-        if should_format {
-            writeln!(self.output)?;
-        } else if should_space {
-            write!(self.output, " ")?;
+    // whether `table` fits on one line according to `table_layout`
+    fn is_table_inline(&self, table: &Table, sequential_keys: bool) -> bool {
+        let layout = self.table_layout;
+        if table.0.is_empty() {
+            return true;
         }
-        self.indentation_level += 1;
+        if Self::contains_table(table)
+            || (layout.split_keyed && !sequential_keys)
+            || table.0.len() > layout.max_inline_entries
+        {
+            return false;
+        }
+        if let Some(max_inline_width) = layout.max_inline_width {
+            let mut line = String::new();
+            let mut formatter = Formatter {
+                indentation_level: self.indentation_level,
+                indentation_mode: self.indentation_mode,
+                table_layout: self.table_layout,
+                max_line_width: None,
+                output: Output::new(&mut line),
+            };
+            if formatter
+                .format_table_entries(table, sequential_keys, false)
+                .is_err()
+                || line.len() > max_inline_width
+                || line.contains('\n')
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    // array entries share lines in groups of `table_layout.entries_per_line`, entries with
+    // a key always get a line of their own
+    fn format_table_entries(
+        &mut self,
+        table: &Table,
+        sequential_keys: bool,
+        multiline: bool,
+    ) -> fmt::Result {
+        let entries_per_line = self.table_layout.entries_per_line.max(1);
+        let mut on_line = 0;
         for (index, (key, value)) in table.0.iter().enumerate() {
-            if should_format {
+            let key = key.as_ref().filter(|_| !sequential_keys);
+            if index != 0 {
+                write!(self.output, ",")?;
+                if multiline && (key.is_some() || on_line == entries_per_line) {
+                    writeln!(self.output)?;
+                    on_line = 0;
+                } else {
+                    write!(self.output, " ")?;
+                }
+            }
+            if multiline && on_line == 0 {
                 self.indent()?;
             }
             let is_last = index + 1 == table.0.len();
-            if is_last && key.is_none() {
-                let wrap = matches!(value, RValue::Select(_));
+            if let Some(key) = key {
+                write!(self.output, "[")?;
+                self.format_rvalue(key)?;
+                write!(self.output, "] = ")?;
+                self.format_rvalue(value)?;
+                // the next entry starts a new line
+                on_line = entries_per_line;
+            } else {
+                let wrap = is_last && matches!(value, RValue::Select(_));
                 if wrap {
                     write!(self.output, "(")?;
                 }
@@ -224,26 +398,31 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 if wrap {
                     write!(self.output, ")")?;
                 }
-            } else {
-                if !sequential_keys {
-                    if let Some(key) = key {
-                        write!(self.output, "[")?;
-                        self.format_rvalue(key)?;
-                        write!(self.output, "] = ")?;
-                    }
-                }
-                self.format_rvalue(value)?;
-                if !is_last {
-                    write!(self.output, ",")?;
-                    write!(self.output, "{}", if should_format { "\n" } else { " " })?;
-                }
+                on_line += 1;
             }
         }
+        if multiline && self.table_layout.trailing_comma {
+            write!(self.output, ",")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn format_table(&mut self, table: &Table) -> fmt::Result {
+        let sequential_keys = Self::are_table_keys_sequential(table);
+        let multiline = !self.is_table_inline(table, sequential_keys);
+        write!(self.output, "{{")?;
+        if multiline {
+            writeln!(self.output)?;
+        } else if !table.0.is_empty() {
+            write!(self.output, " ")?;
+        }
+        self.indentation_level += 1;
+        self.format_table_entries(table, sequential_keys, multiline)?;
         self.indentation_level -= 1;
-        if should_format {
+        if multiline {
             writeln!(self.output)?;
             self.indent()?;
-        } else if should_space {
+        } else if !table.0.is_empty() {
             write!(self.output, " ")?;
         }
         write!(self.output, "}}")
@@ -274,25 +453,112 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             Ok(())
         };
 
-        parentheses(self, binary.left_group(), &binary.left)?;
-        write!(self.output, " {} ", binary.operation)?;
-        parentheses(self, binary.right_group(), &binary.right)
+        if self.fits(|f| f.format_binary(binary)) {
+            parentheses(self, binary.left_group(), &binary.left)?;
+            write!(self.output, " {} ", binary.operation)?;
+            return parentheses(self, binary.right_group(), &binary.right);
+        }
+
+        // too long for the line, every operator of the chain starts a new line
+        let mut chain = Vec::new();
+        Self::binary_chain(binary, &mut chain);
+        self.indentation_level += 1;
+        for (operation, rvalue, wrap) in chain {
+            if let Some(operation) = operation {
+                writeln!(self.output)?;
+                self.indent()?;
+                write!(self.output, "{} ", operation)?;
+            }
+            parentheses(self, wrap, rvalue)?;
+        }
+        self.indentation_level -= 1;
+        Ok(())
+    }
+
+    // the operands of `binary`, each with the operator before it and whether it's in
+    // parentheses. operands that are operations of the same precedence and don't need
+    // parentheses are part of the chain, ex. `a .. (b .. c)` or `a + b - c`
+    fn binary_chain<'b>(
+        binary: &'b Binary,
+        chain: &mut Vec<(Option<BinaryOperation>, &'b RValue, bool)>,
+    ) {
+        let operands = [
+            (&binary.left, binary.left_group(), None),
+            (&binary.right, binary.right_group(), Some(binary.operation)),
+        ];
+        for (rvalue, wrap, operation) in operands {
+            let start = chain.len();
+            match rvalue.as_ref() {
+                RValue::Binary(operand) if !wrap && operand.precedence() == binary.precedence() => {
+                    Self::binary_chain(operand, chain)
+                }
+                rvalue => chain.push((None, rvalue, wrap)),
+            }
+            chain[start].0 = operation;
+        }
+    }
+
+    // nested if expressions in the else branch are written with elseif
+    pub(crate) fn format_if_expression(&mut self, if_expression: &IfExpression) -> fmt::Result {
+        write!(self.output, "if ")?;
+        let mut if_expression = if_expression;
+        loop {
+            self.format_rvalue(&if_expression.condition)?;
+            write!(self.output, " then ")?;
+            self.format_rvalue(&if_expression.then_value)?;
+            if let RValue::IfExpression(else_if) = &*if_expression.else_value {
+                write!(self.output, " elseif ")?;
+                if_expression = else_if;
+            } else {
+                write!(self.output, " else ")?;
+                return self.format_rvalue(&if_expression.else_value);
+            }
+        }
     }
 
-    fn format_closure_parameters(&mut self, closure: &Closure) -> fmt::Result {
+    pub(crate) fn format_interpolated_string(
+        &mut self,
+        interpolated_string: &InterpolatedString,
+    ) -> fmt::Result {
+        write!(self.output, "`")?;
+        for (i, string) in interpolated_string.strings.iter().enumerate() {
+            // braces start a value and backticks end the string
+            let escaped = Self::escape_string(string)
+                .replace('{', r"\{")
+                .replace('}', r"\}")
+                .replace('`', r"\`");
+            write!(self.output, "{}", escaped)?;
+            if let Some(value) = interpolated_string.values.get(i) {
+                // luau doesn't allow `{{`, tables are wrapped in parentheses
+                let wrap = matches!(value, RValue::Table(_));
+                write!(self.output, "{{")?;
+                if wrap {
+                    write!(self.output, "(")?;
+                }
+                self.format_rvalue(value)?;
+                if wrap {
+                    write!(self.output, ")")?;
+                }
+                write!(self.output, "}}")?;
+            }
+        }
+        write!(self.output, "`")
+    }
+
+    // the first parameter of a method is the implicit `self`
+    fn format_closure_parameters(&mut self, closure: &Closure, is_method: bool) -> fmt::Result {
         let function = closure.function.lock();
+        let mut parameters = function.parameters.iter().skip(is_method as usize);
         write!(
             self.output,
             "{}",
             if function.is_variadic {
-                function
-                    .parameters
-                    .iter()
+                parameters
                     .map(|x| x.to_string())
                     .chain(std::iter::once("...".into()))
                     .join(", ")
             } else {
-                function.parameters.iter().join(", ")
+                parameters.join(", ")
             }
         )
     }
@@ -341,15 +607,32 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
     pub(crate) fn format_closure(&mut self, closure: &Closure) -> fmt::Result {
         write!(self.output, "function(")?;
-        self.format_closure_parameters(closure)?;
+        self.format_closure_parameters(closure, false)?;
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
     fn format_named_function(&mut self, name: &LValue, closure: &Closure) -> fmt::Result {
-        write!(self.output, "function {}(", name)?;
-        self.format_closure_parameters(closure)?;
+        let is_method = match name {
+            LValue::Index(Index {
+                left,
+                right: box RValue::Literal(Literal::String(key)),
+            }) if closure.function.lock().is_method => {
+                write!(
+                    self.output,
+                    "function {}:{}(",
+                    left,
+                    String::from_utf8_lossy(key)
+                )?;
+                true
+            }
+            _ => {
+                write!(self.output, "function {}(", name)?;
+                false
+            }
+        };
+        self.format_closure_parameters(closure, is_method)?;
         write!(self.output, ")")?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
@@ -366,6 +649,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             RValue::Unary(unary) => self.format_unary(unary),
             RValue::Binary(binary) => self.format_binary(binary),
             RValue::Closure(closure) => self.format_closure(closure),
+            RValue::IfExpression(if_expression) => self.format_if_expression(if_expression),
+            RValue::InterpolatedString(interpolated_string) => {
+                self.format_interpolated_string(interpolated_string)
+            }
             RValue::Literal(Literal::Number(n)) if n.is_infinite() => {
                 // TODO: only insert parentheses when necessary
                 write!(self.output, "(")?;
@@ -392,8 +679,12 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 
-    fn format_arg_list(&mut self, list: &[RValue]) -> fmt::Result {
+    fn format_arg_list(&mut self, list: &[RValue], multiline: bool) -> fmt::Result {
         for (index, rvalue) in list.iter().enumerate() {
+            if multiline {
+                writeln!(self.output)?;
+                self.indent()?;
+            }
             if index + 1 == list.len() {
                 let wrap = matches!(rvalue, RValue::Select(_));
                 if wrap {
@@ -405,11 +696,47 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 }
             } else {
                 self.format_rvalue(rvalue)?;
-                write!(self.output, ", ")?;
+                write!(self.output, "{}", if multiline { "," } else { ", " })?;
             }
         }
         Ok(())
     }
+
+    // arguments that don't fit on the line are written one per line
+    fn format_arguments(&mut self, arguments: &[RValue]) -> fmt::Result {
+        write!(self.output, "(")?;
+        let multiline = !arguments.is_empty()
+            && !self.fits(|f| {
+                f.format_arg_list(arguments, false)?;
+                write!(f.output, ")")
+            });
+        self.indentation_level += multiline as usize;
+        self.format_arg_list(arguments, multiline)?;
+        self.indentation_level -= multiline as usize;
+        if multiline {
+            writeln!(self.output)?;
+            self.indent()?;
+        }
+        write!(self.output, ")")
+    }
+
+    // whether `index` can be the name in `function a.b.c()`
+    pub(crate) fn is_function_name(mut index: &Index) -> bool {
+        loop {
+            let box RValue::Literal(Literal::String(key)) = &index.right else {
+                return false;
+            };
+            if !Self::is_valid_name(key) {
+                return false;
+            }
+            match &index.left {
+                box RValue::Index(left) => index = left,
+                box RValue::Global(_) | box RValue::Local(_) => return true,
+                _ => return false,
+            }
+        }
+    }
+
     pub(crate) fn is_valid_name(name: &[u8]) -> bool {
         if !(name
             .iter()
@@ -512,26 +839,42 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             write!(self.output, ")")?;
         }
 
-        write!(self.output, "(")?;
-        self.format_arg_list(&call.arguments)?;
-        write!(self.output, ")")
+        self.format_arguments(&call.arguments)
     }
 
     pub(crate) fn format_method_call(&mut self, method_call: &MethodCall) -> fmt::Result {
-        let wrap = Self::should_wrap_left_rvalue(&method_call.value);
+        // a:b():c(), from the last call to the first
+        let mut chain = vec![method_call];
+        while let RValue::Select(Select::MethodCall(value)) = chain.last().unwrap().value.as_ref() {
+            chain.push(value);
+        }
+        let multiline = chain.len() > 1 && !self.fits(|f| f.format_method_call(method_call));
+        if !multiline {
+            chain.truncate(1);
+        }
+
+        let value = &chain.last().unwrap().value;
+        let wrap = Self::should_wrap_left_rvalue(value);
         if wrap {
             write!(self.output, "(")?;
         }
-        self.format_rvalue(&method_call.value)?;
+        self.format_rvalue(value)?;
         if wrap {
             write!(self.output, ")")?;
         }
 
-        write!(self.output, ":{}", method_call.method)?;
-
-        write!(self.output, "(")?;
-        self.format_arg_list(&method_call.arguments)?;
-        write!(self.output, ")")
+        // too long for the line, every call of the chain starts a new line
+        self.indentation_level += multiline as usize;
+        for method_call in chain.into_iter().rev() {
+            if multiline {
+                writeln!(self.output)?;
+                self.indent()?;
+            }
+            write!(self.output, ":{}", method_call.method)?;
+            self.format_arguments(&method_call.arguments)?;
+        }
+        self.indentation_level -= multiline as usize;
+        Ok(())
     }
 
     pub(crate) fn format_if(&mut self, r#if: &If) -> fmt::Result {
@@ -573,32 +916,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             && let RValue::Closure(closure) = &assign.right[0]
         {
             let left = &assign.left[0];
-            if assign.prefix || left.as_global().is_some() || {
-                if let LValue::Index(ref index) = left {
-                    let mut index = index;
-                    let mut valid = true;
-                    loop {
-                        if let box RValue::Literal(Literal::String(ref key)) = &index.right
-                            && Self::is_valid_name(key)
-                        {
-                            match index.left {
-                                box RValue::Index(ref i) => {
-                                    index = i;
-                                    continue;
-                                }
-                                box RValue::Global(_) | box RValue::Local(_) => {}
-                                _ => valid = false,
-                            }
-                        } else {
-                            valid = false;
-                        }
-                        break;
-                    }
-                    valid
-                } else {
-                    false
-                }
-            } {
+            if assign.prefix
+                || left.as_global().is_some()
+                || matches!(left, LValue::Index(index) if Self::is_function_name(index))
+            {
                 return self.format_named_function(left, closure);
             }
         }
@@ -631,6 +952,15 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         Ok(())
     }
 
+    pub(crate) fn format_compound_assign(
+        &mut self,
+        compound_assign: &CompoundAssign,
+    ) -> fmt::Result {
+        self.format_lvalue(&compound_assign.left)?;
+        write!(self.output, " {}= ", compound_assign.operation)?;
+        self.format_rvalue(&compound_assign.right)
+    }
+
     pub(crate) fn format_while(&mut self, r#while: &While) -> fmt::Result {
         write!(self.output, "while ")?;
 
@@ -725,6 +1055,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
         match statement {
             Statement::Assign(assign) => self.format_assign(assign),
+            Statement::CompoundAssign(compound_assign) => {
+                self.format_compound_assign(compound_assign)
+            }
             Statement::If(r#if) => self.format_if(r#if),
             Statement::While(r#while) => self.format_while(r#while),
             Statement::Repeat(repeat) => self.format_repeat(repeat),
@@ -60,11 +60,6 @@ impl LocalRw for If {
 
 impl fmt::Display for If {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_if(self)
+        Formatter::new(f).format_if(self)
     }
 }
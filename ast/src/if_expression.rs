@@ -0,0 +1,96 @@
+use std::fmt;
+
+use crate::{
+    formatter::Formatter, Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse,
+};
+
+// `if condition then a else b`, only luau has these
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfExpression {
+    pub condition: Box<RValue>,
+    pub then_value: Box<RValue>,
+    pub else_value: Box<RValue>,
+}
+
+impl IfExpression {
+    pub fn new(condition: RValue, then_value: RValue, else_value: RValue) -> Self {
+        Self {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+        }
+    }
+
+    fn reduce_with(self, reduce_value: fn(RValue) -> RValue) -> RValue {
+        match self.condition.reduce_condition() {
+            RValue::Literal(Literal::Boolean(true)) => reduce_value(*self.then_value),
+            RValue::Literal(Literal::Boolean(false) | Literal::Nil) => {
+                reduce_value(*self.else_value)
+            }
+            condition => Self::new(
+                condition,
+                reduce_value(*self.then_value),
+                reduce_value(*self.else_value),
+            )
+            .into(),
+        }
+    }
+}
+
+impl Reduce for IfExpression {
+    fn reduce(self) -> RValue {
+        self.reduce_with(RValue::reduce)
+    }
+
+    fn reduce_condition(self) -> RValue {
+        self.reduce_with(RValue::reduce_condition)
+    }
+}
+
+impl SideEffects for IfExpression {
+    fn has_side_effects(&self) -> bool {
+        self.condition.has_side_effects()
+            || self.then_value.has_side_effects()
+            || self.else_value.has_side_effects()
+    }
+}
+
+impl Traverse for IfExpression {
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        vec![
+            &mut self.condition,
+            &mut self.then_value,
+            &mut self.else_value,
+        ]
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        vec![&self.condition, &self.then_value, &self.else_value]
+    }
+}
+
+impl LocalRw for IfExpression {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.condition
+            .values_read()
+            .into_iter()
+            .chain(self.then_value.values_read())
+            .chain(self.else_value.values_read())
+            .collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.condition
+            .values_read_mut()
+            .into_iter()
+            .chain(self.then_value.values_read_mut())
+            .chain(self.else_value.values_read_mut())
+            .collect()
+    }
+}
+
+impl fmt::Display for IfExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter::new(f).format_if_expression(self)
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+
+use crate::{formatter::Formatter, has_side_effects, LocalRw, RValue, RcLocal, Traverse};
+
+// `hello {name}!`, only luau has these. there's one more string than there are values,
+// every value is written between two strings
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpolatedString {
+    pub strings: Vec<Vec<u8>>,
+    pub values: Vec<RValue>,
+}
+
+// values are converted with tostring, which can call __tostring
+has_side_effects!(InterpolatedString);
+
+impl InterpolatedString {
+    pub fn new(strings: Vec<Vec<u8>>, values: Vec<RValue>) -> Self {
+        assert_eq!(strings.len(), values.len() + 1);
+        Self { strings, values }
+    }
+}
+
+impl Traverse for InterpolatedString {
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        self.values.iter_mut().collect()
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        self.values.iter().collect()
+    }
+}
+
+impl LocalRw for InterpolatedString {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.values.iter().flat_map(|v| v.values_read()).collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.values
+            .iter_mut()
+            .flat_map(|v| v.values_read_mut())
+            .collect()
+    }
+}
+
+impl fmt::Display for InterpolatedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter::new(f).format_interpolated_string(self)
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Block, InterpolatedString, Literal, MethodCall, RValue, Select, Statement, Traverse};
+
+// the strings between the `%*` placeholders of a format string, `%%` is a percent sign.
+// none if the format string has any other placeholder
+fn split_format_string(format: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut strings = vec![Vec::new()];
+    let mut bytes = format.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == b'%' {
+            match bytes.next()? {
+                b'*' => strings.push(Vec::new()),
+                b'%' => strings.last_mut().unwrap().push(b'%'),
+                _ => return None,
+            }
+        } else {
+            strings.last_mut().unwrap().push(byte);
+        }
+    }
+    Some(strings)
+}
+
+// `("hello %*!"):format(name)` as `hello {name}!`
+fn interpolated_string(method_call: &MethodCall) -> Option<InterpolatedString> {
+    let RValue::Literal(Literal::String(format)) = &*method_call.value else {
+        return None;
+    };
+    // luau compiles interpolated strings without values to plain strings
+    if method_call.method != "format" || method_call.arguments.is_empty() {
+        return None;
+    }
+    let strings = split_format_string(format)?;
+    if strings.len() != method_call.arguments.len() + 1 {
+        return None;
+    }
+    Some(InterpolatedString::new(
+        strings,
+        method_call.arguments.clone(),
+    ))
+}
+
+// luau compiles interpolated strings to calls to string.format with `%*` for every value,
+// this turns them back into interpolated strings. closures are left alone, their bodies
+// are handled on their own
+pub fn reconstruct_interpolated_strings(block: &mut Block) {
+    for statement in &mut block.0 {
+        statement.traverse_rvalues(&mut |rvalue| {
+            if let RValue::MethodCall(method_call)
+            | RValue::Select(Select::MethodCall(method_call)) = rvalue
+                && let Some(interpolated_string) = interpolated_string(method_call)
+            {
+                *rvalue = interpolated_string.into();
+            }
+        });
+        match statement {
+            Statement::If(r#if) => {
+                reconstruct_interpolated_strings(&mut r#if.then_block.lock());
+                reconstruct_interpolated_strings(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                reconstruct_interpolated_strings(&mut r#while.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                reconstruct_interpolated_strings(&mut repeat.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                reconstruct_interpolated_strings(&mut numeric_for.block.lock());
+            }
+            Statement::GenericFor(generic_for) => {
+                reconstruct_interpolated_strings(&mut generic_for.block.lock());
+            }
+            _ => {}
+        }
+    }
+}
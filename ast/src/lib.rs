@@ -18,17 +18,22 @@ mod r#break;
 mod call;
 mod close;
 mod closure;
+pub mod compound_assignments;
 mod r#continue;
 mod r#for;
 pub mod formatter;
 mod global;
 mod goto;
 mod r#if;
+mod if_expression;
 mod index;
+mod interpolated_string;
+pub mod interpolated_strings;
 mod literal;
 mod local;
 //mod name_gen;
 pub mod local_declarations;
+pub mod method_definitions;
 pub mod name_locals;
 mod repeat;
 pub mod replace_locals;
@@ -49,7 +54,9 @@ pub use close::*;
 pub use closure::*;
 pub use global::*;
 pub use goto::*;
+pub use if_expression::*;
 pub use index::*;
+pub use interpolated_string::*;
 pub use literal::*;
 pub use local::*;
 pub use r#break::*;
@@ -105,6 +112,8 @@ pub enum RValue {
     Binary(Binary),
     Closure(Closure),
     Select(Select),
+    IfExpression(IfExpression),
+    InterpolatedString(InterpolatedString),
 }
 
 impl type_system::Infer for RValue {
@@ -118,6 +127,7 @@ impl type_system::Infer for RValue {
             RValue::Index(_) => Type::Any,
             RValue::Unary(_) => Type::Any,
             RValue::Binary(_) => Type::Any,
+            RValue::IfExpression(_) => Type::Any,
             RValue::Closure(closure) => closure.infer(system),
             _ => Type::VarArg,
         }
@@ -132,6 +142,7 @@ impl<'a: 'b, 'b> Reduce for RValue {
             Self::Literal(literal) => literal.reduce(),
             Self::Table(table) => table.reduce(),
             Self::Closure(closure) => closure.reduce(),
+            Self::IfExpression(if_expression) => if_expression.reduce(),
             other => other,
         }
     }
@@ -143,6 +154,7 @@ impl<'a: 'b, 'b> Reduce for RValue {
             Self::Literal(literal) => literal.reduce_condition(),
             Self::Table(table) => table.reduce_condition(),
             Self::Closure(closure) => closure.reduce_condition(),
+            Self::IfExpression(if_expression) => if_expression.reduce_condition(),
             other => other,
         }
     }
@@ -153,9 +165,13 @@ impl RValue {
         match self {
             Self::Binary(binary) => binary.precedence(),
             Self::Unary(unary) => unary.precedence(),
+            // operands that are if expressions always need parentheses
+            Self::IfExpression(_) => 0,
             RValue::Literal(Literal::Number(n)) if n.is_finite() && n.is_sign_negative() => {
                 return 7;
             }
+            RValue::Literal(Literal::Int64(n)) if n.is_negative() => 7,
+            RValue::Literal(Literal::Imaginary(n)) if n.is_sign_negative() => 7,
             _ => 9,
         }
     }
@@ -185,6 +201,10 @@ impl fmt::Display for RValue {
             RValue::Binary(binary) => write!(f, "{}", binary),
             RValue::Closure(closure) => write!(f, "{}", closure),
             RValue::Select(select) => write!(f, "{}", select),
+            RValue::IfExpression(if_expression) => write!(f, "{}", if_expression),
+            RValue::InterpolatedString(interpolated_string) => {
+                write!(f, "{}", interpolated_string)
+            }
         }
     }
 }
@@ -265,6 +285,7 @@ pub enum Statement {
     Call(Call),
     MethodCall(MethodCall),
     Assign(Assign),
+    CompoundAssign(CompoundAssign),
     If(If),
     Goto(Goto),
     Label(Label),
@@ -312,6 +333,7 @@ impl fmt::Display for Statement {
             Statement::Call(call) => write!(f, "{}", call),
             Statement::MethodCall(method_call) => write!(f, "{}", method_call),
             Statement::Assign(assign) => write!(f, "{}", assign),
+            Statement::CompoundAssign(compound_assign) => write!(f, "{}", compound_assign),
             // TODO: STYLE: replace all `if_` with `r#if`, etc
             Statement::If(if_) => write!(f, "{}", if_),
             Statement::Goto(goto) => write!(f, "{}", goto),
@@ -355,6 +377,6 @@ impl DerefMut for Block {
 
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter::format(self, f, Default::default())
+        Formatter::format(self, f, Default::default(), Default::default(), None)
     }
 }
@@ -14,6 +14,12 @@ pub enum Literal {
     Number(f64),
     String(Vec<u8>),
     Vector(f32, f32, f32),
+    // LuaJIT 64-bit integer cdata (`1LL`, `1ULL`)
+    Int64(i64),
+    UInt64(u64),
+    // LuaJIT complex cdata with no real part (`2i`)
+    #[from(ignore)]
+    Imaginary(f64),
 }
 
 impl Reduce for Literal {
@@ -27,7 +33,10 @@ impl Reduce for Literal {
             Literal::Boolean(true)
             | Literal::Number(_)
             | Literal::String(_)
-            | Literal::Vector(..) => true,
+            | Literal::Vector(..)
+            | Literal::Int64(_)
+            | Literal::UInt64(_)
+            | Literal::Imaginary(_) => true,
         })
         .into()
     }
@@ -41,6 +50,8 @@ impl Infer for Literal {
             Literal::Number(_) => Type::Number,
             Literal::String(_) => Type::String,
             Literal::Vector(..) => Type::Vector,
+            // TODO: cdata type
+            Literal::Int64(_) | Literal::UInt64(_) | Literal::Imaginary(_) => Type::Any,
         }
     }
 }
@@ -71,6 +82,7 @@ impl fmt::Display for Literal {
                 let printed = buffer.format_finite(value);
                 write!(f, "{}", printed.strip_suffix(".0").unwrap_or(printed))
             }
+            &Literal::Imaginary(value) => write!(f, "{}i", Literal::Number(value)),
             Literal::String(value) => {
                 write!(
                     f,
@@ -79,6 +91,8 @@ impl fmt::Display for Literal {
                 )
             }
             Literal::Vector(x, y, z) => write!(f, "Vector3.new({}, {}, {})", x, y, z),
+            Literal::Int64(value) => write!(f, "{}LL", value),
+            Literal::UInt64(value) => write!(f, "{}ULL", value),
         }
     }
 }
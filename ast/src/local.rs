@@ -1,21 +1,42 @@
 use crate::{type_system::Infer, SideEffects, Traverse, Type, TypeSystem};
 use by_address::ByAddress;
-use derive_more::From;
 use enum_dispatch::enum_dispatch;
-use nohash_hasher::NoHashHasher;
 use parking_lot::Mutex;
 use std::{
+    cell::Cell,
+    cmp::Ordering,
     fmt::{self, Display},
     hash::{Hash, Hasher},
 };
 use triomphe::Arc;
 
-#[derive(Debug, Default, From, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
-pub struct Local(pub Option<String>);
+// where a local is defined in the bytecode, names derived from this stay the same when
+// unrelated parts of the chunk change
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq, Hash)]
+pub struct Origin {
+    pub register: usize,
+    // the pc of the block and the position in it of the definition, 0 is the block's
+    // parameters and `n` is statement `n - 1`. none for parameters and the initial value
+    pub definition: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, PartialOrd, Ord, Eq, Hash)]
+pub struct Local(pub Option<String>, pub Option<Origin>);
 
 impl Local {
     pub fn new(name: Option<String>) -> Self {
-        Self(name)
+        Self(name, None)
+    }
+
+    // the local every definition of a register is made to before ssa construction
+    pub fn register(register: usize) -> Self {
+        Self(
+            None,
+            Some(Origin {
+                register,
+                definition: None,
+            }),
+        )
     }
 }
 
@@ -28,8 +49,61 @@ impl fmt::Display for Local {
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct RcLocal(pub ByAddress<Arc<Mutex<Local>>>);
+thread_local! {
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+// the id the next local created on this thread gets
+pub fn next_local_id() -> u64 {
+    NEXT_ID.get()
+}
+
+// ids have to be unique among locals that end up in the same collection. setting the counter
+// before work that creates locals makes their ids independent of what else ran on the thread
+// before
+pub fn set_next_local_id(id: u64) {
+    NEXT_ID.set(id);
+}
+
+// locals are equal if they're the same allocation, but they're hashed and ordered by when they
+// were created so that iterating over a hash map of locals or sorting them gives the same
+// order every run, no matter where the locals were allocated. locals with the same id only
+// happen if the counter was reset carelessly, they're ordered by address so `Ord` stays
+// consistent with `Eq`
+#[derive(Debug, Clone)]
+pub struct RcLocal(pub ByAddress<Arc<Mutex<Local>>>, u64);
+
+impl Default for RcLocal {
+    fn default() -> Self {
+        Self::new(Local::default())
+    }
+}
+
+impl PartialEq for RcLocal {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Eq for RcLocal {}
+
+impl Hash for RcLocal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.1.hash(state);
+    }
+}
+
+impl PartialOrd for RcLocal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RcLocal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.1.cmp(&other.1).then_with(|| self.0.cmp(&other.0))
+    }
+}
 
 impl Infer for RcLocal {
     fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type {
@@ -41,11 +115,7 @@ impl Display for RcLocal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.0 .0.lock().0 {
             Some(name) => write!(f, "{}", name),
-            None => {
-                let mut hasher = NoHashHasher::<u8>::default();
-                self.hash(&mut hasher);
-                write!(f, "UNNAMED_{}", hasher.finish())
-            }
+            None => write!(f, "UNNAMED_{}", self.1),
         }
     }
 }
@@ -56,7 +126,9 @@ impl Traverse for RcLocal {}
 
 impl RcLocal {
     pub fn new(local: Local) -> Self {
-        Self(ByAddress(Arc::new(Mutex::new(local))))
+        let id = NEXT_ID.get();
+        NEXT_ID.set(id + 1);
+        Self(ByAddress(Arc::new(Mutex::new(local))), id)
     }
 }
 
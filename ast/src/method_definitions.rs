@@ -0,0 +1,162 @@
+use std::fmt;
+
+use rustc_hash::FxHashSet;
+
+use crate::{
+    formatter::Formatter, Block, LValue, Literal, LocalRw, RValue, RcLocal, Select, Statement,
+    Traverse,
+};
+
+fn is_self(local: &RcLocal) -> bool {
+    local.0 .0.lock().0.as_deref() == Some("self")
+}
+
+// calls `callback` with every nested block of a statement, not including closures
+fn nested_blocks(statement: &Statement, callback: &mut impl FnMut(&mut Block)) {
+    match statement {
+        Statement::If(r#if) => {
+            callback(&mut r#if.then_block.lock());
+            callback(&mut r#if.else_block.lock());
+        }
+        Statement::While(r#while) => callback(&mut r#while.block.lock()),
+        Statement::Repeat(repeat) => callback(&mut repeat.block.lock()),
+        Statement::NumericFor(numeric_for) => callback(&mut numeric_for.block.lock()),
+        Statement::GenericFor(generic_for) => callback(&mut generic_for.block.lock()),
+        _ => {}
+    }
+}
+
+#[derive(Default)]
+struct Uses {
+    // names called as methods, ex. `a:b()`
+    method_calls: FxHashSet<Vec<u8>>,
+    // names read from a table, ex. `a.b()` or `c = a.b`
+    indexed: FxHashSet<Vec<u8>>,
+    // whether a table is read with a key that isn't a constant, ex. `a[k]`, which could read
+    // any name
+    dynamic_index: bool,
+}
+
+impl Uses {
+    fn collect(&mut self, block: &mut Block) {
+        for statement in &mut block.0 {
+            if let Statement::MethodCall(method_call) = statement {
+                self.method_calls
+                    .insert(method_call.method.as_bytes().to_vec());
+            }
+            statement.traverse_rvalues(&mut |rvalue| match rvalue {
+                RValue::MethodCall(method_call)
+                | RValue::Select(Select::MethodCall(method_call)) => {
+                    self.method_calls
+                        .insert(method_call.method.as_bytes().to_vec());
+                }
+                RValue::Index(index) => match &index.right {
+                    box RValue::Literal(Literal::String(key)) => {
+                        self.indexed.insert(key.clone());
+                    }
+                    box RValue::Literal(_) => {}
+                    _ => self.dynamic_index = true,
+                },
+                RValue::Closure(closure) => self.collect(&mut closure.function.lock().body),
+                _ => {}
+            });
+            nested_blocks(statement, &mut |block| self.collect(block));
+        }
+    }
+
+    // whether `name` is only ever called as a method in the chunk. a function that is also
+    // read as a value, ex. `local f = a.b; f(x)`, might be called with anything as its first
+    // argument
+    fn only_method_calls(&self, name: &[u8]) -> bool {
+        self.method_calls.contains(name) && !self.indexed.contains(name) && !self.dynamic_index
+    }
+}
+
+// whether naming `parameter` `self` would make `block` refer to a different value, because it
+// uses the global `self` or another local named `self`, ex. the `self` of an enclosing method
+fn self_is_used(block: &mut Block, parameter: &RcLocal) -> bool {
+    let mut used = false;
+    for statement in &mut block.0 {
+        used |= statement
+            .values_read()
+            .into_iter()
+            .chain(statement.values_written())
+            .any(|local| local != parameter && is_self(local));
+        statement.traverse_rvalues(&mut |rvalue| match rvalue {
+            RValue::Global(global) => used |= global.0 == b"self",
+            RValue::Closure(closure) => {
+                used |= self_is_used(&mut closure.function.lock().body, parameter)
+            }
+            _ => {}
+        });
+        if let Statement::Assign(assign) = statement {
+            used |= assign
+                .left
+                .iter()
+                .any(|l| matches!(l, LValue::Global(global) if global.0 == b"self"));
+        }
+        nested_blocks(statement, &mut |block| {
+            used |= self_is_used(block, parameter)
+        });
+        if used {
+            break;
+        }
+    }
+    used
+}
+
+// `a.b = function(self, ...)` as `function a:b(...)`
+fn mark_method(statement: &Statement, uses: Option<&Uses>) {
+    let Statement::Assign(assign) = statement else {
+        return;
+    };
+    if assign.prefix || assign.left.len() != 1 || assign.right.len() != 1 {
+        return;
+    }
+    let (LValue::Index(index), RValue::Closure(closure)) = (&assign.left[0], &assign.right[0])
+    else {
+        return;
+    };
+    let box RValue::Literal(Literal::String(name)) = &index.right else {
+        return;
+    };
+    if !Formatter::<fmt::Formatter>::is_function_name(index) {
+        return;
+    }
+    let mut function = closure.function.lock();
+    let Some(parameter) = function.parameters.first().cloned() else {
+        return;
+    };
+    if (is_self(&parameter) || uses.is_some_and(|uses| uses.only_method_calls(name)))
+        && !self_is_used(&mut function.body, &parameter)
+    {
+        parameter.0 .0.lock().0 = Some("self".to_string());
+        function.is_method = true;
+    }
+}
+
+fn mark_methods(block: &mut Block, uses: Option<&Uses>) {
+    for statement in &mut block.0 {
+        // enclosing methods are marked first, so that their `self` is seen by nested ones
+        mark_method(statement, uses);
+        statement.traverse_rvalues(&mut |rvalue| {
+            if let RValue::Closure(closure) = rvalue {
+                mark_methods(&mut closure.function.lock().body, uses);
+            }
+        });
+        nested_blocks(statement, &mut |block| mark_methods(block, uses));
+    }
+}
+
+// functions assigned to a table whose first parameter is named `self` are written with method
+// syntax. with `infer_methods`, so are functions whose name is only ever called as a method,
+// which renames their first parameter to `self`. this has to run on the whole chunk after
+// upvalues are linked and before locals are named
+pub fn reconstruct_method_definitions(block: &mut Block, infer_methods: bool) {
+    let uses = infer_methods.then(|| {
+        let mut uses = Uses::default();
+        uses.collect(block);
+        uses
+    });
+    mark_methods(block, uses.as_ref());
+}
@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use indexmap::IndexSet;
 use rustc_hash::FxHashSet;
 use triomphe::Arc;
 
@@ -35,7 +38,8 @@ impl Namer {
             statement.post_traverse_values(&mut |value| -> Option<()> {
                 if let itertools::Either::Right(RValue::Closure(closure)) = value {
                     let mut function = closure.function.lock();
-                    for param in &function.parameters {
+                    // the `self` of a method keeps its name
+                    for param in function.parameters.iter().skip(function.is_method as usize) {
                         self.name_local("p", param);
                     }
                     self.name_locals(&mut function.body);
@@ -116,6 +120,7 @@ impl Namer {
     }
 }
 
+// `rename` names every local, not just those that don't have a name yet
 pub fn name_locals(block: &mut Block, rename: bool) {
     let mut namer = Namer {
         rename,
@@ -125,3 +130,84 @@ pub fn name_locals(block: &mut Block, rename: bool) {
     namer.find_upvalues(block);
     namer.name_locals(block);
 }
+
+// locals declared directly in a block or the blocks nested in it, not in closures
+fn declared_locals(block: &Block, locals: &mut IndexSet<RcLocal>) {
+    for statement in &block.0 {
+        match statement {
+            Statement::Assign(assign) if assign.prefix => {
+                locals.extend(assign.left.iter().filter_map(|l| l.as_local()).cloned());
+            }
+            Statement::If(r#if) => {
+                declared_locals(&r#if.then_block.lock(), locals);
+                declared_locals(&r#if.else_block.lock(), locals);
+            }
+            Statement::While(r#while) => declared_locals(&r#while.block.lock(), locals),
+            Statement::Repeat(repeat) => declared_locals(&repeat.block.lock(), locals),
+            Statement::NumericFor(numeric_for) => {
+                locals.insert(numeric_for.counter.clone());
+                declared_locals(&numeric_for.block.lock(), locals);
+            }
+            Statement::GenericFor(generic_for) => {
+                locals.extend(generic_for.res_locals.iter().cloned());
+                declared_locals(&generic_for.block.lock(), locals);
+            }
+            _ => {}
+        }
+    }
+}
+
+// names the parameters and locals of one function after where they're defined in the bytecode,
+// so that a name only changes when the code around its definition does:
+// `{function}_p{index}` for parameters, `{function}_r{register}` for the value a register has
+// on entry and `{function}_r{register}_pc{pc}` for definitions in the block starting at `pc`.
+// a register defined more than once in the same block gets the position of the definition in
+// the block as well, and locals defined at the same position are numbered after the first.
+// locals that don't come from a register are numbered, `{function}_t{n}`
+pub fn name_locals_stable(function: &str, parameters: &[RcLocal], block: &Block) {
+    for (index, parameter) in parameters.iter().enumerate() {
+        parameter.0 .0.lock().0 = Some(format!("{}_p{}", function, index));
+    }
+    let mut locals = IndexSet::new();
+    declared_locals(block, &mut locals);
+    let mut definitions = BTreeMap::<_, Vec<_>>::new();
+    let mut temporaries = 0;
+    for local in locals {
+        if parameters.contains(&local) {
+            continue;
+        }
+        let origin = local.0 .0.lock().1;
+        match origin {
+            Some(origin) => definitions
+                .entry((origin.register, origin.definition.map(|(pc, _)| pc)))
+                .or_default()
+                .push((origin.definition, local)),
+            None => {
+                temporaries += 1;
+                local.0 .0.lock().0 = Some(format!("{}_t{}", function, temporaries));
+            }
+        }
+    }
+    let mut used_names = FxHashSet::default();
+    for ((register, pc), mut locals) in definitions {
+        // stable, locals defined at the same position are named in the order they're declared
+        locals.sort_by_key(|&(definition, _)| definition);
+        let base = match pc {
+            Some(pc) => format!("{}_r{}_pc{}", function, register, pc),
+            None => format!("{}_r{}", function, register),
+        };
+        let single = locals.len() == 1;
+        for (definition, local) in locals {
+            let mut name = match definition {
+                Some((_, position)) if !single => format!("{}_{}", base, position),
+                _ => base.clone(),
+            };
+            let mut n = 1;
+            while !used_names.insert(name.clone()) {
+                n += 1;
+                name = format!("{}_{}", base, n);
+            }
+            local.0 .0.lock().0 = Some(name);
+        }
+    }
+}
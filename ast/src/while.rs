@@ -50,11 +50,6 @@ impl LocalRw for While {
 
 impl fmt::Display for While {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        Formatter {
-            indentation_level: 0,
-            indentation_mode: Default::default(),
-            output: f,
-        }
-        .format_while(self)
+        Formatter::new(f).format_while(self)
     }
 }
@@ -0,0 +1,125 @@
+use ast::{
+    compound_assignments::reconstruct_compound_assignments, Assign, Binary, BinaryOperation, Block,
+    Call, Global, If, Index, Literal, Local, RValue, RcLocal, Statement,
+};
+
+fn local(name: &str) -> RcLocal {
+    RcLocal::new(Local::new(Some(name.to_string())))
+}
+
+fn global(name: &str) -> RValue {
+    Global::new(name.as_bytes().to_vec()).into()
+}
+
+fn number(value: f64) -> RValue {
+    Literal::Number(value).into()
+}
+
+fn binary(left: RValue, operation: BinaryOperation, right: RValue) -> RValue {
+    Binary::new(left, right, operation).into()
+}
+
+fn assign(left: impl Into<RValue>, right: RValue) -> Statement {
+    let left = left.into().into_lvalue().unwrap();
+    Assign::new(vec![left], vec![right]).into()
+}
+
+fn reconstruct(statements: Vec<Statement>) -> String {
+    let mut block = Block(statements);
+    reconstruct_compound_assignments(&mut block);
+    block.to_string()
+}
+
+#[test]
+fn compound_assignments() {
+    let a = local("a");
+    let b = local("b");
+    let t = local("t");
+    let field = || RValue::from(Index::new(t.clone().into(), Literal::from("x").into()));
+    assert_eq!(
+        reconstruct(vec![
+            assign(
+                a.clone(),
+                binary(a.clone().into(), BinaryOperation::Add, number(1.0))
+            ),
+            assign(
+                b.clone(),
+                binary(b.clone().into(), BinaryOperation::Concat, a.clone().into())
+            ),
+            assign(
+                global("g"),
+                binary(global("g"), BinaryOperation::Pow, number(2.0))
+            ),
+            assign(
+                field(),
+                binary(field(), BinaryOperation::Mul, b.clone().into())
+            ),
+        ]),
+        "a += 1\nb ..= a\ng ^= 2\nt.x *= b"
+    );
+}
+
+#[test]
+fn nested_blocks() {
+    let a = local("a");
+    let statement = If::new(
+        a.clone().into(),
+        Block(vec![assign(
+            a.clone(),
+            binary(a.clone().into(), BinaryOperation::Sub, number(1.0)),
+        )]),
+        Block::default(),
+    );
+    assert_eq!(
+        reconstruct(vec![statement.into()]),
+        "if a then\n\ta -= 1\nend"
+    );
+}
+
+#[test]
+fn not_compound_assignments() {
+    let a = local("a");
+    let b = local("b");
+    let call = || RValue::from(Call::new(global("f"), Vec::new()));
+    let mut declaration = Assign::new(
+        vec![a.clone().into()],
+        vec![binary(a.clone().into(), BinaryOperation::Add, number(1.0))],
+    );
+    declaration.prefix = true;
+    let statements = vec![
+        // the operand order matters for metamethods and concatenation
+        assign(
+            a.clone(),
+            binary(b.clone().into(), BinaryOperation::Add, a.clone().into()),
+        ),
+        // `a` would be read after it's changed
+        assign(
+            a.clone(),
+            binary(a.clone().into(), BinaryOperation::Add, a.clone().into()),
+        ),
+        assign(
+            a.clone(),
+            binary(a.clone().into(), BinaryOperation::And, b.clone().into()),
+        ),
+        assign(
+            a.clone(),
+            binary(
+                a.clone().into(),
+                BinaryOperation::LessThan,
+                b.clone().into(),
+            ),
+        ),
+        // `f()` would only be called once
+        assign(
+            Index::new(call(), Literal::from("x").into()),
+            binary(
+                Index::new(call(), Literal::from("x").into()).into(),
+                BinaryOperation::Add,
+                number(1.0),
+            ),
+        ),
+        declaration.into(),
+    ];
+    let expected = Block(statements.clone()).to_string();
+    assert_eq!(reconstruct(statements), expected);
+}
@@ -0,0 +1,66 @@
+use ast::{
+    interpolated_strings::reconstruct_interpolated_strings, Block, Call, Global, Literal, Local,
+    MethodCall, RValue, RcLocal, Return, Statement, Table,
+};
+
+fn local(name: &str) -> RValue {
+    RcLocal::new(Local::new(Some(name.to_string()))).into()
+}
+
+fn string(value: &str) -> RValue {
+    Literal::from(value).into()
+}
+
+fn format(receiver: RValue, method: &str, arguments: Vec<RValue>) -> RValue {
+    MethodCall::new(receiver, method.to_string(), arguments).into()
+}
+
+fn reconstruct(values: Vec<RValue>) -> String {
+    let mut block = Block(vec![Return::new(values).into()]);
+    reconstruct_interpolated_strings(&mut block);
+    block.to_string()
+}
+
+#[test]
+fn interpolated_strings() {
+    assert_eq!(
+        reconstruct(vec![
+            format(string("hello %*!"), "format", vec![local("name")]),
+            format(string("%*%*"), "format", vec![local("a"), local("b")]),
+            format(
+                string("100%% {%*} `%*`"),
+                "format",
+                vec![local("a"), local("b")]
+            ),
+            format(string("%*\n"), "format", vec![Table::default().into()]),
+        ]),
+        r"return `hello {name}!`, `{a}{b}`, `100% \{{a}\} \`{b}\``, `{({})}\n`"
+    );
+}
+
+#[test]
+fn nested_values() {
+    let call = Call::new(
+        Global::new(b"print".to_vec()).into(),
+        vec![format(string("%* items"), "format", vec![local("n")])],
+    );
+    let mut block = Block(vec![Statement::from(call)]);
+    reconstruct_interpolated_strings(&mut block);
+    assert_eq!(block.to_string(), "print(`{n} items`)");
+}
+
+#[test]
+fn not_interpolated_strings() {
+    let values = vec![
+        // luau only emits `%*`
+        format(string("%d"), "format", vec![local("a")]),
+        format(string("%*"), "format", Vec::new()),
+        format(string("%* %*"), "format", vec![local("a")]),
+        format(string("%*"), "rep", vec![local("a")]),
+        format(local("s"), "format", vec![local("a")]),
+        // a trailing `%` is malformed
+        format(string("%*%"), "format", vec![local("a")]),
+    ];
+    let expected = Block(vec![Return::new(values.clone()).into()]).to_string();
+    assert_eq!(reconstruct(values), expected);
+}
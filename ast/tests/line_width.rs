@@ -0,0 +1,80 @@
+use ast::{
+    formatter::{Formatter, IndentationMode, TableLayout},
+    Binary, BinaryOperation, Block, Call, Global, If, MethodCall, RValue, Select, Statement,
+};
+
+fn global(name: &str) -> RValue {
+    Global::new(name.as_bytes().to_vec()).into()
+}
+
+fn format(statement: Statement, max_line_width: Option<usize>) -> String {
+    let mut output = String::new();
+    Formatter::format(
+        &Block(vec![statement]),
+        &mut output,
+        IndentationMode::default(),
+        TableLayout::default(),
+        max_line_width,
+    )
+    .unwrap();
+    output
+}
+
+// `x = a .. b .. c`, 15 columns
+fn concat() -> Statement {
+    // concatenation is right associative
+    let right = Binary::new(global("b"), global("c"), BinaryOperation::Concat);
+    let value = Binary::new(global("a"), right.into(), BinaryOperation::Concat);
+    ast::Assign::new(vec![Global::new(b"x".to_vec()).into()], vec![value.into()]).into()
+}
+
+// `f(a, b)`, 7 columns
+fn call() -> Statement {
+    Call::new(global("f"), vec![global("a"), global("b")]).into()
+}
+
+// `s:a():b()`, 9 columns
+fn method_chain() -> Statement {
+    let first = MethodCall::new(global("s"), "a".to_string(), Vec::new());
+    MethodCall::new(
+        Select::MethodCall(first).into(),
+        "b".to_string(),
+        Vec::new(),
+    )
+    .into()
+}
+
+#[test]
+fn no_max_line_width() {
+    assert_eq!(format(concat(), None), "x = a .. b .. c");
+    assert_eq!(format(call(), None), "f(a, b)");
+}
+
+#[test]
+fn binary_chains() {
+    assert_eq!(format(concat(), Some(15)), "x = a .. b .. c");
+    assert_eq!(format(concat(), Some(14)), "x = a\n\t.. b\n\t.. c");
+}
+
+#[test]
+fn arguments() {
+    assert_eq!(format(call(), Some(7)), "f(a, b)");
+    assert_eq!(format(call(), Some(6)), "f(\n\ta,\n\tb\n)");
+}
+
+#[test]
+fn method_chains() {
+    assert_eq!(format(method_chain(), Some(9)), "s:a():b()");
+    assert_eq!(format(method_chain(), Some(8)), "s\n\t:a()\n\t:b()");
+}
+
+#[test]
+fn indentation() {
+    // a tab is counted as 4 columns, so `\tf(a, b)` is 11 columns wide
+    let statement = || If::new(global("c"), Block(vec![call()]), Block::default()).into();
+    assert_eq!(format(statement(), Some(11)), "if c then\n\tf(a, b)\nend");
+    assert_eq!(
+        format(statement(), Some(10)),
+        "if c then\n\tf(\n\t\ta,\n\t\tb\n\t)\nend"
+    );
+}
@@ -0,0 +1,27 @@
+use std::cmp::Ordering;
+
+use ast::{Local, RcLocal};
+
+#[test]
+fn same_id() {
+    ast::set_next_local_id(0);
+    let a = RcLocal::new(Local::default());
+    ast::set_next_local_id(0);
+    let b = RcLocal::new(Local::default());
+    assert_ne!(a, b);
+    assert_ne!(a.cmp(&b), Ordering::Equal);
+    assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+    assert_eq!(a.cmp(&a.clone()), Ordering::Equal);
+}
+
+#[test]
+fn creation_order() {
+    ast::set_next_local_id(0);
+    let locals = (0..4)
+        .map(|_| RcLocal::new(Local::default()))
+        .collect::<Vec<_>>();
+    let mut sorted = locals.clone();
+    sorted.reverse();
+    sorted.sort();
+    assert_eq!(sorted, locals);
+}
@@ -0,0 +1,164 @@
+use by_address::ByAddress;
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+use ast::{
+    method_definitions::reconstruct_method_definitions, Assign, Block, Call, Closure, Function,
+    Global, Index, Literal, Local, MethodCall, RValue, RcLocal, Return, Statement,
+};
+
+fn local(name: &str) -> RcLocal {
+    RcLocal::new(Local::new(Some(name.to_string())))
+}
+
+fn global(name: &str) -> RValue {
+    Global::new(name.as_bytes().to_vec()).into()
+}
+
+fn index(left: RValue, key: &str) -> RValue {
+    Index::new(left, Literal::from(key).into()).into()
+}
+
+fn closure(parameters: Vec<RcLocal>, body: Vec<Statement>) -> RValue {
+    Closure {
+        function: ByAddress(Arc::new(Mutex::new(Function {
+            parameters,
+            body: Block(body),
+            ..Default::default()
+        }))),
+        upvalues: Vec::new(),
+    }
+    .into()
+}
+
+// `t.{name} = function({parameter}) return {value} end`
+fn define(name: &str, parameter: &RcLocal, value: RValue) -> Statement {
+    Assign::new(
+        vec![index(global("t"), name).into_lvalue().unwrap()],
+        vec![closure(
+            vec![parameter.clone()],
+            vec![Return::new(vec![value]).into()],
+        )],
+    )
+    .into()
+}
+
+fn method_call(name: &str) -> Statement {
+    MethodCall::new(global("t"), name.to_string(), Vec::new()).into()
+}
+
+fn reconstruct(statements: Vec<Statement>) -> String {
+    let mut block = Block(statements);
+    reconstruct_method_definitions(&mut block, true);
+    block.to_string()
+}
+
+#[test]
+fn self_parameter() {
+    let parameter = local("self");
+    assert_eq!(
+        reconstruct(vec![define(
+            "get",
+            &parameter,
+            index(parameter.clone().into(), "v")
+        )]),
+        "function t:get()\n\treturn self.v\nend"
+    );
+}
+
+#[test]
+fn only_called_as_method() {
+    let parameter = local("p");
+    assert_eq!(
+        reconstruct(vec![
+            define("get", &parameter, index(parameter.clone().into(), "v")),
+            method_call("get"),
+        ]),
+        "function t:get()\n\treturn self.v\nend\nt:get()"
+    );
+}
+
+#[test]
+fn not_inferred() {
+    let parameter = local("p");
+    let statements = vec![
+        define("get", &parameter, index(parameter.clone().into(), "v")),
+        method_call("get"),
+    ];
+    let expected = Block(statements.clone()).to_string();
+    let mut block = Block(statements);
+    reconstruct_method_definitions(&mut block, false);
+    assert_eq!(block.to_string(), expected);
+    assert!(expected.starts_with("function t.get(p)\n"));
+}
+
+#[test]
+fn not_methods() {
+    let p = local("p");
+    let statements = vec![
+        // `get` is also read from the table
+        define("get", &p, p.clone().into()),
+        method_call("get"),
+        Return::new(vec![index(global("t"), "get")]).into(),
+    ];
+    let expected = Block(statements.clone()).to_string();
+    assert_eq!(reconstruct(statements), expected);
+    assert!(expected.starts_with("function t.get(p)\n"));
+}
+
+#[test]
+fn global_self() {
+    // naming `p` `self` would hide the global
+    let p = local("p");
+    let statements = vec![define("get", &p, global("self")), method_call("get")];
+    let expected = Block(statements.clone()).to_string();
+    assert_eq!(reconstruct(statements), expected);
+    assert!(expected.starts_with("function t.get(p)\n\treturn self\nend"));
+}
+
+#[test]
+fn shadowed_self() {
+    // the inner function reads the `self` of the enclosing method, which a `self` parameter
+    // would shadow
+    let outer = local("self");
+    let inner = local("p");
+    let inner_function = Assign::new(
+        vec![index(outer.clone().into(), "inner").into_lvalue().unwrap()],
+        vec![closure(
+            vec![inner.clone()],
+            vec![Return::new(vec![outer.clone().into(), inner.clone().into()]).into()],
+        )],
+    );
+    let outer_function = Assign::new(
+        vec![index(global("t"), "outer").into_lvalue().unwrap()],
+        vec![closure(vec![outer.clone()], vec![inner_function.into()])],
+    );
+    assert_eq!(
+        reconstruct(vec![outer_function.into(), method_call("inner")]),
+        "function t:outer()\n\tfunction self.inner(p)\n\t\treturn self, p\n\tend\nend\nt:inner()"
+    );
+}
+
+#[test]
+fn aliased() {
+    // `local f = t.get; f(x)` and `local f = t[k]; f(x)` might pass anything as `p`
+    let p = local("p");
+    let f = local("f");
+    let call = || Call::new(f.clone().into(), vec![global("x")]).into();
+    for key in [Literal::from("get").into(), global("k")] {
+        let mut alias = Assign::new(
+            vec![f.clone().into()],
+            vec![Index::new(global("t"), key).into()],
+        );
+        alias.prefix = true;
+        let statements = vec![
+            define("get", &p, p.clone().into()),
+            method_call("get"),
+            alias.into(),
+            call(),
+        ];
+        let expected = Block(statements.clone()).to_string();
+        assert_eq!(reconstruct(statements), expected);
+        assert!(expected.starts_with("function t.get(p)\n"));
+    }
+}
@@ -0,0 +1,111 @@
+use ast::{
+    formatter::{Formatter, IndentationMode, TableLayout},
+    Assign, Block, Global, Literal, RValue, Table,
+};
+
+fn number(value: f64) -> RValue {
+    Literal::Number(value).into()
+}
+
+fn array(length: usize) -> Table {
+    Table((1..=length).map(|i| (None, number(i as f64))).collect())
+}
+
+fn format(table: Table, layout: TableLayout, indentation_mode: IndentationMode) -> String {
+    let block = Block(vec![Assign::new(
+        vec![Global::new(b"t".to_vec()).into()],
+        vec![table.into()],
+    )
+    .into()]);
+    let mut output = String::new();
+    Formatter::format(&block, &mut output, indentation_mode, layout, None).unwrap();
+    output
+}
+
+fn format_default(table: Table, layout: TableLayout) -> String {
+    format(table, layout, IndentationMode::default())
+}
+
+#[test]
+fn max_inline_entries() {
+    let layout = TableLayout::default();
+    assert_eq!(format_default(array(3), layout), "t = { 1, 2, 3 }");
+    assert_eq!(
+        format_default(array(4), layout),
+        "t = {\n\t1,\n\t2,\n\t3,\n\t4\n}"
+    );
+    assert_eq!(
+        format_default(
+            array(4),
+            TableLayout {
+                max_inline_entries: 4,
+                ..layout
+            }
+        ),
+        "t = { 1, 2, 3, 4 }"
+    );
+}
+
+#[test]
+fn max_inline_width() {
+    // the entries of `{ 1, 2, 3 }` are 7 columns wide
+    let layout = |width| TableLayout {
+        max_inline_width: Some(width),
+        ..Default::default()
+    };
+    assert_eq!(format_default(array(3), layout(7)), "t = { 1, 2, 3 }");
+    assert_eq!(
+        format_default(array(3), layout(6)),
+        "t = {\n\t1,\n\t2,\n\t3\n}"
+    );
+}
+
+#[test]
+fn keyed_entries() {
+    let keyed = || {
+        Table(vec![
+            (Some(Literal::from("x").into()), number(1.0)),
+            (Some(number(5.0)), number(2.0)),
+        ])
+    };
+    assert_eq!(
+        format_default(keyed(), TableLayout::default()),
+        "t = {\n\t[\"x\"] = 1,\n\t[5] = 2\n}"
+    );
+    assert_eq!(
+        format_default(
+            keyed(),
+            TableLayout {
+                split_keyed: false,
+                ..Default::default()
+            }
+        ),
+        "t = { [\"x\"] = 1, [5] = 2 }"
+    );
+}
+
+#[test]
+fn entries_per_line_and_trailing_comma() {
+    let layout = TableLayout {
+        entries_per_line: 2,
+        trailing_comma: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        format_default(array(5), layout),
+        "t = {\n\t1, 2,\n\t3, 4,\n\t5,\n}"
+    );
+    // inline tables never have a trailing comma
+    assert_eq!(format_default(array(2), layout), "t = { 1, 2 }");
+}
+
+#[test]
+fn nested_tables() {
+    // a table containing a table is always written over multiple lines, and nested tables are
+    // indented like blocks
+    let nested = Table(vec![(None, array(2).into()), (None, array(4).into())]);
+    assert_eq!(
+        format(nested, TableLayout::default(), IndentationMode::Spaces(2)),
+        "t = {\n  { 1, 2 },\n  {\n    1,\n    2,\n    3,\n    4\n  }\n}"
+    );
+}
@@ -13,23 +13,37 @@ use petgraph::{
     visit::{Bfs, Walker},
 };
 
-use crate::function::Function;
-
-fn arguments(args: &Vec<(ast::RcLocal, ast::RValue)>) -> String {
-    let mut s = String::new();
-    for (i, (local, new_local)) in args.iter().enumerate() {
-        use std::fmt::Write;
-        write!(s, "{} -> {}", local, new_local).unwrap();
-        if i + 1 != args.len() {
-            s.push('\n');
-        }
-    }
-    s
-}
+use crate::{
+    function::Function,
+    listing::{self, Names},
+};
 
 struct FunctionLabeller<'a> {
     function: &'a Function,
     counter: RefCell<usize>,
+    // locals are printed with these names instead of being named if there are any
+    names: Option<RefCell<&'a mut Names>>,
+}
+
+impl FunctionLabeller<'_> {
+    fn arguments(&self, args: &[(ast::RcLocal, ast::RValue)]) -> String {
+        let mut s = String::new();
+        for (i, (local, new_local)) in args.iter().enumerate() {
+            use std::fmt::Write;
+            match &self.names {
+                Some(names) => s.push_str(&listing::print_argument(
+                    local,
+                    new_local,
+                    &mut names.borrow_mut(),
+                )),
+                None => write!(s, "{} -> {}", local, new_local).unwrap(),
+            }
+            if i + 1 != args.len() {
+                s.push('\n');
+            }
+        }
+        s
+    }
 }
 
 impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FunctionLabeller<'a> {
@@ -44,8 +58,11 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FunctionLabeller<'a> {
         } else {
             ""
         };
-        dot::LabelText::LabelStr(
-            block
+        let statements = match &self.names {
+            Some(names) => listing::print_statements(&block.0, &mut names.borrow_mut())
+                .trim_end()
+                .to_string(),
+            None => block
                 .iter()
                 .map(|s| {
                     for local in s.values() {
@@ -58,10 +75,9 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FunctionLabeller<'a> {
                     }
                     s
                 })
-                .join("\n")
-                .into(),
-        )
-        .prefix_line(dot::LabelText::LabelStr(
+                .join("\n"),
+        };
+        dot::LabelText::LabelStr(statements.into()).prefix_line(dot::LabelText::LabelStr(
             format!("{} {}", n.index(), prefix).into(),
         ))
     }
@@ -70,10 +86,10 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FunctionLabeller<'a> {
         let edge = self.function.graph().edge_weight(*e).unwrap();
         match edge.branch_type {
             crate::block::BranchType::Unconditional => {
-                dot::LabelText::LabelStr(arguments(&edge.arguments).into())
+                dot::LabelText::LabelStr(self.arguments(&edge.arguments).into())
             }
             crate::block::BranchType::Then => {
-                let arguments = arguments(&edge.arguments);
+                let arguments = self.arguments(&edge.arguments);
                 if !arguments.is_empty() {
                     dot::LabelText::LabelStr(format!("t\n{}", arguments).into())
                 } else {
@@ -81,7 +97,7 @@ impl<'a> Labeller<'a, NodeIndex, EdgeIndex> for FunctionLabeller<'a> {
                 }
             }
             crate::block::BranchType::Else => {
-                let arguments = arguments(&edge.arguments);
+                let arguments = self.arguments(&edge.arguments);
                 if !arguments.is_empty() {
                     dot::LabelText::LabelStr(format!("e\n{}", arguments).into())
                 } else {
@@ -127,6 +143,24 @@ pub fn render_to<W: Write>(function: &Function, output: &mut W) -> std::io::Resu
         &FunctionLabeller {
             function,
             counter: RefCell::new(1),
+            names: None,
+        },
+        output,
+    )
+}
+
+// like `render_to`, but locals are printed like in the listing (see `listing::print_with_names`)
+// and aren't named
+pub fn render_with_names_to<W: Write>(
+    function: &Function,
+    names: &mut Names,
+    output: &mut W,
+) -> std::io::Result<()> {
+    dot::render(
+        &FunctionLabeller {
+            function,
+            counter: RefCell::new(1),
+            names: Some(RefCell::new(names)),
         },
         output,
     )
@@ -1,20 +1,33 @@
 use ast::{LocalRw, RcLocal};
 use contracts::requires;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
 
 use petgraph::{
+    algo::tarjan_scc,
     stable_graph::{EdgeReference, Neighbors, NodeIndex, StableDiGraph},
-    visit::{EdgeRef, IntoEdgesDirected},
+    visit::{Dfs, EdgeRef, IntoEdgesDirected, NodeFiltered, Walker},
     Direction,
 };
 
 use crate::block::{BlockEdge, BranchType};
 
+// a loop that can be entered through more than one of its blocks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrreducibleLoop {
+    pub nodes: Vec<NodeIndex>,
+    // the blocks of the loop with a predecessor outside of it
+    pub entries: Vec<NodeIndex>,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct Function {
     pub id: usize,
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
     pub is_variadic: bool,
+    // the pc of the first instruction of blocks that were lifted from the bytecode
+    pub block_pcs: FxHashMap<NodeIndex, usize>,
     graph: StableDiGraph<ast::Block, BlockEdge>,
     entry: Option<NodeIndex>,
 }
@@ -26,6 +39,7 @@ impl Function {
             name: None,
             parameters: Vec::new(),
             is_variadic: false,
+            block_pcs: FxHashMap::default(),
             graph: StableDiGraph::new(),
             entry: None,
         }
@@ -179,4 +193,54 @@ impl Function {
     pub fn remove_block(&mut self, block: NodeIndex) -> Option<ast::Block> {
         self.graph.remove_node(block)
     }
+
+    // loops with more than one entry. loops nested in a loop with a single entry are found
+    // by looking at the loop without its header. blocks that can't be reached are ignored
+    pub fn irreducible_loops(&self) -> Vec<IrreducibleLoop> {
+        let mut loops = Vec::new();
+        if let Some(entry) = self.entry {
+            let reachable = Dfs::new(&self.graph, entry)
+                .iter(&self.graph)
+                .collect::<FxHashSet<_>>();
+            self.find_irreducible_loops(&reachable, &reachable, &mut loops);
+        }
+        loops
+    }
+
+    fn find_irreducible_loops(
+        &self,
+        nodes: &FxHashSet<NodeIndex>,
+        reachable: &FxHashSet<NodeIndex>,
+        loops: &mut Vec<IrreducibleLoop>,
+    ) {
+        let graph = NodeFiltered::from_fn(&self.graph, |n| nodes.contains(&n));
+        for mut component in tarjan_scc(&graph) {
+            // a block that only loops to itself has a single entry
+            if component.len() == 1 {
+                continue;
+            }
+            component.sort_unstable();
+            let members = component.iter().copied().collect::<FxHashSet<_>>();
+            let entries = component
+                .iter()
+                .copied()
+                .filter(|&node| {
+                    Some(node) == self.entry
+                        || self
+                            .predecessor_blocks(node)
+                            .any(|p| reachable.contains(&p) && !members.contains(&p))
+                })
+                .collect_vec();
+            if entries.len() > 1 {
+                loops.push(IrreducibleLoop {
+                    nodes: component,
+                    entries,
+                });
+            } else {
+                let mut body = members;
+                body.remove(&entries[0]);
+                self.find_irreducible_loops(&body, reachable, loops);
+            }
+        }
+    }
 }
@@ -7,5 +7,6 @@
 pub mod block;
 pub mod dot;
 pub mod function;
+pub mod listing;
 pub mod pattern;
 pub mod ssa;
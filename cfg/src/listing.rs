@@ -0,0 +1,101 @@
+// a plain text format for control flow graphs that's easier to diff between passes than
+// the dot output and can be parsed back, so passes can be tested without bytecode:
+//
+// function 0
+// params a, ...
+//
+// block 0 entry
+//     b = @print
+//     b(a)
+//     if a
+//     -> t 1 [c = a]
+//     -> e 2
+//
+// globals are prefixed with @ to tell them apart from locals, statements that only exist
+// in the control flow graph (ex. numforinit) are written as keywords.
+// loops, gotos and breaks are only created after restructuring and aren't part of the format
+use std::io::Write;
+
+use ast::{RValue, RcLocal, Statement};
+
+use crate::function::Function;
+
+mod parse;
+mod print;
+
+pub use parse::{parse, ParseError};
+pub use print::Names;
+
+// names that are never given to locals
+const KEYWORDS: &[&str] = &[
+    "and",
+    "block",
+    "break",
+    "close",
+    "closure",
+    "copy",
+    "do",
+    "else",
+    "elseif",
+    "end",
+    "entry",
+    "false",
+    "for",
+    "function",
+    "generalized",
+    "genericforinit",
+    "genericfornext",
+    "goto",
+    "if",
+    "in",
+    "inf",
+    "local",
+    "nan",
+    "nil",
+    "not",
+    "numforinit",
+    "numfornext",
+    "or",
+    "parallel",
+    "params",
+    "ref",
+    "repeat",
+    "return",
+    "setlist",
+    "tail",
+    "then",
+    "true",
+    "until",
+    "vector",
+    "while",
+];
+
+pub fn print(function: &Function) -> String {
+    print_with_names(function, &mut Names::default())
+}
+
+// locals that were printed with `names` before keep their names
+pub fn print_with_names(function: &Function, names: &mut Names) -> String {
+    print::Printer::new(names).print(function)
+}
+
+// for the labels in the dot output
+pub(crate) fn print_statements(statements: &[Statement], names: &mut Names) -> String {
+    print::Printer::new(names).statements(statements)
+}
+
+pub(crate) fn print_argument(local: &RcLocal, value: &RValue, names: &mut Names) -> String {
+    print::Printer::new(names).argument(local, value)
+}
+
+pub fn render_to<W: Write>(function: &Function, output: &mut W) -> std::io::Result<()> {
+    output.write_all(print(function).as_bytes())
+}
+
+pub fn render_with_names_to<W: Write>(
+    function: &Function,
+    names: &mut Names,
+    output: &mut W,
+) -> std::io::Result<()> {
+    output.write_all(print_with_names(function, names).as_bytes())
+}
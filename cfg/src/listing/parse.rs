@@ -0,0 +1,881 @@
+use ast::{LValue, Local, RValue, RcLocal, Statement};
+use by_address::ByAddress;
+use parking_lot::Mutex;
+use petgraph::stable_graph::NodeIndex;
+use rustc_hash::FxHashMap;
+use triomphe::Arc;
+
+use super::KEYWORDS;
+use crate::{
+    block::{BlockEdge, BranchType},
+    function::Function,
+};
+
+#[derive(Debug, thiserror::Error)]
+#[error("line {line}: {message}")]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Global(Vec<u8>),
+    Number(f64),
+    // the magnitude, the sign is a unary minus
+    Int64(u64),
+    UInt64(u64),
+    Imaginary(f64),
+    String(Vec<u8>),
+    Symbol(&'static str),
+}
+
+// longest first
+const SYMBOLS: &[&str] = &[
+    "...", "->", "..", "//", "==", "~=", "<=", ">=", "+", "-", "*", "/", "%", "^", "#", "<", ">",
+    "=", "(", ")", "{", "}", "[", "]", ",", ".", ":", ";",
+];
+
+const UNARY_PRIORITY: usize = 8;
+
+fn binary_operation(token: &Token) -> Option<(ast::BinaryOperation, usize, usize)> {
+    use ast::BinaryOperation::*;
+    // left and right priority, the same as lua's
+    Some(match token {
+        Token::Symbol("+") => (Add, 6, 6),
+        Token::Symbol("-") => (Sub, 6, 6),
+        Token::Symbol("*") => (Mul, 7, 7),
+        Token::Symbol("/") => (Div, 7, 7),
+        Token::Symbol("//") => (IDiv, 7, 7),
+        Token::Symbol("%") => (Mod, 7, 7),
+        Token::Symbol("^") => (Pow, 10, 9),
+        Token::Symbol("..") => (Concat, 5, 4),
+        Token::Symbol("==") => (Equal, 3, 3),
+        Token::Symbol("~=") => (NotEqual, 3, 3),
+        Token::Symbol("<") => (LessThan, 3, 3),
+        Token::Symbol("<=") => (LessThanOrEqual, 3, 3),
+        Token::Symbol(">") => (GreaterThan, 3, 3),
+        Token::Symbol(">=") => (GreaterThanOrEqual, 3, 3),
+        Token::Name(name) if name == "and" => (And, 2, 2),
+        Token::Name(name) if name == "or" => (Or, 1, 1),
+        _ => return None,
+    })
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let bytes = line.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token::Name(line[start..i].to_string()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len()
+                && (bytes[i].is_ascii_digit()
+                    || (bytes[i] == b'.' && bytes.get(i + 1) != Some(&b'.'))
+                    || bytes[i] == b'e'
+                    || bytes[i] == b'E'
+                    || ((bytes[i] == b'-' || bytes[i] == b'+')
+                        && matches!(bytes[i - 1], b'e' | b'E')))
+            {
+                i += 1;
+            }
+            let number = &line[start..i];
+            if line[i..].starts_with("ULL") {
+                i += 3;
+                tokens.push(Token::UInt64(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid integer {}", number))?,
+                ));
+            } else if line[i..].starts_with("LL") {
+                i += 2;
+                tokens.push(Token::Int64(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid integer {}", number))?,
+                ));
+            } else if line[i..].starts_with('i') {
+                i += 1;
+                tokens.push(Token::Imaginary(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid number {}", number))?,
+                ));
+            } else {
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid number {}", number))?,
+                ));
+            }
+        } else if c == b'"' {
+            let (string, len) = unescape_string(&bytes[i..])?;
+            tokens.push(Token::String(string));
+            i += len;
+        } else if c == b'@' {
+            i += 1;
+            if bytes.get(i) == Some(&b'"') {
+                let (string, len) = unescape_string(&bytes[i..])?;
+                tokens.push(Token::Global(string));
+                i += len;
+            } else {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                if start == i {
+                    return Err("expected global name after @".to_string());
+                }
+                tokens.push(Token::Global(bytes[start..i].to_vec()));
+            }
+        } else if let Some(symbol) = SYMBOLS.iter().find(|s| line[i..].starts_with(**s)) {
+            tokens.push(Token::Symbol(symbol));
+            i += symbol.len();
+        } else {
+            return Err(format!(
+                "unexpected character {:?}",
+                line[i..].chars().next().unwrap()
+            ));
+        }
+    }
+    Ok(tokens)
+}
+
+// returns the string and the length of the literal including the quotes
+fn unescape_string(bytes: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut string = Vec::new();
+    let mut i = 1;
+    loop {
+        match bytes.get(i) {
+            None => return Err("unterminated string".to_string()),
+            Some(b'"') => return Ok((string, i + 1)),
+            Some(b'\\') => {
+                i += 1;
+                match bytes.get(i) {
+                    Some(b'n') => string.push(b'\n'),
+                    Some(b'r') => string.push(b'\r'),
+                    Some(b't') => string.push(b'\t'),
+                    Some(b'"') => string.push(b'"'),
+                    Some(b'\\') => string.push(b'\\'),
+                    Some(c) if c.is_ascii_digit() => {
+                        let start = i;
+                        while i < bytes.len() && i - start < 3 && bytes[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                        let code = std::str::from_utf8(&bytes[start..i]).unwrap();
+                        string.push(
+                            code.parse()
+                                .map_err(|_| format!("invalid escape \\{}", code))?,
+                        );
+                        continue;
+                    }
+                    _ => return Err("invalid escape".to_string()),
+                }
+                i += 1;
+            }
+            Some(&c) => {
+                string.push(c);
+                i += 1;
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    // line numbers and contents of every line that isn't blank
+    lines: Vec<(usize, &'a str)>,
+    line: usize,
+    tokens: Vec<Token>,
+    position: usize,
+    locals: FxHashMap<String, RcLocal>,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: self
+                .lines
+                .get(self.line)
+                .or(self.lines.last())
+                .map(|&(line, _)| line)
+                .unwrap_or(0),
+            message: message.into(),
+        })
+    }
+
+    fn current_line(&self) -> Option<&'a str> {
+        self.lines.get(self.line).map(|&(_, line)| line.trim())
+    }
+
+    // moves to the next line and tokenizes it
+    fn next_line(&mut self) -> Result<(), ParseError> {
+        self.line += 1;
+        self.position = 0;
+        self.tokens = match self.current_line() {
+            Some(line) if !line.starts_with("--") => match tokenize(line) {
+                Ok(tokens) => tokens,
+                Err(message) => return self.error(message),
+            },
+            _ => Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, ParseError> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => self.error("unexpected end of line"),
+        }
+    }
+
+    fn check_symbol(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn check_keyword(&mut self, keyword: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Name(name)) if name == keyword) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParseError> {
+        if self.check_symbol(symbol) {
+            Ok(())
+        } else {
+            self.error(format!("expected {}", symbol))
+        }
+    }
+
+    fn expect_end_of_line(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => self.error(format!("unexpected {:?}", token)),
+        }
+    }
+
+    fn expect_usize(&mut self) -> Result<usize, ParseError> {
+        match self.next()? {
+            Token::Number(n) if n >= 0.0 && n.fract() == 0.0 => Ok(n as usize),
+            token => self.error(format!("expected an index, got {:?}", token)),
+        }
+    }
+
+    fn local(&mut self) -> Result<RcLocal, ParseError> {
+        match self.next()? {
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => Ok(self
+                .locals
+                .entry(name.clone())
+                .or_insert_with(|| RcLocal::new(Local::new(Some(name))))
+                .clone()),
+            token => self.error(format!("expected a local, got {:?}", token)),
+        }
+    }
+
+    fn locals(&mut self) -> Result<Vec<RcLocal>, ParseError> {
+        let mut locals = vec![self.local()?];
+        while self.check_symbol(",") {
+            locals.push(self.local()?);
+        }
+        Ok(locals)
+    }
+
+    fn rvalues(&mut self) -> Result<Vec<RValue>, ParseError> {
+        let mut rvalues = vec![self.rvalue()?];
+        while self.check_symbol(",") {
+            rvalues.push(self.rvalue()?);
+        }
+        Ok(rvalues)
+    }
+
+    fn lvalues(&mut self) -> Result<Vec<LValue>, ParseError> {
+        let mut lvalues = vec![self.lvalue()?];
+        while self.check_symbol(",") {
+            lvalues.push(self.lvalue()?);
+        }
+        Ok(lvalues)
+    }
+
+    fn lvalue(&mut self) -> Result<LValue, ParseError> {
+        match self.suffixed()?.into_lvalue() {
+            Some(lvalue) => Ok(lvalue),
+            None => self.error("expected a local, global or index"),
+        }
+    }
+
+    fn arguments(&mut self) -> Result<Vec<RValue>, ParseError> {
+        self.expect_symbol("(")?;
+        if self.check_symbol(")") {
+            return Ok(Vec::new());
+        }
+        let arguments = self.rvalues()?;
+        self.expect_symbol(")")?;
+        Ok(arguments)
+    }
+
+    fn primary(&mut self) -> Result<RValue, ParseError> {
+        match self.next()? {
+            Token::Global(name) => Ok(ast::Global::new(name).into()),
+            Token::Symbol("(") => {
+                let rvalue = self.rvalue()?;
+                self.expect_symbol(")")?;
+                // parenthesized multiple values are truncated to one
+                Ok(match rvalue {
+                    RValue::VarArg(var_arg) => RValue::Select(var_arg.into()),
+                    RValue::Call(call) => RValue::Select(call.into()),
+                    RValue::MethodCall(method_call) => RValue::Select(method_call.into()),
+                    rvalue => rvalue,
+                })
+            }
+            Token::Name(_) => {
+                self.position -= 1;
+                Ok(self.local()?.into())
+            }
+            token => self.error(format!("unexpected {:?}", token)),
+        }
+    }
+
+    fn suffixed(&mut self) -> Result<RValue, ParseError> {
+        let mut rvalue = self.primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Symbol(".")) => {
+                    self.position += 1;
+                    match self.next()? {
+                        Token::Name(key) => {
+                            rvalue = ast::Index::new(
+                                rvalue,
+                                ast::Literal::String(key.into_bytes()).into(),
+                            )
+                            .into()
+                        }
+                        token => return self.error(format!("expected a name, got {:?}", token)),
+                    }
+                }
+                Some(Token::Symbol("[")) => {
+                    self.position += 1;
+                    let key = self.rvalue()?;
+                    self.expect_symbol("]")?;
+                    rvalue = ast::Index::new(rvalue, key).into();
+                }
+                Some(Token::Symbol(":")) => {
+                    self.position += 1;
+                    let method = match self.next()? {
+                        Token::Name(method) => method,
+                        token => return self.error(format!("expected a name, got {:?}", token)),
+                    };
+                    let arguments = self.arguments()?;
+                    rvalue = ast::MethodCall::new(rvalue, method, arguments).into();
+                }
+                Some(Token::Symbol("(")) => {
+                    let arguments = self.arguments()?;
+                    rvalue = ast::Call::new(rvalue, arguments).into();
+                }
+                _ => return Ok(rvalue),
+            }
+        }
+    }
+
+    fn simple(&mut self) -> Result<RValue, ParseError> {
+        let literal = match self.peek() {
+            Some(Token::Number(n)) => ast::Literal::Number(*n),
+            Some(&Token::Int64(n)) => match i64::try_from(n) {
+                Ok(n) => ast::Literal::Int64(n),
+                Err(_) => return self.error(format!("{} is out of range", n)),
+            },
+            Some(&Token::UInt64(n)) => ast::Literal::UInt64(n),
+            Some(&Token::Imaginary(n)) => ast::Literal::Imaginary(n),
+            Some(Token::String(string)) => ast::Literal::String(string.clone()),
+            Some(Token::Name(name)) => match name.as_str() {
+                "nil" => ast::Literal::Nil,
+                "true" => ast::Literal::Boolean(true),
+                "false" => ast::Literal::Boolean(false),
+                "inf" => ast::Literal::Number(f64::INFINITY),
+                "nan" => ast::Literal::Number(f64::NAN),
+                "vector" => {
+                    self.position += 1;
+                    let components = self.arguments()?;
+                    let mut values = Vec::with_capacity(3);
+                    for component in &components {
+                        match component {
+                            RValue::Literal(ast::Literal::Number(n)) => values.push(*n as f32),
+                            RValue::Unary(ast::Unary {
+                                value: box RValue::Literal(ast::Literal::Number(n)),
+                                operation: ast::UnaryOperation::Negate,
+                            }) => values.push(-*n as f32),
+                            _ => return self.error("vector components must be numbers"),
+                        }
+                    }
+                    let &[x, y, z] = values.as_slice() else {
+                        return self.error("vectors have 3 components");
+                    };
+                    return Ok(ast::Literal::Vector(x, y, z).into());
+                }
+                "closure" => {
+                    self.position += 1;
+                    return self.closure();
+                }
+                "if" => {
+                    self.position += 1;
+                    return self.if_expression();
+                }
+                _ => return self.suffixed(),
+            },
+            Some(Token::Symbol("...")) => {
+                self.position += 1;
+                return Ok(ast::VarArg.into());
+            }
+            Some(Token::Symbol("{")) => {
+                self.position += 1;
+                return self.table();
+            }
+            _ => return self.suffixed(),
+        };
+        self.position += 1;
+        Ok(literal.into())
+    }
+
+    fn closure(&mut self) -> Result<RValue, ParseError> {
+        self.expect_symbol("[")?;
+        let mut upvalues = Vec::new();
+        while !self.check_symbol("]") {
+            if !upvalues.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            if self.check_keyword("copy") {
+                upvalues.push(ast::Upvalue::Copy(self.local()?));
+            } else if self.check_keyword("ref") {
+                upvalues.push(ast::Upvalue::Ref(self.local()?));
+            } else {
+                return self.error("expected copy or ref");
+            }
+        }
+        Ok(ast::Closure {
+            function: ByAddress(Arc::new(Mutex::new(ast::Function::default()))),
+            upvalues,
+        }
+        .into())
+    }
+
+    fn if_expression(&mut self) -> Result<RValue, ParseError> {
+        let condition = self.rvalue()?;
+        if !self.check_keyword("then") {
+            return self.error("expected then");
+        }
+        let then_value = self.rvalue()?;
+        if !self.check_keyword("else") {
+            return self.error("expected else");
+        }
+        let else_value = self.rvalue()?;
+        Ok(ast::IfExpression::new(condition, then_value, else_value).into())
+    }
+
+    fn table(&mut self) -> Result<RValue, ParseError> {
+        let mut fields = Vec::new();
+        while !self.check_symbol("}") {
+            if !fields.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            if self.check_symbol("[") {
+                let key = self.rvalue()?;
+                self.expect_symbol("]")?;
+                self.expect_symbol("=")?;
+                fields.push((Some(key), self.rvalue()?));
+            } else {
+                fields.push((None, self.rvalue()?));
+            }
+        }
+        Ok(ast::Table(fields).into())
+    }
+
+    fn subexpression(&mut self, limit: usize) -> Result<RValue, ParseError> {
+        let operation = match self.peek() {
+            Some(Token::Name(name)) if name == "not" => Some(ast::UnaryOperation::Not),
+            Some(Token::Symbol("-")) => Some(ast::UnaryOperation::Negate),
+            Some(Token::Symbol("#")) => Some(ast::UnaryOperation::Length),
+            _ => None,
+        };
+        let mut left = if let Some(operation) = operation {
+            self.position += 1;
+            // -1 is a literal, -(1) is the negation of one
+            let literal = matches!(
+                self.peek(),
+                Some(
+                    Token::Number(_) | Token::Int64(_) | Token::UInt64(_) | Token::Imaginary(_)
+                )
+            ) || matches!(self.peek(), Some(Token::Name(name)) if name == "inf" || name == "nan");
+            if operation == ast::UnaryOperation::Negate
+                && literal
+                && let Some(&Token::Int64(n)) = self.peek()
+            {
+                self.position += 1;
+                if n > 1 << 63 {
+                    return self.error(format!("-{} is out of range", n));
+                }
+                RValue::Literal(ast::Literal::Int64((n as i64).wrapping_neg()))
+            } else {
+                let value = self.subexpression(UNARY_PRIORITY)?;
+                match value {
+                    RValue::Literal(ast::Literal::Number(n))
+                        if operation == ast::UnaryOperation::Negate && literal =>
+                    {
+                        RValue::Literal(ast::Literal::Number(-n))
+                    }
+                    RValue::Literal(ast::Literal::Imaginary(n))
+                        if operation == ast::UnaryOperation::Negate && literal =>
+                    {
+                        RValue::Literal(ast::Literal::Imaginary(-n))
+                    }
+                    value => ast::Unary::new(value, operation).into(),
+                }
+            }
+        } else {
+            self.simple()?
+        };
+        while let Some((operation, left_priority, right_priority)) =
+            self.peek().and_then(binary_operation)
+            && left_priority > limit
+        {
+            self.position += 1;
+            let right = self.subexpression(right_priority)?;
+            left = ast::Binary::new(left, right, operation).into();
+        }
+        Ok(left)
+    }
+
+    fn rvalue(&mut self) -> Result<RValue, ParseError> {
+        self.subexpression(0)
+    }
+
+    // statements in a block of an if statement, until a line that's just `else` or `end`
+    fn nested_block(&mut self) -> Result<(ast::Block, String), ParseError> {
+        let mut block = ast::Block::default();
+        loop {
+            self.next_line()?;
+            match self.current_line() {
+                None => return self.error("expected end"),
+                Some(line @ ("else" | "end")) => return Ok((block, line.to_string())),
+                Some(_) => block.push(self.statement()?),
+            }
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        let line = self.current_line().unwrap();
+        if let Some(text) = line.strip_prefix("--") {
+            return Ok(
+                ast::Comment::new(text.strip_prefix(' ').unwrap_or(text).to_string()).into(),
+            );
+        }
+        let statement = match self.peek() {
+            Some(Token::Symbol(";")) => {
+                self.position += 1;
+                ast::Empty {}.into()
+            }
+            Some(Token::Name(keyword)) if KEYWORDS.contains(&keyword.as_str()) => {
+                let keyword = keyword.clone();
+                self.position += 1;
+                match keyword.as_str() {
+                    "if" => {
+                        let condition = self.rvalue()?;
+                        if self.check_keyword("then") {
+                            self.expect_end_of_line()?;
+                            let (then_block, end) = self.nested_block()?;
+                            let else_block = if end == "else" {
+                                let (else_block, end) = self.nested_block()?;
+                                if end != "end" {
+                                    return self.error("expected end");
+                                }
+                                else_block
+                            } else {
+                                ast::Block::default()
+                            };
+                            // the current line is the `end`
+                            self.position = self.tokens.len();
+                            ast::If::new(condition, then_block, else_block).into()
+                        } else {
+                            ast::If::new(condition, Default::default(), Default::default()).into()
+                        }
+                    }
+                    "return" => {
+                        let values = if self.peek().is_some() {
+                            self.rvalues()?
+                        } else {
+                            Vec::new()
+                        };
+                        ast::Return::new(values).into()
+                    }
+                    "close" => ast::Close {
+                        locals: self.locals()?,
+                    }
+                    .into(),
+                    "setlist" => {
+                        let object_local = self.local()?;
+                        let index = self.expect_usize()?;
+                        let values = if self.check_symbol("=") {
+                            self.rvalues()?
+                        } else {
+                            Vec::new()
+                        };
+                        let tail = if self.check_keyword("tail") {
+                            Some(self.rvalue()?)
+                        } else {
+                            None
+                        };
+                        ast::SetList::new(object_local, index, values, tail).into()
+                    }
+                    "numforinit" => {
+                        let (left, right) = self.assignment(3, 3)?;
+                        let mut pairs = left.into_iter().zip(right);
+                        ast::NumForInit {
+                            counter: pairs.next().unwrap(),
+                            limit: pairs.next().unwrap(),
+                            step: pairs.next().unwrap(),
+                        }
+                        .into()
+                    }
+                    "numfornext" => {
+                        let (mut left, right) = self.assignment(1, 3)?;
+                        let [counter, limit, step]: [RValue; 3] = right.try_into().unwrap();
+                        ast::NumForNext {
+                            counter: (left.pop().unwrap(), counter),
+                            limit,
+                            step,
+                        }
+                        .into()
+                    }
+                    "genericforinit" => {
+                        let generalized = self.check_keyword("generalized");
+                        let (left, right) = self.assignment(3, 3)?;
+                        ast::GenericForInit(ast::Assign::new(left, right), generalized).into()
+                    }
+                    "genericfornext" => {
+                        let left = self.lvalues()?;
+                        self.expect_symbol("=")?;
+                        let generator = self.rvalue()?;
+                        self.expect_symbol(",")?;
+                        let state = self.rvalue()?;
+                        ast::GenericForNext {
+                            res_locals: left,
+                            generator,
+                            state,
+                        }
+                        .into()
+                    }
+                    "parallel" | "local" => {
+                        let parallel = keyword == "parallel";
+                        let prefix = keyword == "local" || self.check_keyword("local");
+                        let left = self.lvalues()?;
+                        let right = if self.check_symbol("=") {
+                            self.rvalues()?
+                        } else {
+                            Vec::new()
+                        };
+                        let mut assign = ast::Assign::new(left, right);
+                        assign.prefix = prefix;
+                        assign.parallel = parallel;
+                        assign.into()
+                    }
+                    _ => return self.error(format!("unexpected {}", keyword)),
+                }
+            }
+            _ => {
+                let start = self.position;
+                match self.suffixed()? {
+                    RValue::Call(call) if self.peek().is_none() => call.into(),
+                    RValue::MethodCall(method_call) if self.peek().is_none() => method_call.into(),
+                    _ => {
+                        self.position = start;
+                        let left = self.lvalues()?;
+                        self.expect_symbol("=")?;
+                        let right = self.rvalues()?;
+                        ast::Assign::new(left, right).into()
+                    }
+                }
+            }
+        };
+        self.expect_end_of_line()?;
+        Ok(statement)
+    }
+
+    fn assignment(
+        &mut self,
+        left_len: usize,
+        right_len: usize,
+    ) -> Result<(Vec<LValue>, Vec<RValue>), ParseError> {
+        let left = self.lvalues()?;
+        self.expect_symbol("=")?;
+        let right = self.rvalues()?;
+        if left.len() != left_len || right.len() != right_len {
+            return self.error(format!(
+                "expected {} values on the left and {} on the right",
+                left_len, right_len
+            ));
+        }
+        Ok((left, right))
+    }
+
+    fn edge(&mut self) -> Result<(NodeIndex, BlockEdge), ParseError> {
+        self.expect_symbol("->")?;
+        let branch_type = match self.next()? {
+            Token::Name(name) if name == "u" => BranchType::Unconditional,
+            Token::Name(name) if name == "t" => BranchType::Then,
+            Token::Name(name) if name == "e" => BranchType::Else,
+            token => return self.error(format!("expected u, t or e, got {:?}", token)),
+        };
+        let target = NodeIndex::new(self.expect_usize()?);
+        let mut edge = BlockEdge::new(branch_type);
+        if self.check_symbol("[") {
+            loop {
+                let local = self.local()?;
+                self.expect_symbol("=")?;
+                edge.arguments.push((local, self.rvalue()?));
+                if !self.check_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_symbol("]")?;
+        }
+        self.expect_end_of_line()?;
+        Ok((target, edge))
+    }
+
+    fn function(&mut self) -> Result<Function, ParseError> {
+        // names can contain anything, so this line isn't tokenized
+        let Some(header) = self
+            .current_line()
+            .and_then(|line| line.strip_prefix("function "))
+        else {
+            return self.error("expected function");
+        };
+        let (id, name) = match header.trim().split_once(' ') {
+            Some((id, name)) => (id, Some(name.trim().to_string())),
+            None => (header.trim(), None),
+        };
+        let Ok(id) = id.parse() else {
+            return self.error(format!("invalid function id {}", id));
+        };
+        let mut function = Function::new(id);
+        function.name = name;
+
+        self.next_line()?;
+        if self.check_keyword("params") {
+            loop {
+                if self.check_symbol("...") {
+                    function.is_variadic = true;
+                    break;
+                }
+                let param = self.local()?;
+                function.parameters.push(param);
+                if !self.check_symbol(",") {
+                    break;
+                }
+            }
+            self.expect_end_of_line()?;
+            self.next_line()?;
+        }
+
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        while self.current_line().is_some() {
+            if !self.check_keyword("block") {
+                return self.error("expected block");
+            }
+            let node = self.expect_usize()?;
+            let entry = self.check_keyword("entry");
+            self.expect_end_of_line()?;
+            if nodes.iter().any(|(n, _, _)| *n == node) {
+                return self.error(format!("block {} is defined twice", node));
+            }
+
+            let mut block = ast::Block::default();
+            loop {
+                self.next_line()?;
+                match self.peek() {
+                    None if self.current_line().is_none() => break,
+                    Some(Token::Name(name)) if name == "block" => break,
+                    Some(Token::Symbol("->")) => edges.push((node, self.edge()?)),
+                    _ if !edges.iter().any(|&(source, _)| source == node) => {
+                        block.push(self.statement()?)
+                    }
+                    _ => return self.error("statements must come before edges"),
+                }
+            }
+            nodes.push((node, block, entry));
+        }
+
+        // add placeholders so that parsed blocks keep their indices
+        let len = nodes
+            .iter()
+            .map(|&(node, _, _)| node + 1)
+            .max()
+            .unwrap_or(0);
+        for _ in 0..len {
+            function.new_block();
+        }
+        for index in 0..len {
+            if !nodes.iter().any(|&(node, _, _)| node == index) {
+                function.remove_block(NodeIndex::new(index));
+            }
+        }
+        for (node, block, entry) in nodes {
+            let node = NodeIndex::new(node);
+            *function.block_mut(node).unwrap() = block;
+            if entry {
+                function.set_entry(node);
+            }
+        }
+        for (source, (target, edge)) in edges {
+            if !function.has_block(target) {
+                return self.error(format!("block {} doesn't exist", target.index()));
+            }
+            function
+                .graph_mut()
+                .add_edge(NodeIndex::new(source), target, edge);
+        }
+        if function.entry().is_none() {
+            return self.error("no entry block");
+        }
+        Ok(function)
+    }
+}
+
+pub fn parse(input: &str) -> Result<Function, ParseError> {
+    let mut parser = Parser {
+        lines: input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line))
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect(),
+        line: 0,
+        tokens: Vec::new(),
+        position: 0,
+        locals: FxHashMap::default(),
+    };
+    parser.function()
+}
@@ -0,0 +1,454 @@
+use std::fmt::Write;
+
+use ast::{RValue, RcLocal, Statement};
+use itertools::Itertools;
+use petgraph::visit::EdgeRef;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::KEYWORDS;
+use crate::{block::BranchType, function::Function};
+
+pub(super) fn is_name(name: &str) -> bool {
+    name.chars()
+        .enumerate()
+        .all(|(i, c)| (i != 0 && c.is_ascii_digit()) || c.is_ascii_alphabetic() || c == '_')
+        && !name.is_empty()
+        && !KEYWORDS.contains(&name)
+}
+
+pub(super) fn escape_string(string: &[u8]) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for &c in string {
+        match c {
+            b'\n' => escaped.push_str(r"\n"),
+            b'\r' => escaped.push_str(r"\r"),
+            b'\t' => escaped.push_str(r"\t"),
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str(r"\\"),
+            b' ' => escaped.push(' '),
+            _ if c.is_ascii_graphic() => escaped.push(c as char),
+            _ => write!(escaped, "\\{:03}", c).unwrap(),
+        }
+    }
+    escaped
+}
+
+// the names locals are printed with, every local gets a distinct name even if the names
+// they have are the same. the locals themselves are never renamed
+#[derive(Debug, Default)]
+pub struct Names {
+    names: FxHashMap<RcLocal, String>,
+    used_names: FxHashSet<String>,
+    counter: usize,
+}
+
+impl Names {
+    fn local(&mut self, local: &RcLocal) -> String {
+        if let Some(name) = self.names.get(local) {
+            return name.clone();
+        }
+        let base = match &local.0 .0.lock().0 {
+            Some(name) if is_name(name) => name.clone(),
+            _ => "v".to_string(),
+        };
+        let name = if base != "v" && !self.used_names.contains(&base) {
+            base
+        } else {
+            loop {
+                self.counter += 1;
+                let name = if base == "v" {
+                    format!("v{}", self.counter)
+                } else {
+                    format!("{}_{}", base, self.counter)
+                };
+                if !self.used_names.contains(&name) {
+                    break name;
+                }
+            }
+        };
+        self.used_names.insert(name.clone());
+        self.names.insert(local.clone(), name.clone());
+        name
+    }
+}
+
+pub(super) struct Printer<'a> {
+    output: String,
+    names: &'a mut Names,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(names: &'a mut Names) -> Self {
+        Self {
+            output: String::new(),
+            names,
+        }
+    }
+
+    pub fn print(mut self, function: &Function) -> String {
+        write!(self.output, "function {}", function.id).unwrap();
+        if let Some(name) = &function.name {
+            write!(self.output, " {}", name).unwrap();
+        }
+        self.output.push('\n');
+        if !function.parameters.is_empty() || function.is_variadic {
+            let params = function
+                .parameters
+                .iter()
+                .map(|param| self.local(param))
+                .chain(function.is_variadic.then(|| "...".to_string()))
+                .join(", ");
+            writeln!(self.output, "params {}", params).unwrap();
+        }
+        for node in function.graph().node_indices().sorted() {
+            self.output.push('\n');
+            if function.entry() == &Some(node) {
+                writeln!(self.output, "block {} entry", node.index()).unwrap();
+            } else {
+                writeln!(self.output, "block {}", node.index()).unwrap();
+            }
+            for statement in &function.block(node).unwrap().0 {
+                self.statement(statement, 1);
+            }
+            for edge in function
+                .edges(node)
+                .sorted_by_key(|edge| edge.weight().branch_type != BranchType::Then)
+            {
+                let branch_type = match edge.weight().branch_type {
+                    BranchType::Unconditional => "u",
+                    BranchType::Then => "t",
+                    BranchType::Else => "e",
+                };
+                let mut line = format!("    -> {} {}", branch_type, edge.target().index());
+                if !edge.weight().arguments.is_empty() {
+                    let arguments = edge
+                        .weight()
+                        .arguments
+                        .iter()
+                        .map(|(local, value)| self.argument(local, value))
+                        .join(", ");
+                    write!(line, " [{}]", arguments).unwrap();
+                }
+                writeln!(self.output, "{}", line).unwrap();
+            }
+        }
+        self.output
+    }
+
+    fn local(&mut self, local: &RcLocal) -> String {
+        self.names.local(local)
+    }
+
+    // one statement per line, without indentation
+    pub fn statements(mut self, statements: &[Statement]) -> String {
+        for statement in statements {
+            self.statement(statement, 0);
+        }
+        self.output
+    }
+
+    pub fn argument(&mut self, local: &RcLocal, value: &RValue) -> String {
+        format!("{} = {}", self.local(local), self.rvalue(value))
+    }
+
+    fn rvalues(&mut self, rvalues: &[RValue]) -> String {
+        rvalues.iter().map(|rvalue| self.rvalue(rvalue)).join(", ")
+    }
+
+    fn lvalues(&mut self, lvalues: &[ast::LValue]) -> String {
+        lvalues
+            .iter()
+            .map(|lvalue| match lvalue {
+                ast::LValue::Local(local) => self.local(local),
+                ast::LValue::Global(global) => self.global(global),
+                ast::LValue::Index(index) => self.index(index),
+            })
+            .join(", ")
+    }
+
+    fn global(&mut self, global: &ast::Global) -> String {
+        match std::str::from_utf8(&global.0) {
+            Ok(name) if is_name(name) => format!("@{}", name),
+            _ => format!("@\"{}\"", escape_string(&global.0)),
+        }
+    }
+
+    fn index(&mut self, index: &ast::Index) -> String {
+        let left = self.prefix(&index.left);
+        match &*index.right {
+            RValue::Literal(ast::Literal::String(key))
+                if let Ok(key) = std::str::from_utf8(key)
+                    && is_name(key) =>
+            {
+                format!("{}.{}", left, key)
+            }
+            right => format!("{}[{}]", left, self.rvalue(right)),
+        }
+    }
+
+    // values that are called or indexed
+    fn prefix(&mut self, rvalue: &RValue) -> String {
+        match rvalue {
+            RValue::Local(_)
+            | RValue::Global(_)
+            | RValue::Index(_)
+            | RValue::Call(_)
+            | RValue::MethodCall(_)
+            | RValue::Select(_) => self.rvalue(rvalue),
+            _ => format!("({})", self.rvalue(rvalue)),
+        }
+    }
+
+    // operands of unary and binary expressions are always parenthesized if they're
+    // expressions themselves so precedence never matters
+    fn operand(&mut self, rvalue: &RValue) -> String {
+        match rvalue {
+            RValue::Unary(_) | RValue::Binary(_) | RValue::IfExpression(_) => {
+                format!("({})", self.rvalue(rvalue))
+            }
+            RValue::Literal(ast::Literal::Number(n)) if n.is_sign_negative() => {
+                format!("({})", self.rvalue(rvalue))
+            }
+            RValue::Literal(ast::Literal::Int64(n)) if n.is_negative() => {
+                format!("({})", self.rvalue(rvalue))
+            }
+            RValue::Literal(ast::Literal::Imaginary(n)) if n.is_sign_negative() => {
+                format!("({})", self.rvalue(rvalue))
+            }
+            _ => self.rvalue(rvalue),
+        }
+    }
+
+    fn literal(&mut self, literal: &ast::Literal) -> String {
+        match literal {
+            ast::Literal::Number(n) if n.is_nan() => "nan".to_string(),
+            ast::Literal::Number(n) if n.is_infinite() => {
+                if n.is_sign_negative() { "-inf" } else { "inf" }.to_string()
+            }
+            ast::Literal::Number(n) if *n == 0.0 && n.is_sign_negative() => "-0".to_string(),
+            ast::Literal::String(string) => format!("\"{}\"", escape_string(string)),
+            ast::Literal::Vector(x, y, z) => format!("vector({}, {}, {})", x, y, z),
+            _ => literal.to_string(),
+        }
+    }
+
+    fn call(&mut self, call: &ast::Call) -> String {
+        let value = self.prefix(&call.value);
+        format!("{}({})", value, self.rvalues(&call.arguments))
+    }
+
+    fn method_call(&mut self, method_call: &ast::MethodCall) -> String {
+        let value = self.prefix(&method_call.value);
+        format!(
+            "{}:{}({})",
+            value,
+            method_call.method,
+            self.rvalues(&method_call.arguments)
+        )
+    }
+
+    fn rvalue(&mut self, rvalue: &RValue) -> String {
+        match rvalue {
+            RValue::Local(local) => self.local(local),
+            RValue::Global(global) => self.global(global),
+            RValue::Call(call) => self.call(call),
+            RValue::MethodCall(method_call) => self.method_call(method_call),
+            RValue::VarArg(_) => "...".to_string(),
+            RValue::Table(table) => {
+                let fields = table
+                    .0
+                    .iter()
+                    .map(|(key, value)| match key {
+                        Some(key) => format!("[{}] = {}", self.rvalue(key), self.rvalue(value)),
+                        None => self.rvalue(value),
+                    })
+                    .join(", ");
+                format!("{{{}}}", fields)
+            }
+            RValue::Literal(literal) => self.literal(literal),
+            RValue::Index(index) => self.index(index),
+            RValue::Unary(unary) => {
+                let value = self.operand(&unary.value);
+                // -1 is a literal, the negation of 1 isn't
+                let value = match &*unary.value {
+                    RValue::Literal(
+                        ast::Literal::Number(_)
+                        | ast::Literal::Int64(_)
+                        | ast::Literal::UInt64(_)
+                        | ast::Literal::Imaginary(_),
+                    ) if unary.operation == ast::UnaryOperation::Negate => format!("({})", value),
+                    _ => value,
+                };
+                format!("{}{}", unary.operation, value)
+            }
+            RValue::Binary(binary) => {
+                let left = self.operand(&binary.left);
+                let right = self.operand(&binary.right);
+                format!("{} {} {}", left, binary.operation, right)
+            }
+            RValue::Closure(closure) => {
+                let upvalues = closure
+                    .upvalues
+                    .iter()
+                    .map(|upvalue| match upvalue {
+                        ast::Upvalue::Copy(local) => format!("copy {}", self.local(local)),
+                        ast::Upvalue::Ref(local) => format!("ref {}", self.local(local)),
+                    })
+                    .join(", ");
+                format!("closure [{}]", upvalues)
+            }
+            RValue::IfExpression(if_expression) => {
+                let condition = self.rvalue(&if_expression.condition);
+                let then_value = self.rvalue(&if_expression.then_value);
+                let else_value = self.rvalue(&if_expression.else_value);
+                format!("if {} then {} else {}", condition, then_value, else_value)
+            }
+            // these only exist after restructuring
+            RValue::InterpolatedString(_) => rvalue.to_string(),
+            // parenthesized multiple values are truncated to one
+            RValue::Select(select) => match select {
+                ast::Select::VarArg(_) => "(...)".to_string(),
+                ast::Select::Call(call) => format!("({})", self.call(call)),
+                ast::Select::MethodCall(method_call) => {
+                    format!("({})", self.method_call(method_call))
+                }
+            },
+        }
+    }
+
+    fn line(&mut self, indentation: usize, line: &str) {
+        for _ in 0..indentation {
+            self.output.push_str("    ");
+        }
+        self.output.push_str(line);
+        self.output.push('\n');
+    }
+
+    fn statement(&mut self, statement: &Statement, indentation: usize) {
+        let line = match statement {
+            Statement::Empty(_) => ";".to_string(),
+            Statement::Call(call) => self.call(call),
+            Statement::MethodCall(method_call) => self.method_call(method_call),
+            Statement::Assign(assign) => {
+                let mut line = String::new();
+                if assign.parallel {
+                    line.push_str("parallel ");
+                }
+                if assign.prefix {
+                    line.push_str("local ");
+                }
+                line.push_str(&self.lvalues(&assign.left));
+                if !assign.right.is_empty() {
+                    write!(line, " = {}", self.rvalues(&assign.right)).unwrap();
+                }
+                line
+            }
+            Statement::If(r#if) => {
+                let condition = self.rvalue(&r#if.condition);
+                let then_block = r#if.then_block.lock();
+                let else_block = r#if.else_block.lock();
+                // conditional jumps in the graph don't have blocks
+                if then_block.is_empty() && else_block.is_empty() {
+                    format!("if {}", condition)
+                } else {
+                    self.line(indentation, &format!("if {} then", condition));
+                    for statement in then_block.iter() {
+                        self.statement(statement, indentation + 1);
+                    }
+                    if !else_block.is_empty() {
+                        self.line(indentation, "else");
+                        for statement in else_block.iter() {
+                            self.statement(statement, indentation + 1);
+                        }
+                    }
+                    "end".to_string()
+                }
+            }
+            Statement::NumForInit(num_for_init) => {
+                let left = self.lvalues(&[
+                    num_for_init.counter.0.clone(),
+                    num_for_init.limit.0.clone(),
+                    num_for_init.step.0.clone(),
+                ]);
+                let right = self.rvalues(&[
+                    num_for_init.counter.1.clone(),
+                    num_for_init.limit.1.clone(),
+                    num_for_init.step.1.clone(),
+                ]);
+                format!("numforinit {} = {}", left, right)
+            }
+            Statement::NumForNext(num_for_next) => {
+                let left = self.lvalues(std::slice::from_ref(&num_for_next.counter.0));
+                let right = self.rvalues(&[
+                    num_for_next.counter.1.clone(),
+                    num_for_next.limit.clone(),
+                    num_for_next.step.clone(),
+                ]);
+                format!("numfornext {} = {}", left, right)
+            }
+            Statement::GenericForInit(generic_for_init) => {
+                let left = self.lvalues(&generic_for_init.0.left);
+                let right = self.rvalues(&generic_for_init.0.right);
+                if generic_for_init.1 {
+                    format!("genericforinit generalized {} = {}", left, right)
+                } else {
+                    format!("genericforinit {} = {}", left, right)
+                }
+            }
+            Statement::GenericForNext(generic_for_next) => {
+                let left = self.lvalues(&generic_for_next.res_locals);
+                let right = self.rvalues(&[
+                    generic_for_next.generator.clone(),
+                    generic_for_next.state.clone(),
+                ]);
+                format!("genericfornext {} = {}", left, right)
+            }
+            Statement::Return(r#return) => {
+                if r#return.values.is_empty() {
+                    "return".to_string()
+                } else {
+                    format!("return {}", self.rvalues(&r#return.values))
+                }
+            }
+            Statement::Close(close) => format!(
+                "close {}",
+                close
+                    .locals
+                    .iter()
+                    .map(|local| self.local(local))
+                    .join(", ")
+            ),
+            Statement::SetList(set_list) => {
+                let mut line = format!(
+                    "setlist {} {}",
+                    self.local(&set_list.object_local),
+                    set_list.index
+                );
+                if !set_list.values.is_empty() {
+                    write!(line, " = {}", self.rvalues(&set_list.values)).unwrap();
+                }
+                if let Some(tail) = &set_list.tail {
+                    write!(line, " tail {}", self.rvalue(tail)).unwrap();
+                }
+                line
+            }
+            Statement::Comment(comment) => format!("-- {}", comment.text),
+            // these only exist after restructuring
+            Statement::CompoundAssign(_)
+            | Statement::Goto(_)
+            | Statement::Label(_)
+            | Statement::While(_)
+            | Statement::Repeat(_)
+            | Statement::NumericFor(_)
+            | Statement::GenericFor(_)
+            | Statement::Continue(_)
+            | Statement::Break(_) => {
+                format!(
+                    "-- unsupported: {}",
+                    statement.to_string().replace('\n', " ")
+                )
+            }
+        };
+        self.line(indentation, &line);
+    }
+}
@@ -351,6 +351,21 @@ impl<'a> SsaConstructor<'a> {
         same
     }
 
+    // a new version of `local` defined at `position` in `node`, see `ast::Origin`
+    fn new_local(&mut self, node: NodeIndex, local: &RcLocal, position: usize) -> RcLocal {
+        let origin = local.0 .0.lock().1.map(|origin| ast::Origin {
+            register: origin.register,
+            definition: self.function.block_pcs.get(&node).map(|&pc| (pc, position)),
+        });
+        let new_local = RcLocal::new(ast::Local(None, origin));
+        self.old_locals.insert(new_local.clone(), local.clone());
+        if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
+            upvalues.insert(new_local.clone());
+        }
+        self.local_count += 1;
+        new_local
+    }
+
     fn find_local(&mut self, node: NodeIndex, local: &RcLocal) -> RcLocal {
         let res = if let Some(new_local) = self
             .current_definition
@@ -362,13 +377,7 @@ impl<'a> SsaConstructor<'a> {
         } else {
             // search globally
             if !self.sealed_blocks.contains(&node) {
-                // TODO: this code is repeated multiple times, create new_local function
-                let param_local = RcLocal::default();
-                self.old_locals.insert(param_local.clone(), local.clone());
-                if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
-                    upvalues.insert(param_local.clone());
-                }
-                self.local_count += 1;
+                let param_local = self.new_local(node, local, 0);
                 self.incomplete_params
                     .entry(node)
                     .or_default()
@@ -377,12 +386,7 @@ impl<'a> SsaConstructor<'a> {
             } else if let Ok(pred) = self.function.predecessor_blocks(node).exactly_one() {
                 self.find_local(pred, local)
             } else {
-                let param_local = RcLocal::default();
-                self.old_locals.insert(param_local.clone(), local.clone());
-                if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
-                    upvalues.insert(param_local.clone());
-                }
-                self.local_count += 1;
+                let param_local = self.new_local(node, local, 0);
                 self.write_local(node, local, &param_local);
 
                 self.add_param_args(node, local, param_local)
@@ -523,12 +527,7 @@ impl<'a> SsaConstructor<'a> {
                     && let Some(local) = assign.left[0].as_local().cloned()
                     && assign.right[0].as_closure().is_some()
                 {
-                    let new_local = RcLocal::default();
-                    self.old_locals.insert(new_local.clone(), local.clone());
-                    if let Some(upvalues) = self.new_upvalues_in.get_mut(&local) {
-                        upvalues.insert(new_local.clone());
-                    }
-                    self.local_count += 1;
+                    let new_local = self.new_local(node, &local, stat_index + 1);
                     self.write_local(node, &local, &new_local);
                     let statement = self
                         .function
@@ -549,12 +548,7 @@ impl<'a> SsaConstructor<'a> {
                     self.read(node, stat_index);
                     // write
                     for (local_index, local) in written.iter().enumerate() {
-                        let new_local = RcLocal::default();
-                        self.old_locals.insert(new_local.clone(), local.clone());
-                        if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
-                            upvalues.insert(new_local.clone());
-                        }
-                        self.local_count += 1;
+                        let new_local = self.new_local(node, local, stat_index + 1);
                         self.write_local(node, local, &new_local);
                         let statement = self
                             .function
@@ -795,7 +795,8 @@ impl<'a> Destructor<'a> {
         let mut param_map = FxHashMap::default();
         if let Some((_, BlockEdge { arguments, .. })) = self.function.edges_to_block(node).next() {
             for param in arguments.iter().map(|(p, _)| p) {
-                let temp_param = RcLocal::default();
+                // a copy of the parameter, so it's named after the same register
+                let temp_param = RcLocal::new(ast::Local(None, param.0 .0.lock().1));
                 if let Some(group) = self.upvalue_to_group.get(param) {
                     self.upvalue_to_group
                         .insert(temp_param.clone(), group.clone());
@@ -855,7 +856,7 @@ impl<'a> Destructor<'a> {
                 };
 
                 for (param, arg) in args {
-                    let temp_local = RcLocal::default();
+                    let temp_local = RcLocal::new(ast::Local(None, param.0 .0.lock().1));
                     if let ast::RValue::Local(arg) = arg
                         && let Some(group) = self.upvalue_to_group.get(arg)
                     {
@@ -268,7 +268,9 @@ fn match_conditional_sequence(
     }
 }
 
-pub fn structure_conditionals(function: &mut Function) -> bool {
+// `if_expressions` is whether values that depend on a condition can be written as
+// `if c then a else b` instead of with `and` and `or`
+pub fn structure_conditionals(function: &mut Function, if_expressions: bool) -> bool {
     let mut did_structure = false;
     // TODO: does this need to be in dfs post order?
     let mut dfs = DfsPostOrder::new(function.graph(), function.entry().unwrap());
@@ -276,7 +278,7 @@ pub fn structure_conditionals(function: &mut Function) -> bool {
         if simplify_condition(function, node) {
             did_structure = true;
         }
-        if structure_bool_conditional(function, node) {
+        if structure_bool_conditional(function, node, if_expressions) {
             did_structure = true;
         }
 
@@ -363,12 +365,40 @@ fn is_truthy(rvalue: ast::RValue) -> Option<bool> {
     }
 }
 
+// `if c then a else b` unless it's the same as `c or b` or `c and a`, which unlike
+// `c and a or b` don't depend on `a` being truthy
+fn make_if_expression(
+    condition: ast::RValue,
+    mut then_value: ast::RValue,
+    mut else_value: ast::RValue,
+) -> ast::RValue {
+    let condition = match condition.reduce_condition() {
+        ast::RValue::Unary(ast::Unary {
+            box value,
+            operation: ast::UnaryOperation::Not,
+        }) => {
+            std::mem::swap(&mut then_value, &mut else_value);
+            value
+        }
+        condition => condition,
+    };
+    if !condition.has_side_effects() {
+        if then_value == condition {
+            return ast::Binary::new(condition, else_value, ast::BinaryOperation::Or).reduce();
+        } else if else_value == condition {
+            return ast::Binary::new(condition, then_value, ast::BinaryOperation::And).reduce();
+        }
+    }
+    ast::IfExpression::new(condition, then_value, else_value).reduce()
+}
+
 // TODO: STYLE: rename
 fn make_bool_conditional(
     function: &mut Function,
     node: NodeIndex,
     mut then_value: ast::RValue,
     mut else_value: ast::RValue,
+    if_expressions: bool,
 ) -> Option<ast::RValue> {
     let block = function.block_mut(node).unwrap();
     let r#if = block.last_mut().unwrap().as_if_mut().unwrap();
@@ -386,6 +416,9 @@ fn make_bool_conditional(
             cond
         };
         Some(cond.reduce())
+    } else if if_expressions && !then_value.has_side_effects() && !else_value.has_side_effects() {
+        let condition = std::mem::replace(&mut r#if.condition, ast::Literal::Nil.into());
+        Some(make_if_expression(condition, then_value, else_value))
     } else {
         // TODO: `v0 and v1 and v2`, v0, v1 and v2 are truthy, but only v2 is treated as such
         let then_truthy = match is_truthy(then_value.clone()) {
@@ -446,7 +479,11 @@ fn make_bool_conditional(
 // local a; if g then a = true else a = false end; return a -> return g and true or false
 // local a; if g then a = false else a = true end; return a -> return not g
 // local a; if g == 1 then a = true else a = false end; return a -> return g == 1
-fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool {
+fn structure_bool_conditional(
+    function: &mut Function,
+    node: NodeIndex,
+    if_expressions: bool,
+) -> bool {
     let match_triangle = |assigner, next, next_args: FxHashMap<ast::RcLocal, ast::RValue>| {
         if let Some(edge_to_next) = function.unconditional_edge(assigner)
             && edge_to_next.target() == next
@@ -489,7 +526,9 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
                 let then_value = then_value.clone();
                 let else_value = else_value.clone();
 
-                if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
+                if let Some(res) =
+                    make_bool_conditional(function, node, then_value, else_value, if_expressions)
+                {
                     function
                         .graph_mut()
                         .edge_weight_mut(then_edge)
@@ -537,7 +576,9 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
                 else_edge.id(),
             );
             let res_local = res_local.clone();
-            if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
+            if let Some(res) =
+                make_bool_conditional(function, node, then_value, else_value, if_expressions)
+            {
                 function
                     .graph_mut()
                     .edge_weight_mut(then_edge)
@@ -586,7 +627,9 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
                 function.unconditional_edge(else_block).unwrap().id(),
             );
             let res_local = res_local.clone();
-            if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
+            if let Some(res) =
+                make_bool_conditional(function, node, then_value, else_value, if_expressions)
+            {
                 function
                     .graph_mut()
                     .edge_weight_mut(then_edge)
@@ -626,8 +669,11 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
             && let Some(else_assign) = single_assign(function.block(else_edge.target()).unwrap())
             // TODO: allow multiple unused (excl. first) locals in left
             && else_assign.left.len() == 1 && else_assign.right.len() == 1
-            && let Ok((then_param, ast::RValue::Local(then_arg))) = then_edge.weight().arguments.iter().exactly_one()
-            && let Ok((else_param, ast::RValue::Local(else_arg))) = else_edge.weight().arguments.iter().exactly_one()
+            // the values are passed to `next` by the edges leaving the assigners
+            && let Some(then_exit) = function.unconditional_edge(then_edge.target())
+            && let Ok((then_param, ast::RValue::Local(then_arg))) = then_exit.weight().arguments.iter().exactly_one()
+            && let Some(else_exit) = function.unconditional_edge(else_edge.target())
+            && let Ok((else_param, ast::RValue::Local(else_arg))) = else_exit.weight().arguments.iter().exactly_one()
             && then_param == else_param
             && then_assign.left[0].as_local() == Some(then_arg)
             && else_assign.left[0].as_local() == Some(else_arg)
@@ -643,7 +689,9 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
                 function.unconditional_edge(then_block).unwrap().id(),
                 function.unconditional_edge(else_block).unwrap().id(),
             );
-            if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
+            if let Some(res) =
+                make_bool_conditional(function, node, then_value, else_value, if_expressions)
+            {
                 function
                     .graph_mut()
                     .edge_weight_mut(then_edge)
@@ -700,7 +748,9 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
             let then_value = then_value.clone();
             let else_value = else_value.clone();
 
-            if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
+            if let Some(res) =
+                make_bool_conditional(function, node, then_value, else_value, if_expressions)
+            {
                 function.remove_block(then_target);
                 function.remove_block(else_target);
                 let block = function.block_mut(node).unwrap();
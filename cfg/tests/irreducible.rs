@@ -0,0 +1,42 @@
+use cfg::listing;
+use petgraph::stable_graph::NodeIndex;
+
+#[test]
+fn irreducible_loops() {
+    // block 2 jumps into the middle of the loop formed by blocks 1 and 3
+    let mut function = listing::parse(
+        r#"function 0
+params a
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    @print("a")
+    -> u 2
+
+block 2
+    if @cond()
+    -> t 3
+    -> e 1
+
+block 3
+    return
+"#,
+    )
+    .unwrap();
+    let loops = function.irreducible_loops();
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].nodes, [1, 2].map(NodeIndex::new));
+    assert_eq!(loops[0].entries, [1, 2].map(NodeIndex::new));
+
+    // only entered through block 1 once the edge from the entry to block 2 is gone
+    let edge = function
+        .graph()
+        .find_edge(NodeIndex::new(0), NodeIndex::new(2))
+        .unwrap();
+    function.graph_mut().remove_edge(edge);
+    assert!(function.irreducible_loops().is_empty());
+}
@@ -0,0 +1,231 @@
+use cfg::{listing, ssa::structuring::structure_conditionals};
+
+fn assert_round_trip(input: &str) {
+    let function = listing::parse(input).unwrap();
+    assert_eq!(listing::print(&function), input);
+}
+
+#[test]
+fn round_trip() {
+    assert_round_trip(
+        r#"function 3 test
+params a, b, ...
+
+block 0 entry
+    local c = @print
+    d, e = a.x, a["not a name"]
+    @"weird global" = {1, [2] = "two\n", [b] = (...)}
+    c(-1, -(1), (-1) ^ 2, not (a == b), (#a) .. "x", 5LL, -5LL, 7ULL, 2i, -(2i), inf, -inf)
+    f = a:method((c(b)), closure [copy a, ref b])
+    parallel g, h = h, g
+    ;
+    if a
+    -> t 1 [i = a]
+    -> e 2
+
+block 1
+    numforinit j, k, l = 1, 10, i
+    -> u 3
+
+block 2
+    -- a comment
+    genericforinit generalized m, n, o = a, nil, nil
+    return a, b
+
+block 3
+    numfornext j = j, k, l
+    if j <= k then
+        setlist d 1 = j tail c()
+    else
+        close j
+    end
+    return
+"#,
+    );
+}
+
+#[test]
+fn parse_error() {
+    let error = listing::parse("function 0\n\nblock 0 entry\n    a = \n").unwrap_err();
+    assert_eq!(error.line, 4);
+}
+
+#[test]
+fn structure_and() {
+    let mut function = listing::parse(
+        r#"function 0
+params a, b
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    if b
+    -> t 3
+    -> e 2
+
+block 2
+    return
+
+block 3
+    @print(a, b)
+    -> u 2
+"#,
+    )
+    .unwrap();
+    assert!(structure_conditionals(&mut function, false));
+    assert_eq!(
+        listing::print(&function),
+        r#"function 0
+params a, b
+
+block 0 entry
+    if a and b
+    -> t 3
+    -> e 2
+
+block 2
+    return
+
+block 3
+    @print(a, b)
+    -> u 2
+"#
+    );
+}
+
+#[test]
+fn structure_if_expression() {
+    let input = r#"function 0
+params a, x, y
+
+block 0 entry
+    c = y
+    if a
+    -> t 1
+    -> e 2 [d = c]
+
+block 1
+    b = x
+    -> u 2 [d = b]
+
+block 2
+    return d
+"#;
+    // x might be falsy, so this can't be written as `a and x or c`
+    let mut function = listing::parse(input).unwrap();
+    assert!(!structure_conditionals(&mut function, false));
+
+    let mut function = listing::parse(input).unwrap();
+    assert!(structure_conditionals(&mut function, true));
+    let output = listing::print(&function);
+    assert!(output.contains("d = if a then x else c\n"));
+    assert_round_trip(&output);
+}
+
+// `if a then d = x else d = y end`, with the values passed to the join by both assigners
+fn diamond(then_value: &str, else_value: &str) -> String {
+    format!(
+        r#"function 0
+params a, x, y
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    b = {}
+    -> u 3 [d = b]
+
+block 2
+    c = {}
+    -> u 3 [d = c]
+
+block 3
+    return d
+"#,
+        then_value, else_value
+    )
+}
+
+#[test]
+fn structure_diamond() {
+    let mut function = listing::parse(&diamond("1", "2")).unwrap();
+    assert!(structure_conditionals(&mut function, false));
+    assert_eq!(
+        listing::print(&function),
+        r#"function 0
+params a, x, y
+
+block 0 entry
+    d = (a and 1) or 2
+    -> u 3 [d = d]
+
+block 3
+    return d
+"#
+    );
+
+    let mut function = listing::parse(&diamond("false", "true")).unwrap();
+    assert!(structure_conditionals(&mut function, false));
+    assert!(listing::print(&function).contains("d = not a\n"));
+}
+
+#[test]
+fn structure_diamond_if_expression() {
+    let input = diamond("x", "y");
+    let mut function = listing::parse(&input).unwrap();
+    assert!(!structure_conditionals(&mut function, false));
+    assert_eq!(listing::print(&function), input);
+
+    let mut function = listing::parse(&input).unwrap();
+    assert!(structure_conditionals(&mut function, true));
+    let output = listing::print(&function);
+    assert!(output.contains("d = if a then x else y\n"));
+    assert_round_trip(&output);
+
+    // reading a global can call `__index`, the assignments are kept as statements
+    let input = diamond("@x", "y");
+    let mut function = listing::parse(&input).unwrap();
+    assert!(!structure_conditionals(&mut function, true));
+    assert_eq!(listing::print(&function), input);
+}
+
+#[test]
+fn names_across_prints() {
+    let mut function = listing::parse(
+        r#"function 0
+
+block 0 entry
+    a = 1
+    return a
+"#,
+    )
+    .unwrap();
+    let mut names = listing::Names::default();
+    listing::print_with_names(&function, &mut names);
+
+    // a local created by a pass isn't named, but keeps its name in later prints
+    let local = ast::RcLocal::default();
+    let entry = function.entry().unwrap();
+    function.block_mut(entry).unwrap().insert(
+        0,
+        ast::Assign::new(vec![local.clone().into()], vec![ast::Literal::Nil.into()]).into(),
+    );
+    let output = listing::print_with_names(&function, &mut names);
+    assert_eq!(
+        output,
+        r#"function 0
+
+block 0 entry
+    v1 = nil
+    a = 1
+    return a
+"#
+    );
+    assert_eq!(listing::print_with_names(&function, &mut names), output);
+    assert_eq!(local.0 .0.lock().0, None);
+}
@@ -0,0 +1,88 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use cfg::{function::Function, listing::Names};
+
+use crate::pass::PASSES;
+
+// the stages the ir can be dumped at, in order. passes are dumped every time they run
+pub fn stages() -> impl Iterator<Item = &'static str> {
+    ["lift", "ssa"]
+        .into_iter()
+        .chain(PASSES.iter().map(|pass| pass.name()))
+        .chain(["destruct"])
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dump {
+    pub directory: PathBuf,
+    pub stages: Vec<String>,
+    // indices of functions in the order the frontend lifted them (main is 0),
+    // every function is dumped if this is empty
+    pub functions: Vec<usize>,
+}
+
+impl Dump {
+    pub(crate) fn dumper(&self, function: usize) -> Option<Dumper<'_>> {
+        if self.stages.is_empty()
+            || (!self.functions.is_empty() && !self.functions.contains(&function))
+        {
+            return None;
+        }
+        Some(Dumper {
+            dump: self,
+            function,
+            sequence: 0,
+            names: Names::default(),
+        })
+    }
+}
+
+pub(crate) struct Dumper<'a> {
+    dump: &'a Dump,
+    function: usize,
+    // so that the files are listed in the order they were written
+    sequence: usize,
+    // shared by every dump of the function so that the same local has the same name in all
+    // of them, the locals are only named at the very end
+    names: Names,
+}
+
+impl Dumper<'_> {
+    // `iteration` is the round of the pass manager for stages that are passes
+    pub fn dump(
+        &mut self,
+        stage: &str,
+        iteration: Option<usize>,
+        function: &Function,
+    ) -> Result<(), String> {
+        if !self.dump.stages.iter().any(|s| s == stage) {
+            return Ok(());
+        }
+        let name = match iteration {
+            Some(iteration) => format!(
+                "{}-{:02}-{}-{}",
+                self.function, self.sequence, stage, iteration
+            ),
+            None => format!("{}-{:02}-{}", self.function, self.sequence, stage),
+        };
+        self.sequence += 1;
+
+        let path = self.dump.directory.join(name);
+        std::fs::create_dir_all(&self.dump.directory)
+            .and_then(|_| {
+                cfg::dot::render_with_names_to(
+                    function,
+                    &mut self.names,
+                    &mut BufWriter::new(File::create(path.with_extension("dot"))?),
+                )
+            })
+            .and_then(|_| {
+                cfg::listing::render_with_names_to(
+                    function,
+                    &mut self.names,
+                    &mut BufWriter::new(File::create(path.with_extension("txt"))?),
+                )
+            })
+            .map_err(|err| format!("failed to dump {}: {}", path.display(), err))
+    }
+}
@@ -0,0 +1,514 @@
+use ast::{
+    compound_assignments::reconstruct_compound_assignments,
+    formatter::Formatter,
+    interpolated_strings::reconstruct_interpolated_strings,
+    local_declarations::LocalDeclarer,
+    method_definitions::reconstruct_method_definitions,
+    name_locals::{name_locals, name_locals_stable},
+    replace_locals::replace_locals,
+    Traverse,
+};
+
+use std::{
+    any::Any,
+    cell::Cell,
+    fmt::Write,
+    panic,
+    sync::{Once, OnceLock},
+};
+
+use by_address::ByAddress;
+use cfg::{function::Function, ssa};
+use indexmap::IndexMap;
+use parking_lot::Mutex;
+use rayon::prelude::*;
+use rustc_hash::{FxHashMap, FxHashSet};
+use triomphe::Arc;
+use web_time::Instant;
+
+pub use options::{
+    Budget, DecompileOptions, Dialect, IndentationMode, Naming, OpcodeMap, TableLayout,
+};
+use pass::{Pass, PassManager};
+pub use result::{DecompileResult, FunctionResult, FunctionStatus, Timings};
+
+pub mod dump;
+mod options;
+pub mod pass;
+mod result;
+
+pub struct LiftedFunction {
+    // closures in other functions refer to this function through this, the body is filled
+    // in once it's decompiled
+    pub ast_function: Arc<Mutex<ast::Function>>,
+    pub function: Function,
+    pub upvalues: Vec<ast::RcLocal>,
+}
+
+// everything that is specific to a bytecode format, the rest of the pipeline is shared
+pub trait Frontend {
+    // lifts every function in the chunk, the main function must come first. the order must
+    // only depend on the bytecode, it decides the order functions are reported in
+    fn lift(&self) -> Result<Vec<LiftedFunction>, String>;
+
+    // whether `a.b(a)` can be turned into `a:b()`.
+    // this isn't the case if the vm has a dedicated instruction for method calls
+    // (ex. luau's NAMECALL, which invokes __namecall)
+    fn structure_method_calls(&self) -> bool {
+        true
+    }
+
+    // the dialect the output is in unless the options say otherwise
+    fn dialect(&self) -> Dialect;
+}
+
+// the ids of the locals a function creates, see `decompile`
+const LOCAL_IDS_PER_FUNCTION: u64 = 1 << 32;
+
+// errors are for chunks that couldn't be lifted at all, functions that fail
+// are reported in the result
+pub fn decompile(
+    frontend: &dyn Frontend,
+    options: &DecompileOptions,
+) -> Result<DecompileResult, String> {
+    let mut timings = Timings::default();
+    let start = Instant::now();
+    // locals are hashed and ordered by id, see `ast::RcLocal`. lifting happens on this thread,
+    // every function then creates its locals in its own range of ids on whichever thread
+    // it's on, so the ids don't depend on scheduling and are unique once functions are linked
+    ast::set_next_local_id(0);
+    install_panic_hook();
+    // a frontend that panics on malformed bytecode fails the chunk, not the caller
+    let suppress_panics = SUPPRESS_PANICS.replace(true);
+    let lifted = panic::catch_unwind(panic::AssertUnwindSafe(|| frontend.lift()));
+    SUPPRESS_PANICS.set(suppress_panics);
+    let lifted =
+        lifted.map_err(|payload| format!("failed to lift: {}", panic_message(&*payload)))??;
+    let Some(main) = lifted.first().map(|lifted| lifted.ast_function.clone()) else {
+        return Err("the chunk has no functions".to_string());
+    };
+    debug_assert!(ast::next_local_id() < LOCAL_IDS_PER_FUNCTION);
+    let function_count = lifted.len() as u64;
+    timings.lift = start.elapsed();
+    let mut disabled_passes = options
+        .disabled_passes
+        .iter()
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>();
+    if !frontend.structure_method_calls() {
+        disabled_passes.push(pass::StructureMethodCalls.name());
+    }
+    let pass_manager = PassManager::new(&disabled_passes);
+    let dialect = options.dialect.unwrap_or_else(|| frontend.dialect());
+
+    // functions are independent until their upvalues are linked, the results are
+    // collected in the order they were lifted so the output doesn't depend on scheduling
+    let decompile_all = || {
+        lifted
+            .into_par_iter()
+            .enumerate()
+            .map(|(index, lifted)| {
+                let ast_function = lifted.ast_function.clone();
+                let dumper = options.dump.dumper(index);
+                let mut args = panic::AssertUnwindSafe(Some((lifted, &pass_manager, dumper)));
+                let suppress_panics = SUPPRESS_PANICS.replace(true);
+                let result = panic::catch_unwind(move || {
+                    let (lifted, pass_manager, dumper) = args.take().unwrap();
+                    ast::set_next_local_id((index as u64 + 1) * LOCAL_IDS_PER_FUNCTION);
+                    decompile_function(lifted, index, options, dialect, pass_manager, dumper)
+                });
+                SUPPRESS_PANICS.set(suppress_panics);
+                (ast_function, result)
+            })
+            .collect::<Vec<_>>()
+    };
+    let results = if options.threads == 0 {
+        decompile_all()
+    } else {
+        thread_pool(options.threads)?.install(decompile_all)
+    };
+    // this thread might have decompiled some of the functions, locals created from here on
+    // come after all of them
+    ast::set_next_local_id((function_count + 1) * LOCAL_IDS_PER_FUNCTION);
+
+    let mut functions = Vec::with_capacity(results.len());
+    let mut upvalues = results
+        .into_iter()
+        .map(|(ast_function, result)| match result {
+            Ok(decompiled) => {
+                timings += decompiled.timings;
+                functions.push(FunctionResult {
+                    status: match decompiled.fell_back {
+                        Some(reason) => FunctionStatus::FellBack(reason),
+                        None => FunctionStatus::Ok,
+                    },
+                    gotos: decompiled.gotos,
+                    warnings: decompiled.warnings,
+                });
+                (decompiled.ast_function, decompiled.upvalues)
+            }
+            Err(payload) => {
+                ast_function
+                    .lock()
+                    .body
+                    .push(ast::Comment::new("failed to decompile".to_string()).into());
+                functions.push(FunctionResult {
+                    status: FunctionStatus::Failed(panic_message(&*payload)),
+                    gotos: 0,
+                    warnings: Vec::new(),
+                });
+                (ByAddress(ast_function), Vec::new())
+            }
+        })
+        .collect::<FxHashMap<_, _>>();
+
+    let start = Instant::now();
+    let main = ByAddress(main);
+    upvalues.remove(&main);
+    let mut body = Arc::try_unwrap(main.0).unwrap().into_inner().body;
+    link_upvalues(&mut body, &mut upvalues);
+    reconstruct_method_definitions(&mut body, options.infer_methods);
+    name_locals(&mut body, options.naming == Naming::Generated);
+    let mut source = String::new();
+    if let Some(header) = &options.header {
+        for line in header.lines() {
+            writeln!(source, "-- {}", line).unwrap();
+        }
+    }
+    Formatter::format(
+        &body,
+        &mut source,
+        options.indentation,
+        options.table_layout,
+        options.max_line_width,
+    )
+    .unwrap();
+    timings.format = start.elapsed();
+
+    Ok(DecompileResult {
+        source,
+        functions,
+        timings,
+        dialect,
+    })
+}
+
+// `decompile` is called for every chunk, so the pools are kept around instead of spawning
+// the threads again every time. there's one per number of threads
+fn thread_pool(threads: usize) -> Result<Arc<rayon::ThreadPool>, String> {
+    static POOLS: OnceLock<Mutex<FxHashMap<usize, Arc<rayon::ThreadPool>>>> = OnceLock::new();
+    let mut pools = POOLS.get_or_init(Default::default).lock();
+    if let Some(pool) = pools.get(&threads) {
+        return Ok(pool.clone());
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let pool = Arc::new(pool);
+    pools.insert(threads, pool.clone());
+    Ok(pool)
+}
+
+thread_local! {
+    // whether panics on this thread are caught and reported in the result
+    static SUPPRESS_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+// the hook is shared by every thread, so it's installed once instead of being swapped out
+// while functions are decompiled, which would race with other calls to `decompile`
+fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !SUPPRESS_PANICS.get() {
+                prev_hook(info);
+            }
+        }));
+    });
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "panicked".to_string()
+    }
+}
+
+struct DecompiledFunction {
+    ast_function: ByAddress<Arc<Mutex<ast::Function>>>,
+    upvalues: Vec<ast::RcLocal>,
+    timings: Timings,
+    gotos: usize,
+    // why structuring stopped early
+    fell_back: Option<String>,
+    warnings: Vec<String>,
+}
+
+// the work done for a function so far, see `Budget`
+struct Meter {
+    budget: Budget,
+    start: Instant,
+    iterations: usize,
+}
+
+impl Meter {
+    fn new(budget: Budget) -> Self {
+        Self {
+            budget,
+            start: Instant::now(),
+            iterations: 0,
+        }
+    }
+
+    // counts an iteration, returns why the budget is exceeded if it is
+    fn step(&mut self) -> Option<String> {
+        self.iterations += 1;
+        if let Some(iterations) = self
+            .budget
+            .iterations
+            .filter(|&iterations| self.iterations > iterations)
+        {
+            Some(format!("more than {} iterations", iterations))
+        } else {
+            self.budget
+                .time
+                .filter(|&time| self.start.elapsed() > time)
+                .map(|time| format!("took longer than {:?}", time))
+        }
+    }
+}
+
+fn decompile_function(
+    lifted: LiftedFunction,
+    index: usize,
+    options: &DecompileOptions,
+    dialect: Dialect,
+    pass_manager: &PassManager,
+    mut dumper: Option<dump::Dumper>,
+) -> DecompiledFunction {
+    let LiftedFunction {
+        ast_function,
+        mut function,
+        upvalues: upvalues_in,
+    } = lifted;
+    let mut warnings = Vec::new();
+    // the function is decompiled even if it can't be dumped, the first failure is reported
+    let mut dump_ir = |stage: &str, iteration: Option<usize>, function: &Function| {
+        if let Some(Err(err)) = dumper
+            .as_mut()
+            .map(|dumper| dumper.dump(stage, iteration, function))
+        {
+            warnings.push(err);
+            dumper = None;
+        }
+    };
+    let mut timings = Timings::default();
+    dump_ir("lift", None, &function);
+
+    let mut meter = Meter::new(options.budget);
+    let too_large = options
+        .budget
+        .blocks
+        .filter(|&blocks| function.graph().node_count() > blocks)
+        .map(|blocks| format!("more than {} blocks", blocks));
+    let start = Instant::now();
+    let (local_count, local_groups, upvalue_in_groups, upvalue_passed_groups) =
+        cfg::ssa::construct(&mut function, &upvalues_in);
+    let upvalue_to_group = upvalue_in_groups
+        .into_iter()
+        .chain(
+            upvalue_passed_groups
+                .into_iter()
+                .map(|m| (ast::RcLocal::default(), m)),
+        )
+        .flat_map(|(i, g)| g.into_iter().map(move |u| (u, i.clone())))
+        .collect::<IndexMap<_, _>>();
+    // TODO: do we even need this?
+    let local_to_group = local_groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(i, g)| g.into_iter().map(move |l| (l, i)))
+        .collect::<FxHashMap<_, _>>();
+    timings.ssa = start.elapsed();
+    dump_ir("ssa", None, &function);
+
+    let start = Instant::now();
+    let mut fell_back = too_large.clone();
+    if fell_back.is_none() {
+        fell_back = pass_manager.run_with_observer(
+            &mut function,
+            &mut pass::Context::new(
+                &local_to_group,
+                &upvalue_to_group,
+                dialect.has_if_expressions(),
+            ),
+            || meter.step(),
+            |pass, round, function| dump_ir(pass.name(), Some(round), function),
+        );
+    }
+    timings.passes = start.elapsed();
+
+    let start = Instant::now();
+    ssa::Destructor::new(
+        &mut function,
+        upvalue_to_group,
+        upvalues_in.iter().cloned().collect(),
+        local_count,
+    )
+    .destruct();
+    timings.destruct = start.elapsed();
+    dump_ir("destruct", None, &function);
+
+    let start = Instant::now();
+    let params = std::mem::take(&mut function.parameters);
+    let is_variadic = function.is_variadic;
+    // once the budget is exceeded it stays exceeded, so the restructurer stops right away
+    // if the passes did
+    let outer_locals = upvalues_in
+        .iter()
+        .chain(params.iter())
+        .cloned()
+        .collect::<FxHashSet<_>>();
+    let structured = restructure::lift(function, outer_locals.clone(), dialect.has_goto(), || {
+        too_large.clone().or_else(|| meter.step())
+    });
+    let fell_back = fell_back.or(structured.stopped);
+    if structured.dispatched != 0 {
+        warnings.push(format!(
+            "{} blocks written as a state machine, {} has no goto",
+            structured.dispatched, dialect
+        ));
+    }
+    let block = Arc::new(structured.block.into());
+    LocalDeclarer::default().declare_locals(
+        // TODO: why does block.clone() not work?
+        Arc::clone(&block),
+        &outer_locals,
+    );
+    let mut body = Arc::try_unwrap(block).unwrap().into_inner();
+    if dialect == Dialect::Luau {
+        reconstruct_compound_assignments(&mut body);
+        reconstruct_interpolated_strings(&mut body);
+    }
+    timings.restructure = start.elapsed();
+    if let Some(reason) = &fell_back {
+        body.insert(
+            0,
+            ast::Comment::new(format!("structuring stopped early, {}", reason)).into(),
+        );
+    }
+    let gotos = count_gotos(&body);
+
+    {
+        let mut ast_function = ast_function.lock();
+        if options.naming == Naming::Stable {
+            name_locals_stable(
+                &stable_prefix(ast_function.name.as_deref(), index),
+                &params,
+                &body,
+            );
+        }
+        ast_function.body = body;
+        ast_function.parameters = params;
+        ast_function.is_variadic = is_variadic;
+    }
+    DecompiledFunction {
+        ast_function: ByAddress(ast_function),
+        upvalues: upvalues_in,
+        timings,
+        gotos,
+        fell_back,
+        warnings,
+    }
+}
+
+// derived from the function's name so that names don't change when functions are added or
+// removed before it, characters that can't be part of an identifier are replaced.
+// only functions without a name fall back to their index
+fn stable_prefix(name: Option<&str>, index: usize) -> String {
+    match name {
+        Some(name) if !name.is_empty() => {
+            let mut prefix = name
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect::<String>();
+            if prefix.starts_with(|c: char| c.is_ascii_digit()) {
+                prefix.insert(0, '_');
+            }
+            prefix
+        }
+        _ => format!("f{}", index),
+    }
+}
+
+// closures are counted as their own functions
+fn count_gotos(block: &ast::Block) -> usize {
+    block
+        .iter()
+        .map(|statement| match statement {
+            ast::Statement::Goto(_) => 1,
+            ast::Statement::If(r#if) => {
+                count_gotos(&r#if.then_block.lock()) + count_gotos(&r#if.else_block.lock())
+            }
+            ast::Statement::While(r#while) => count_gotos(&r#while.block.lock()),
+            ast::Statement::Repeat(repeat) => count_gotos(&repeat.block.lock()),
+            ast::Statement::NumericFor(numeric_for) => count_gotos(&numeric_for.block.lock()),
+            ast::Statement::GenericFor(generic_for) => count_gotos(&generic_for.block.lock()),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn link_upvalues(
+    body: &mut ast::Block,
+    upvalues: &mut FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>>,
+) {
+    for stat in &mut body.0 {
+        stat.traverse_rvalues(&mut |rvalue| {
+            if let ast::RValue::Closure(closure) = rvalue {
+                // the same function can be closed over more than once
+                let old_upvalues = &upvalues[&closure.function];
+                let mut function = closure.function.lock();
+                // TODO: inefficient, try constructing a map of all up -> new up first
+                // and then call replace_locals on main body
+                let mut local_map =
+                    FxHashMap::with_capacity_and_hasher(old_upvalues.len(), Default::default());
+                for (old, new) in
+                    old_upvalues
+                        .iter()
+                        .zip(closure.upvalues.iter().map(|u| match u {
+                            ast::Upvalue::Copy(l) | ast::Upvalue::Ref(l) => l,
+                        }))
+                {
+                    local_map.insert(old.clone(), new.clone());
+                }
+                link_upvalues(&mut function.body, upvalues);
+                replace_locals(&mut function.body, &local_map);
+            }
+        });
+        match stat {
+            ast::Statement::If(r#if) => {
+                link_upvalues(&mut r#if.then_block.lock(), upvalues);
+                link_upvalues(&mut r#if.else_block.lock(), upvalues);
+            }
+            ast::Statement::While(r#while) => {
+                link_upvalues(&mut r#while.block.lock(), upvalues);
+            }
+            ast::Statement::Repeat(repeat) => {
+                link_upvalues(&mut repeat.block.lock(), upvalues);
+            }
+            ast::Statement::NumericFor(numeric_for) => {
+                link_upvalues(&mut numeric_for.block.lock(), upvalues);
+            }
+            ast::Statement::GenericFor(generic_for) => {
+                link_upvalues(&mut generic_for.block.lock(), upvalues);
+            }
+            _ => {}
+        }
+    }
+}
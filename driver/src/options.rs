@@ -0,0 +1,204 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+pub use ast::formatter::{IndentationMode, TableLayout};
+
+use crate::dump::Dump;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Naming {
+    // every local is given a generated name
+    #[default]
+    Generated,
+    // names from debug info are kept, locals without one are given a generated name
+    Debug,
+    // locals are named after the function, the register they're in and where they're first
+    // defined, so most names stay the same when the bytecode changes a little
+    Stable,
+}
+
+impl Naming {
+    pub const NAMES: &'static [&'static str] = &["generated", "debug", "stable"];
+}
+
+impl FromStr for Naming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "generated" => Ok(Self::Generated),
+            "debug" => Ok(Self::Debug),
+            "stable" => Ok(Self::Stable),
+            _ => Err(format!("unknown naming {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Naming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Generated => write!(f, "generated"),
+            Self::Debug => write!(f, "debug"),
+            Self::Stable => write!(f, "stable"),
+        }
+    }
+}
+
+// the language the output is meant for, this decides which syntax can be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Lua51,
+    Lua52,
+    LuaJit,
+    Luau,
+}
+
+impl Dialect {
+    pub const NAMES: &'static [&'static str] = &["lua51", "lua52", "luajit", "luau"];
+
+    // goto was added in lua 5.2, luajit has it too
+    pub fn has_goto(&self) -> bool {
+        matches!(self, Self::Lua52 | Self::LuaJit)
+    }
+
+    pub fn has_if_expressions(&self) -> bool {
+        matches!(self, Self::Luau)
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lua51" => Ok(Self::Lua51),
+            "lua52" => Ok(Self::Lua52),
+            "luajit" => Ok(Self::LuaJit),
+            "luau" => Ok(Self::Luau),
+            _ => Err(format!("unknown dialect {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lua51 => write!(f, "lua51"),
+            Self::Lua52 => write!(f, "lua52"),
+            Self::LuaJit => write!(f, "luajit"),
+            Self::Luau => write!(f, "luau"),
+        }
+    }
+}
+
+// how operation codes are encoded in the bytecode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpcodeMap {
+    // op = op * key % 256, used by luau. roblox client bytecode uses 203
+    Key(u8),
+    // the contents of a lua 5.1 encoding file, see `lua51_deserializer::Encoding::parse`
+    Encoding(String),
+}
+
+impl Default for OpcodeMap {
+    fn default() -> Self {
+        Self::Key(1)
+    }
+}
+
+// limits on the work done for a single function. once one is reached, the function is
+// finished with the structure it has so far and the rest of its control flow is written
+// with gotos, see `FunctionStatus::FellBack`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+    // from the start of ssa construction
+    pub time: Option<Duration>,
+    // rounds of the pass manager and attempts of the restructurer, counted together
+    pub iterations: Option<usize>,
+    // functions with more blocks than this aren't structured at all
+    pub blocks: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DecompileOptions {
+    pub indentation: IndentationMode,
+    pub table_layout: TableLayout,
+    // lines longer than this are wrapped where possible
+    pub max_line_width: Option<usize>,
+    pub naming: Naming,
+    // functions only ever called as methods are written as methods, which renames their first
+    // parameter to `self` even if it had another name
+    pub infer_methods: bool,
+    // written as a comment at the top of the output
+    pub header: Option<String>,
+    pub opcode_map: OpcodeMap,
+    // names of passes in `pass::PASSES` that shouldn't run
+    pub disabled_passes: Vec<String>,
+    // the frontend's dialect if none
+    pub dialect: Option<Dialect>,
+    pub dump: Dump,
+    // threads to decompile functions on, 0 uses the current rayon pool
+    pub threads: usize,
+    pub budget: Budget,
+}
+
+impl DecompileOptions {
+    pub fn indentation(mut self, indentation: IndentationMode) -> Self {
+        self.indentation = indentation;
+        self
+    }
+
+    pub fn table_layout(mut self, table_layout: TableLayout) -> Self {
+        self.table_layout = table_layout;
+        self
+    }
+
+    pub fn max_line_width(mut self, max_line_width: usize) -> Self {
+        self.max_line_width = Some(max_line_width);
+        self
+    }
+
+    pub fn naming(mut self, naming: Naming) -> Self {
+        self.naming = naming;
+        self
+    }
+
+    pub fn infer_methods(mut self, infer_methods: bool) -> Self {
+        self.infer_methods = infer_methods;
+        self
+    }
+
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.header = Some(header.into());
+        self
+    }
+
+    pub fn opcode_map(mut self, opcode_map: OpcodeMap) -> Self {
+        self.opcode_map = opcode_map;
+        self
+    }
+
+    pub fn disable_pass(mut self, name: impl Into<String>) -> Self {
+        self.disabled_passes.push(name.into());
+        self
+    }
+
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = Some(dialect);
+        self
+    }
+
+    pub fn dump(mut self, dump: Dump) -> Self {
+        self.dump = dump;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn budget(mut self, budget: Budget) -> Self {
+        self.budget = budget;
+        self
+    }
+}
@@ -0,0 +1,227 @@
+use cfg::{
+    function::Function,
+    ssa::{
+        self,
+        structuring::{structure_conditionals, structure_jumps, structure_method_calls},
+    },
+};
+use indexmap::IndexMap;
+use petgraph::{
+    algo::dominators::{simple_fast, Dominators},
+    stable_graph::NodeIndex,
+};
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Analysis {
+    Dominators,
+}
+
+// analyses are computed lazily and cached until a pass that invalidates them changes something
+pub struct Context<'a> {
+    pub local_to_group: &'a FxHashMap<ast::RcLocal, usize>,
+    pub upvalue_to_group: &'a IndexMap<ast::RcLocal, ast::RcLocal>,
+    // whether the dialect has if expressions, see `structure_conditionals`
+    pub if_expressions: bool,
+    dominators: Option<Dominators<NodeIndex>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(
+        local_to_group: &'a FxHashMap<ast::RcLocal, usize>,
+        upvalue_to_group: &'a IndexMap<ast::RcLocal, ast::RcLocal>,
+        if_expressions: bool,
+    ) -> Self {
+        Self {
+            local_to_group,
+            upvalue_to_group,
+            if_expressions,
+            dominators: None,
+        }
+    }
+
+    // only valid in passes that require `Analysis::Dominators`
+    pub fn dominators(&self) -> &Dominators<NodeIndex> {
+        self.dominators.as_ref().unwrap()
+    }
+
+    fn compute(&mut self, analysis: Analysis, function: &Function) {
+        match analysis {
+            Analysis::Dominators => {
+                if self.dominators.is_none() {
+                    self.dominators =
+                        Some(simple_fast(function.graph(), function.entry().unwrap()));
+                }
+            }
+        }
+    }
+
+    fn invalidate(&mut self, analysis: Analysis) {
+        match analysis {
+            Analysis::Dominators => self.dominators = None,
+        }
+    }
+}
+
+pub trait Pass: Sync {
+    fn name(&self) -> &'static str;
+
+    // analyses that must be up to date when the pass runs
+    fn requires(&self) -> &'static [Analysis] {
+        &[]
+    }
+
+    // analyses that are no longer valid if the pass changed the function
+    fn invalidates(&self) -> &'static [Analysis] {
+        &[]
+    }
+
+    // returns whether the function was changed
+    fn run(&self, function: &mut Function, context: &Context) -> bool;
+}
+
+pub struct StructureJumps;
+
+impl Pass for StructureJumps {
+    fn name(&self) -> &'static str {
+        "structure-jumps"
+    }
+
+    fn requires(&self) -> &'static [Analysis] {
+        &[Analysis::Dominators]
+    }
+
+    fn invalidates(&self) -> &'static [Analysis] {
+        &[Analysis::Dominators]
+    }
+
+    fn run(&self, function: &mut Function, context: &Context) -> bool {
+        structure_jumps(function, context.dominators())
+    }
+}
+
+pub struct Inline;
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    // inlining only rewrites statements and doesn't report changes, it runs again
+    // whenever another pass changed something
+    fn run(&self, function: &mut Function, context: &Context) -> bool {
+        ssa::inline::inline(function, context.local_to_group, context.upvalue_to_group);
+        false
+    }
+}
+
+pub struct StructureConditionals;
+
+impl Pass for StructureConditionals {
+    fn name(&self) -> &'static str {
+        "structure-conditionals"
+    }
+
+    fn invalidates(&self) -> &'static [Analysis] {
+        &[Analysis::Dominators]
+    }
+
+    fn run(&self, function: &mut Function, context: &Context) -> bool {
+        structure_conditionals(function, context.if_expressions)
+    }
+}
+
+pub struct StructureMethodCalls;
+
+impl Pass for StructureMethodCalls {
+    fn name(&self) -> &'static str {
+        "structure-method-calls"
+    }
+
+    fn run(&self, function: &mut Function, _context: &Context) -> bool {
+        structure_method_calls(function)
+    }
+}
+
+pub struct RemoveUnnecessaryParams;
+
+impl Pass for RemoveUnnecessaryParams {
+    fn name(&self) -> &'static str {
+        "remove-unnecessary-params"
+    }
+
+    fn run(&self, function: &mut Function, _context: &Context) -> bool {
+        let mut local_map = FxHashMap::default();
+        // TODO: loop until returns false?
+        let changed = ssa::construct::remove_unnecessary_params(function, &mut local_map);
+        ssa::construct::apply_local_map(function, local_map);
+        changed
+    }
+}
+
+// in the order they run in
+pub const PASSES: &[&dyn Pass] = &[
+    &StructureJumps,
+    &Inline,
+    &StructureConditionals,
+    &StructureMethodCalls,
+    &RemoveUnnecessaryParams,
+];
+
+pub struct PassManager {
+    passes: Vec<&'static dyn Pass>,
+}
+
+impl PassManager {
+    // passes that aren't in `PASSES` are ignored
+    pub fn new(disabled: &[impl AsRef<str>]) -> Self {
+        Self {
+            passes: PASSES
+                .iter()
+                .copied()
+                .filter(|pass| !disabled.iter().any(|name| name.as_ref() == pass.name()))
+                .collect(),
+        }
+    }
+
+    // runs every pass in order until none of them change the function
+    pub fn run(&self, function: &mut Function, context: &mut Context) {
+        self.run_with_observer(function, context, || None, |_, _, _| {});
+    }
+
+    // `should_stop` is called before every round, the passes stop early if it returns a
+    // reason, which is then returned. the function is valid after every pass, so it can
+    // still be destructed and structured.
+    // `observer` is called after every pass with the round it ran in
+    pub fn run_with_observer(
+        &self,
+        function: &mut Function,
+        context: &mut Context,
+        mut should_stop: impl FnMut() -> Option<String>,
+        mut observer: impl FnMut(&dyn Pass, usize, &Function),
+    ) -> Option<String> {
+        let mut changed = true;
+        let mut round = 0;
+        while changed {
+            if let Some(reason) = should_stop() {
+                return Some(reason);
+            }
+            changed = false;
+            round += 1;
+            for &pass in &self.passes {
+                for &analysis in pass.requires() {
+                    context.compute(analysis, function);
+                }
+                let pass_changed = pass.run(function, context);
+                observer(pass, round, function);
+                if pass_changed {
+                    changed = true;
+                    for &analysis in pass.invalidates() {
+                        context.invalidate(analysis);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,105 @@
+use std::{fmt, ops::AddAssign, time::Duration};
+
+use crate::Dialect;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionStatus {
+    Ok,
+    // the body of the function is replaced by a comment
+    Failed(String),
+    // the function was decompiled, but not as well as it could've been
+    FellBack(String),
+}
+
+impl fmt::Display for FunctionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::Failed(reason) => write!(f, "failed: {}", reason),
+            Self::FellBack(reason) => write!(f, "fell back: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionResult {
+    pub status: FunctionStatus,
+    // gotos that couldn't be structured
+    pub gotos: usize,
+    pub warnings: Vec<String>,
+}
+
+// time spent in every stage of the pipeline, added up for all functions
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub lift: Duration,
+    pub ssa: Duration,
+    pub passes: Duration,
+    pub destruct: Duration,
+    pub restructure: Duration,
+    pub format: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.lift + self.ssa + self.passes + self.destruct + self.restructure + self.format
+    }
+}
+
+impl AddAssign for Timings {
+    fn add_assign(&mut self, other: Self) {
+        self.lift += other.lift;
+        self.ssa += other.ssa;
+        self.passes += other.passes;
+        self.destruct += other.destruct;
+        self.restructure += other.restructure;
+        self.format += other.format;
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "lift {:?}, ssa {:?}, passes {:?}, destruct {:?}, restructure {:?}, format {:?}",
+            self.lift, self.ssa, self.passes, self.destruct, self.restructure, self.format
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DecompileResult {
+    pub source: String,
+    // in the order the frontend lifted them, main is first
+    pub functions: Vec<FunctionResult>,
+    pub timings: Timings,
+    pub dialect: Dialect,
+}
+
+impl DecompileResult {
+    pub fn functions_failed(&self) -> usize {
+        self.functions
+            .iter()
+            .filter(|function| matches!(function.status, FunctionStatus::Failed(_)))
+            .count()
+    }
+
+    pub fn gotos(&self) -> usize {
+        self.functions.iter().map(|function| function.gotos).sum()
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &str> {
+        self.functions
+            .iter()
+            .flat_map(|function| function.warnings.iter().map(String::as_str))
+    }
+
+    // whether every function was decompiled without falling back, gotos or warnings
+    pub fn is_clean(&self) -> bool {
+        self.functions.iter().all(|function| {
+            function.status == FunctionStatus::Ok
+                && function.gotos == 0
+                && function.warnings.is_empty()
+        })
+    }
+}
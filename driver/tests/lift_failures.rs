@@ -0,0 +1,37 @@
+use driver::{DecompileOptions, Dialect, Frontend, LiftedFunction};
+
+struct NoFunctions;
+
+impl Frontend for NoFunctions {
+    fn lift(&self) -> Result<Vec<LiftedFunction>, String> {
+        Ok(Vec::new())
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Lua51
+    }
+}
+
+struct Panics;
+
+impl Frontend for Panics {
+    fn lift(&self) -> Result<Vec<LiftedFunction>, String> {
+        panic!("malformed")
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Lua51
+    }
+}
+
+#[test]
+fn no_functions() {
+    let result = driver::decompile(&NoFunctions, &DecompileOptions::default());
+    assert_eq!(result.err().unwrap(), "the chunk has no functions");
+}
+
+#[test]
+fn lift_panics() {
+    let result = driver::decompile(&Panics, &DecompileOptions::default());
+    assert_eq!(result.err().unwrap(), "failed to lift: malformed");
+}
@@ -7,6 +7,7 @@ pub use header::Header;
 use crate::{
     chunk::header::{Endianness, Format},
     function::Function,
+    instruction::encoding::Encoding,
 };
 
 pub mod header;
@@ -18,6 +19,10 @@ pub struct Chunk<'a> {
 
 impl<'a> Chunk<'a> {
     pub fn parse(input: &'a [u8]) -> IResult<&[u8], Self> {
+        Self::parse_with_encoding(input, &Encoding::default())
+    }
+
+    pub fn parse_with_encoding(input: &'a [u8], encoding: &Encoding) -> IResult<&'a [u8], Self> {
         let (input, header) = Header::parse(input)?;
         // TODO: pass header to Function::parse
         assert_eq!(header.version_number, 0x51);
@@ -28,7 +33,7 @@ impl<'a> Chunk<'a> {
         assert_eq!(header.instr_width as usize, mem::size_of::<u32>());
         assert_eq!(header.number_width as usize, mem::size_of::<f64>());
         assert!(!header.number_is_integral);
-        let (input, function) = Function::parse(input)?;
+        let (input, function) = Function::parse(input, encoding)?;
 
         Ok((input, Self { function }))
     }
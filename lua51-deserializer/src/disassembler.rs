@@ -0,0 +1,426 @@
+use std::fmt::{self, Write};
+
+use either::Either;
+
+use crate::{
+    argument::{Constant, RegisterOrConstant},
+    chunk::Chunk,
+    Function, Instruction, Value,
+};
+
+// a listing in the style of `luac -l -l`.
+// luac prints addresses to identify functions, we use their path in the closure tree
+// instead so listings of the same chunk can be diffed.
+pub fn disassemble(chunk: &Chunk, output: &mut impl Write) -> fmt::Result {
+    let source = source_name(chunk.function.name);
+    disassemble_function(&chunk.function, &source, "main", output)
+}
+
+impl fmt::Display for Chunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        disassemble(self, f)
+    }
+}
+
+fn source_name(name: &[u8]) -> String {
+    // the null terminator is included in function names
+    let name = name.strip_suffix(b"\0").unwrap_or(name);
+    match name {
+        [] => "=?".to_string(),
+        [b'@' | b'=', rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => "[string]".to_string(),
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+fn disassemble_function(
+    function: &Function,
+    source: &str,
+    path: &str,
+    output: &mut impl Write,
+) -> fmt::Result {
+    let code_length = function.code.len();
+    writeln!(
+        output,
+        "\n{} <{}:{},{}> ({} instruction{}, {} bytes)",
+        if path == "main" { "main" } else { "function" },
+        source,
+        function.line_defined,
+        function.last_line_defined,
+        code_length,
+        plural(code_length),
+        code_length * 4
+    )?;
+    writeln!(
+        output,
+        "{}{} param{}, {} slot{}, {} upvalue{}, {} local{}, {} constant{}, {} function{}",
+        function.number_of_parameters,
+        if function.vararg_flag != 0 { "+" } else { "" },
+        plural(function.number_of_parameters as usize),
+        function.maximum_stack_size,
+        plural(function.maximum_stack_size as usize),
+        function.number_of_upvalues,
+        plural(function.number_of_upvalues as usize),
+        function.locals.len(),
+        plural(function.locals.len()),
+        function.constants.len(),
+        plural(function.constants.len()),
+        function.closures.len(),
+        plural(function.closures.len()),
+    )?;
+
+    for (pc, instruction) in function.code.iter().enumerate() {
+        write!(output, "\t{}\t", pc + 1)?;
+        match function.positions.get(pc) {
+            Some(position) if position.source > 0 => write!(output, "[{}]\t", position.source)?,
+            _ => write!(output, "[-]\t")?,
+        }
+        let (operands, comment) = operands(function, pc, instruction, path);
+        write!(
+            output,
+            "{:<9}\t{}",
+            instruction.operation_code().name(),
+            operands
+        )?;
+        if let Some(comment) = comment {
+            write!(output, "\t; {}", comment)?;
+        }
+        writeln!(output)?;
+    }
+
+    writeln!(
+        output,
+        "constants ({}) for {}:",
+        function.constants.len(),
+        path
+    )?;
+    for (index, constant) in function.constants.iter().enumerate() {
+        writeln!(output, "\t{}\t{}", index + 1, value(constant))?;
+    }
+    writeln!(output, "locals ({}) for {}:", function.locals.len(), path)?;
+    for (index, local) in function.locals.iter().enumerate() {
+        writeln!(
+            output,
+            "\t{}\t{}\t{}\t{}",
+            index,
+            String::from_utf8_lossy(local.name),
+            local.range.start + 1,
+            local.range.end + 1
+        )?;
+    }
+    writeln!(
+        output,
+        "upvalues ({}) for {}:",
+        function.upvalues.len(),
+        path
+    )?;
+    for (index, upvalue) in function.upvalues.iter().enumerate() {
+        // upvalue names include the null terminator
+        let upvalue = upvalue.strip_suffix(b"\0").unwrap_or(upvalue);
+        writeln!(output, "\t{}\t{}", index, String::from_utf8_lossy(upvalue))?;
+    }
+
+    for (index, closure) in function.closures.iter().enumerate() {
+        disassemble_function(closure, source, &format!("{}.{}", path, index), output)?;
+    }
+
+    Ok(())
+}
+
+fn value(value: &Value) -> String {
+    match value {
+        Value::Nil => "nil".to_string(),
+        Value::Boolean(value) => value.to_string(),
+        Value::Number(value) => value.to_string(),
+        Value::String(value) => {
+            let mut escaped = String::with_capacity(value.len() + 2);
+            escaped.push('"');
+            for &byte in value.iter() {
+                match byte {
+                    b'"' => escaped.push_str("\\\""),
+                    b'\\' => escaped.push_str("\\\\"),
+                    0x07 => escaped.push_str("\\a"),
+                    0x08 => escaped.push_str("\\b"),
+                    0x0C => escaped.push_str("\\f"),
+                    b'\n' => escaped.push_str("\\n"),
+                    b'\r' => escaped.push_str("\\r"),
+                    b'\t' => escaped.push_str("\\t"),
+                    0x0B => escaped.push_str("\\v"),
+                    0x20..=0x7E => escaped.push(byte as char),
+                    _ => write!(escaped, "\\{:03}", byte).unwrap(),
+                }
+            }
+            escaped.push('"');
+            escaped
+        }
+    }
+}
+
+// constants are printed as negative numbers like luac does
+fn rk(operand: &RegisterOrConstant) -> i64 {
+    match operand.0 {
+        Either::Left(register) => register.0 as i64,
+        Either::Right(constant) => -1 - constant.0 as i64,
+    }
+}
+
+fn kst(function: &Function, constant: &Constant) -> String {
+    function
+        .constants
+        .get(constant.0 as usize)
+        .map(value)
+        .unwrap_or_else(|| "?".to_string())
+}
+
+// luac prints global names without quotes
+fn global_name(function: &Function, constant: &Constant) -> String {
+    match function.constants.get(constant.0 as usize) {
+        Some(Value::String(name)) => String::from_utf8_lossy(name).into_owned(),
+        _ => kst(function, constant),
+    }
+}
+
+fn rk_comment(function: &Function, operand: &RegisterOrConstant) -> String {
+    match &operand.0 {
+        Either::Left(_) => "-".to_string(),
+        Either::Right(constant) => kst(function, constant),
+    }
+}
+
+fn upvalue_name(function: &Function, upvalue: u8) -> String {
+    function
+        .upvalues
+        .get(upvalue as usize)
+        .map(|name| String::from_utf8_lossy(name.strip_suffix(b"\0").unwrap_or(name)).into_owned())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn operands(
+    function: &Function,
+    pc: usize,
+    instruction: &Instruction,
+    path: &str,
+) -> (String, Option<String>) {
+    let jump_target = |skip: i32| format!("to {}", pc as i64 + 2 + skip as i64);
+    match instruction {
+        Instruction::Move {
+            destination,
+            source,
+        } => (format!("{} {}", destination.0, source.0), None),
+        Instruction::LoadConstant {
+            destination,
+            source,
+        } => (
+            format!("{} {}", destination.0, -1 - source.0 as i64),
+            Some(kst(function, source)),
+        ),
+        Instruction::LoadBoolean {
+            destination,
+            value,
+            skip_next,
+        } => (
+            format!("{} {} {}", destination.0, *value as u8, *skip_next as u8),
+            None,
+        ),
+        Instruction::LoadNil(registers) => (
+            format!(
+                "{} {}",
+                registers.first().map_or(0, |r| r.0),
+                registers.last().map_or(0, |r| r.0)
+            ),
+            None,
+        ),
+        Instruction::GetUpvalue {
+            destination,
+            upvalue,
+        } => (
+            format!("{} {}", destination.0, upvalue.0),
+            Some(upvalue_name(function, upvalue.0)),
+        ),
+        Instruction::GetGlobal {
+            destination,
+            global,
+        } => (
+            format!("{} {}", destination.0, -1 - global.0 as i64),
+            Some(global_name(function, global)),
+        ),
+        Instruction::GetIndex {
+            destination,
+            object,
+            key,
+        } => (
+            format!("{} {} {}", destination.0, object.0, rk(key)),
+            key.0.is_right().then(|| rk_comment(function, key)),
+        ),
+        Instruction::SetGlobal { destination, value } => (
+            format!("{} {}", value.0, -1 - destination.0 as i64),
+            Some(global_name(function, destination)),
+        ),
+        Instruction::SetUpvalue {
+            destination,
+            source,
+        } => (
+            format!("{} {}", source.0, destination.0),
+            Some(upvalue_name(function, destination.0)),
+        ),
+        Instruction::SetIndex { object, key, value } if key.0.is_right() || value.0.is_right() => (
+            format!("{} {} {}", object.0, rk(key), rk(value)),
+            Some(format!(
+                "{} {}",
+                rk_comment(function, key),
+                rk_comment(function, value)
+            )),
+        ),
+        Instruction::SetIndex { object, key, value } => {
+            (format!("{} {} {}", object.0, rk(key), rk(value)), None)
+        }
+        Instruction::NewTable {
+            destination,
+            array_size,
+            hash_size,
+        } => (
+            format!("{} {} {}", destination.0, array_size, hash_size),
+            None,
+        ),
+        Instruction::PrepMethodCall {
+            destination,
+            object,
+            method,
+            ..
+        } => (
+            format!("{} {} {}", destination.0, object.0, rk(method)),
+            method.0.is_right().then(|| rk_comment(function, method)),
+        ),
+        Instruction::Add {
+            destination,
+            lhs,
+            rhs,
+        }
+        | Instruction::Sub {
+            destination,
+            lhs,
+            rhs,
+        }
+        | Instruction::Mul {
+            destination,
+            lhs,
+            rhs,
+        }
+        | Instruction::Div {
+            destination,
+            lhs,
+            rhs,
+        }
+        | Instruction::Mod {
+            destination,
+            lhs,
+            rhs,
+        }
+        | Instruction::Pow {
+            destination,
+            lhs,
+            rhs,
+        } => (
+            format!("{} {} {}", destination.0, rk(lhs), rk(rhs)),
+            (lhs.0.is_right() || rhs.0.is_right()).then(|| {
+                format!(
+                    "{} {}",
+                    rk_comment(function, lhs),
+                    rk_comment(function, rhs)
+                )
+            }),
+        ),
+        Instruction::Minus {
+            destination,
+            operand,
+        }
+        | Instruction::Not {
+            destination,
+            operand,
+        }
+        | Instruction::Length {
+            destination,
+            operand,
+        } => (format!("{} {}", destination.0, operand.0), None),
+        Instruction::Concatenate {
+            destination,
+            operands,
+        } => (
+            format!(
+                "{} {} {}",
+                destination.0,
+                operands.first().map_or(0, |r| r.0),
+                operands.last().map_or(0, |r| r.0)
+            ),
+            None,
+        ),
+        Instruction::Jump(skip) => (skip.to_string(), Some(jump_target(*skip))),
+        Instruction::Equal { lhs, rhs, invert }
+        | Instruction::LessThan { lhs, rhs, invert }
+        | Instruction::LessThanOrEqual { lhs, rhs, invert } => (
+            format!("{} {} {}", !invert as u8, rk(lhs), rk(rhs)),
+            (lhs.0.is_right() || rhs.0.is_right()).then(|| {
+                format!(
+                    "{} {}",
+                    rk_comment(function, lhs),
+                    rk_comment(function, rhs)
+                )
+            }),
+        ),
+        Instruction::Test { value, invert } => (format!("{} {}", value.0, !invert as u8), None),
+        Instruction::TestSet {
+            destination,
+            value,
+            invert,
+        } => (
+            format!("{} {} {}", destination.0, value.0, !invert as u8),
+            None,
+        ),
+        Instruction::Call {
+            function,
+            arguments,
+            return_values,
+        } => (
+            format!("{} {} {}", function.0, arguments, return_values),
+            None,
+        ),
+        // the c operand of TAILCALL is always LUA_MULTRET + 1 and isn't kept
+        Instruction::TailCall {
+            function,
+            arguments,
+        } => (format!("{} {} 0", function.0, arguments), None),
+        Instruction::Return(register, values) => (format!("{} {}", register.0, values), None),
+        Instruction::IterateNumericForLoop { control, skip }
+        | Instruction::InitNumericForLoop { control, skip } => (
+            format!("{} {}", control[0].0, skip),
+            Some(jump_target(*skip)),
+        ),
+        Instruction::IterateGenericForLoop {
+            generator, vars, ..
+        } => (format!("{} {}", generator.0, vars.len()), None),
+        Instruction::SetList {
+            table,
+            number_of_elements,
+            block_number,
+        } => (
+            format!("{} {} {}", table.0, number_of_elements, block_number),
+            None,
+        ),
+        Instruction::Close(register) => (register.0.to_string(), None),
+        Instruction::Closure {
+            destination,
+            function,
+        } => (
+            format!("{} {}", destination.0, function.0),
+            Some(format!("{}.{}", path, function.0)),
+        ),
+        Instruction::VarArg(register, values) => (format!("{} {}", register.0, values), None),
+    }
+}
@@ -6,7 +6,7 @@ use nom::{
 };
 
 use crate::{
-    instruction::{position::Position, Instruction},
+    instruction::{encoding::Encoding, position::Position, Instruction},
     local::Local,
     value::{self, Value},
 };
@@ -29,7 +29,7 @@ pub struct Function<'a> {
 }
 
 impl<'a> Function<'a> {
-    pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Self> {
+    pub fn parse(input: &'a [u8], encoding: &Encoding) -> IResult<&'a [u8], Self> {
         let (input, name) = value::parse_string(input)?;
         let (input, line_defined) = le_u32(input)?;
         let (input, last_line_defined) = le_u32(input)?;
@@ -38,11 +38,13 @@ impl<'a> Function<'a> {
         let (input, vararg_flag) = le_u8(input)?;
         let (input, maximum_stack_size) = le_u8(input)?;
         let (input, code_length) = le_u32(input)?;
-        let (input, code) = count(Instruction::parse, code_length as usize)(input)?;
+        let (input, code) =
+            count(|i| Instruction::parse(i, encoding), code_length as usize)(input)?;
         let (input, constants_length) = le_u32(input)?;
         let (input, constants) = count(Value::parse, constants_length as usize)(input)?;
         let (input, closures_length) = le_u32(input)?;
-        let (input, closures) = count(Self::parse, closures_length as usize)(input)?;
+        let (input, closures) =
+            count(|i| Self::parse(i, encoding), closures_length as usize)(input)?;
         let (input, positions) = opt(Position::parse)(input)?;
         let (input, locals) = opt(Local::parse_list)(input)?;
         let (input, upvalues) = opt(value::parse_strings)(input)?;
@@ -1,5 +1,7 @@
 use either::Either;
 
+use super::encoding::RK_SIZE;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Register(pub u8);
 
@@ -15,10 +17,12 @@ pub struct Constant(pub u32);
 #[derive(Debug, Copy, Clone)]
 pub struct RegisterOrConstant(pub Either<Register, Constant>);
 
+// the highest bit of a b or c operand is set for constants
 impl From<u32> for RegisterOrConstant {
     fn from(value: u32) -> Self {
-        Self(if value > 255 {
-            Either::Right(Constant(value - 256))
+        const CONSTANT_BIT: u32 = 1 << (RK_SIZE - 1);
+        Self(if value & CONSTANT_BIT != 0 {
+            Either::Right(Constant(value & !CONSTANT_BIT))
         } else {
             Either::Left(Register(value as u8))
         })
@@ -0,0 +1,143 @@
+use std::{fs, path::Path};
+
+use num_traits::FromPrimitive;
+
+use super::operation_code::OperationCode;
+
+// the size of b and c, which `RegisterOrConstant` depends on
+pub const RK_SIZE: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub shift: u8,
+    pub size: u8,
+}
+
+impl Field {
+    pub const fn new(shift: u8, size: u8) -> Self {
+        Self { shift, size }
+    }
+
+    pub fn extract(&self, instruction: u32) -> u32 {
+        (instruction >> self.shift) & (u32::MAX >> (32 - self.size as u32))
+    }
+}
+
+// describes where the operation code and operands live in an instruction and which
+// raw operation code maps to which standard one.
+// a lot of custom 5.1 vms shuffle these to make bytecode harder to read.
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    pub operation_code: Field,
+    pub a: Field,
+    pub b: Field,
+    pub c: Field,
+    // usually overlaps b and c
+    pub b_x: Field,
+    // indexed by raw operation code
+    operation_codes: Vec<Option<OperationCode>>,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            operation_code: Field::new(0, 6),
+            a: Field::new(6, 8),
+            c: Field::new(14, RK_SIZE),
+            b: Field::new(23, RK_SIZE),
+            b_x: Field::new(14, 18),
+            operation_codes: (0..=u8::MAX).map(FromPrimitive::from_u8).collect(),
+        }
+    }
+}
+
+impl Encoding {
+    pub(crate) fn operation_code(&self, instruction: u32) -> Option<OperationCode> {
+        self.operation_codes
+            .get(self.operation_code.extract(instruction) as usize)
+            .copied()
+            .flatten()
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&source)
+    }
+
+    // one entry per line, `#` starts a comment:
+    //   op 0 6      field name, shift and size in bits (op, a, b, c or bx)
+    //   MOVE 12     standard operation code name and the raw value it is encoded as
+    // fields that aren't specified keep their standard position. b and c can be moved but
+    // are always 9 bits, the highest bit tells registers and constants apart. if any
+    // operation code is specified, raw values that aren't listed are rejected while decoding.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut encoding = Self::default();
+        let mut operation_codes = Vec::<Option<OperationCode>>::new();
+        for (line_number, line) in source.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: &str| format!("line {}: {}", line_number + 1, message);
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let number = |part: &str| {
+                part.parse::<u8>()
+                    .map_err(|_| error(&format!("invalid number `{}`", part)))
+            };
+            match parts[..] {
+                [name, shift, size] => {
+                    let field = Field::new(number(shift)?, number(size)?);
+                    if field.size == 0 || field.shift as u32 + field.size as u32 > 32 {
+                        return Err(error("field does not fit in an instruction"));
+                    }
+                    let max_size = match name {
+                        "op" => 8,
+                        "a" => 8,
+                        "b" | "c" => RK_SIZE,
+                        "bx" => 32,
+                        _ => return Err(error(&format!("unknown field `{}`", name))),
+                    };
+                    if matches!(name, "b" | "c") && field.size != RK_SIZE {
+                        return Err(error(&format!("`{}` must be {} bits", name, RK_SIZE)));
+                    }
+                    if field.size > max_size {
+                        return Err(error(&format!("`{}` is at most {} bits", name, max_size)));
+                    }
+                    *match name {
+                        "op" => &mut encoding.operation_code,
+                        "a" => &mut encoding.a,
+                        "b" => &mut encoding.b,
+                        "c" => &mut encoding.c,
+                        _ => &mut encoding.b_x,
+                    } = field;
+                }
+                [name, raw] => {
+                    let operation_code = OperationCode::from_name(name)
+                        .ok_or_else(|| error(&format!("unknown operation code `{}`", name)))?;
+                    let raw = number(raw)? as usize;
+                    if operation_codes.len() <= raw {
+                        operation_codes.resize(raw + 1, None);
+                    }
+                    if let Some(other) = operation_codes[raw] {
+                        return Err(error(&format!(
+                            "{} is already used by {}",
+                            raw,
+                            other.name()
+                        )));
+                    }
+                    operation_codes[raw] = Some(operation_code);
+                }
+                _ => {
+                    return Err(error(
+                        "expected `<field> <shift> <size>` or `<name> <value>`",
+                    ))
+                }
+            }
+        }
+        if !operation_codes.is_empty() {
+            encoding.operation_codes = operation_codes;
+        }
+
+        Ok(encoding)
+    }
+}
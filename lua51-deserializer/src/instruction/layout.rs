@@ -1,12 +1,6 @@
-use nom::{
-    error::{Error, ErrorKind, ParseError},
-    number::complete::le_u32,
-    Err, IResult,
-};
-use num_traits::FromPrimitive;
 use strum_macros::EnumDiscriminants;
 
-use super::OperationCode;
+use super::{encoding::Encoding, OperationCode};
 
 #[derive(Debug, EnumDiscriminants)]
 pub enum Layout {
@@ -18,36 +12,27 @@ pub enum Layout {
 }
 
 impl Layout {
-    pub fn parse(input: &[u8], operation_code: u8) -> IResult<&[u8], Self> {
-        let (input, instruction) = le_u32(input)?;
+    pub fn decode(instruction: u32, operation_code: OperationCode, encoding: &Encoding) -> Self {
+        let a = encoding.a.extract(instruction) as u8;
+        match operation_code.instruction_layout() {
+            LayoutDiscriminants::BC => {
+                let b = encoding.b.extract(instruction) as u16;
+                let c = encoding.c.extract(instruction) as u16;
 
-        match OperationCode::from_u8(operation_code).map(|o: OperationCode| o.instruction_layout())
-        {
-            Some(LayoutDiscriminants::BC) => {
-                let a = ((instruction >> 6) & 0xFF) as u8;
-                let c = ((instruction >> 14) & 0x1FF) as u16;
-                let b = ((instruction >> 23) & 0x1FF) as u16;
-
-                Ok((input, Self::BC { a, b, c }))
+                Self::BC { a, b, c }
             }
-            Some(LayoutDiscriminants::BX) => {
-                let a = ((instruction >> 6) & 0xFF) as u8;
-                let b_x = (instruction >> 14) & 0x3FFFF;
+            LayoutDiscriminants::BX => {
+                let b_x = encoding.b_x.extract(instruction);
 
-                Ok((input, Self::BX { a, b_x }))
+                Self::BX { a, b_x }
             }
-            Some(LayoutDiscriminants::BSx) => {
-                let a = ((instruction >> 6) & 0xFF) as u8;
-                let b_x = (instruction >> 14) & 0x3FFFF;
-                // subtract maximum 18 bit signed int
-                let b_sx = b_x as i32 - (((1 << 18) - 1) >> 1);
+            LayoutDiscriminants::BSx => {
+                let b_x = encoding.b_x.extract(instruction);
+                // subtract maximum signed int of the same width
+                let b_sx = (b_x as i64 - (((1i64 << encoding.b_x.size) - 1) >> 1)) as i32;
 
-                Ok((input, Self::BSx { a, b_sx }))
+                Self::BSx { a, b_sx }
             }
-            _ => Err(Err::Failure(Error::from_error_kind(
-                input,
-                ErrorKind::Switch,
-            ))),
         }
     }
 }
@@ -1,14 +1,16 @@
 use nom::{
     error::{Error, ErrorKind, ParseError},
+    number::complete::le_u32,
     Err, IResult,
 };
-use num_traits::ToPrimitive;
 
 use argument::{Constant, Function, Register, RegisterOrConstant, Upvalue};
+use encoding::Encoding;
 use layout::Layout;
 use operation_code::OperationCode;
 
 pub mod argument;
+pub mod encoding;
 mod layout;
 mod operation_code;
 pub mod position;
@@ -17,12 +19,12 @@ pub mod position;
 struct RawInstruction(OperationCode, Layout);
 
 impl RawInstruction {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        // TODO: we read the operation code and then the instruction including the operation code
-        // while parsing the layout.
-        // we should instead read the instruction here and pass it to Layout::parse
-        let operation_code = OperationCode::parse(input).map(|r| r.1)?;
-        let (input, layout) = Layout::parse(input, operation_code.to_u8().unwrap())?;
+    pub fn parse<'a>(input: &'a [u8], encoding: &Encoding) -> IResult<&'a [u8], Self> {
+        let (input, instruction) = le_u32(input)?;
+        let operation_code = encoding
+            .operation_code(instruction)
+            .ok_or_else(|| Err::Failure(Error::from_error_kind(input, ErrorKind::Switch)))?;
+        let layout = Layout::decode(instruction, operation_code, encoding);
 
         Ok((input, Self(operation_code, layout)))
     }
@@ -201,8 +203,51 @@ pub enum Instruction {
 }
 
 impl Instruction {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        let (input, instruction) = RawInstruction::parse(input)?;
+    pub(crate) fn operation_code(&self) -> OperationCode {
+        match self {
+            Self::Move { .. } => OperationCode::Move,
+            Self::LoadConstant { .. } => OperationCode::LoadConstant,
+            Self::LoadBoolean { .. } => OperationCode::LoadBoolean,
+            Self::LoadNil(_) => OperationCode::LoadNil,
+            Self::GetUpvalue { .. } => OperationCode::GetUpvalue,
+            Self::GetGlobal { .. } => OperationCode::GetGlobal,
+            Self::GetIndex { .. } => OperationCode::GetIndex,
+            Self::SetGlobal { .. } => OperationCode::SetGlobal,
+            Self::SetUpvalue { .. } => OperationCode::SetUpvalue,
+            Self::SetIndex { .. } => OperationCode::SetIndex,
+            Self::NewTable { .. } => OperationCode::NewTable,
+            Self::PrepMethodCall { .. } => OperationCode::PrepMethodCall,
+            Self::Add { .. } => OperationCode::Add,
+            Self::Sub { .. } => OperationCode::Subtract,
+            Self::Mul { .. } => OperationCode::Multiply,
+            Self::Div { .. } => OperationCode::Divide,
+            Self::Mod { .. } => OperationCode::Modulo,
+            Self::Pow { .. } => OperationCode::Power,
+            Self::Minus { .. } => OperationCode::Minus,
+            Self::Not { .. } => OperationCode::Not,
+            Self::Length { .. } => OperationCode::Length,
+            Self::Concatenate { .. } => OperationCode::Concatenate,
+            Self::Jump(_) => OperationCode::Jump,
+            Self::Equal { .. } => OperationCode::Equal,
+            Self::LessThan { .. } => OperationCode::LessThan,
+            Self::LessThanOrEqual { .. } => OperationCode::LessThanOrEqual,
+            Self::Test { .. } => OperationCode::Test,
+            Self::TestSet { .. } => OperationCode::TestSet,
+            Self::Call { .. } => OperationCode::Call,
+            Self::TailCall { .. } => OperationCode::TailCall,
+            Self::Return(..) => OperationCode::Return,
+            Self::IterateNumericForLoop { .. } => OperationCode::IterateNumericForLoop,
+            Self::InitNumericForLoop { .. } => OperationCode::InitNumericForLoop,
+            Self::IterateGenericForLoop { .. } => OperationCode::IterateGenericForLoop,
+            Self::SetList { .. } => OperationCode::SetList,
+            Self::Close(_) => OperationCode::Close,
+            Self::Closure { .. } => OperationCode::Closure,
+            Self::VarArg(..) => OperationCode::VarArg,
+        }
+    }
+
+    pub fn parse<'a>(input: &'a [u8], encoding: &Encoding) -> IResult<&'a [u8], Self> {
+        let (input, instruction) = RawInstruction::parse(input, encoding)?;
         let instruction = match instruction {
             RawInstruction(OperationCode::Move, Layout::BC { a, b, .. }) => Self::Move {
                 destination: Register(a),
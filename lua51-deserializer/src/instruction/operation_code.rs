@@ -1,13 +1,8 @@
 use crate::instruction::layout::LayoutDiscriminants;
-use nom::{
-    error::{Error, ErrorKind, ParseError},
-    number::complete::le_u8,
-    Err, IResult,
-};
 use num_derive::{FromPrimitive, ToPrimitive};
 use num_traits::FromPrimitive;
 
-#[derive(Debug, FromPrimitive, ToPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
 pub enum OperationCode {
     Move = 0,
     LoadConstant,
@@ -50,22 +45,54 @@ pub enum OperationCode {
 }
 
 impl OperationCode {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Self> {
-        let (input, operation_code) = le_u8(input)?;
-        let operation_code = operation_code & 0x3F;
+    // the names used by luac
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Move => "MOVE",
+            Self::LoadConstant => "LOADK",
+            Self::LoadBoolean => "LOADBOOL",
+            Self::LoadNil => "LOADNIL",
+            Self::GetUpvalue => "GETUPVAL",
+            Self::GetGlobal => "GETGLOBAL",
+            Self::GetIndex => "GETTABLE",
+            Self::SetGlobal => "SETGLOBAL",
+            Self::SetUpvalue => "SETUPVAL",
+            Self::SetIndex => "SETTABLE",
+            Self::NewTable => "NEWTABLE",
+            Self::PrepMethodCall => "SELF",
+            Self::Add => "ADD",
+            Self::Subtract => "SUB",
+            Self::Multiply => "MUL",
+            Self::Divide => "DIV",
+            Self::Modulo => "MOD",
+            Self::Power => "POW",
+            Self::Minus => "UNM",
+            Self::Not => "NOT",
+            Self::Length => "LEN",
+            Self::Concatenate => "CONCAT",
+            Self::Jump => "JMP",
+            Self::Equal => "EQ",
+            Self::LessThan => "LT",
+            Self::LessThanOrEqual => "LE",
+            Self::Test => "TEST",
+            Self::TestSet => "TESTSET",
+            Self::Call => "CALL",
+            Self::TailCall => "TAILCALL",
+            Self::Return => "RETURN",
+            Self::IterateNumericForLoop => "FORLOOP",
+            Self::InitNumericForLoop => "FORPREP",
+            Self::IterateGenericForLoop => "TFORLOOP",
+            Self::SetList => "SETLIST",
+            Self::Close => "CLOSE",
+            Self::Closure => "CLOSURE",
+            Self::VarArg => "VARARG",
+        }
+    }
 
-        Ok((
-            input,
-            match FromPrimitive::from_u8(operation_code) {
-                None => {
-                    return Err(Err::Failure(Error::from_error_kind(
-                        input,
-                        ErrorKind::Switch,
-                    )))
-                }
-                Some(operation_code) => operation_code,
-            },
-        ))
+    pub fn from_name(name: &str) -> Option<Self> {
+        (0..=u8::MAX)
+            .map_while(FromPrimitive::from_u8)
+            .find(|o: &Self| o.name().eq_ignore_ascii_case(name))
     }
 
     pub fn instruction_layout(&self) -> LayoutDiscriminants {
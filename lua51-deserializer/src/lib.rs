@@ -1,8 +1,9 @@
 pub use function::Function;
-pub use instruction::{argument, Instruction};
+pub use instruction::{argument, encoding::Encoding, Instruction};
 pub use value::Value;
 
 pub mod chunk;
+pub mod disassembler;
 pub mod function;
 pub mod instruction;
 pub mod local;
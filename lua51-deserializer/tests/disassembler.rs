@@ -0,0 +1,49 @@
+use lua51_deserializer::chunk::Chunk;
+
+// the bytecode of
+//     local t = {}
+//     t["a\t\"\\\1"] = 1
+//     if t[1] == 2 then
+//         t = function() return t end
+//     end
+const CHUNK: &[u8] = include_bytes!("listing.luac");
+
+// RK operands are negative for constants, strings are escaped like luac does
+// and closures are named by their path from main
+const LISTING: &[&str] = &[
+    "main <listing.lua:0,0> (9 instructions, 36 bytes)",
+    "0+ params, 2 slots, 0 upvalues, 1 local, 3 constants, 1 function",
+    "\t1\t[1]\tNEWTABLE \t0 0 0",
+    "\t2\t[2]\tSETTABLE \t0 -1 -2\t; \"a\\t\\\"\\\\\\001\" 1",
+    "\t3\t[3]\tGETTABLE \t1 0 -2\t; 1",
+    "\t4\t[3]\tEQ       \t0 1 -3\t; - 2",
+    "\t5\t[3]\tJMP      \t3\t; to 9",
+    "\t6\t[4]\tCLOSURE  \t1 0\t; main.0",
+    "\t7\t[4]\tMOVE     \t0 0",
+    "\t8\t[4]\tMOVE     \t0 1",
+    "\t9\t[5]\tRETURN   \t0 1",
+    "constants (3) for main:",
+    "\t1\t\"a\\t\\\"\\\\\\001\"",
+    "\t2\t1",
+    "\t3\t2",
+    "locals (1) for main:",
+    "\t0\tt\t2\t10",
+    "upvalues (0) for main:",
+    "",
+    "function <listing.lua:4,4> (3 instructions, 12 bytes)",
+    "0 params, 2 slots, 1 upvalue, 0 locals, 0 constants, 0 functions",
+    "\t1\t[4]\tGETUPVAL \t0 0\t; t",
+    "\t2\t[4]\tRETURN   \t0 2",
+    "\t3\t[4]\tRETURN   \t0 1",
+    "constants (0) for main.0:",
+    "locals (0) for main.0:",
+    "upvalues (1) for main.0:",
+    "\t0\tt",
+];
+
+#[test]
+fn listing() {
+    let (_, chunk) = Chunk::parse(CHUNK).unwrap();
+    // every function starts with a blank line
+    assert_eq!(chunk.to_string(), format!("\n{}\n", LISTING.join("\n")));
+}
@@ -0,0 +1,117 @@
+use either::Either;
+use lua51_deserializer::{
+    argument::{Constant, Register, RegisterOrConstant},
+    instruction::encoding::Field,
+    Encoding, Instruction,
+};
+
+// operation code in the highest bits, b and c swapped
+const SHUFFLED: &str = "
+# a comment
+op 26 6
+a 18 8
+b 0 9
+c 9 9   # trailing comment
+bx 0 18
+
+MOVE 0
+LOADK 1
+JMP 2
+ADD 3
+";
+
+fn encode(fields: &[(Field, u32)]) -> [u8; 4] {
+    let mut instruction = 0;
+    for &(field, value) in fields {
+        assert!(value < 1 << field.size);
+        instruction |= value << field.shift;
+    }
+    instruction.to_le_bytes()
+}
+
+#[test]
+fn parse() {
+    let encoding = Encoding::parse(SHUFFLED).unwrap();
+    assert_eq!(encoding.operation_code, Field::new(26, 6));
+    assert_eq!(encoding.a, Field::new(18, 8));
+    assert_eq!(encoding.b, Field::new(0, 9));
+    assert_eq!(encoding.c, Field::new(9, 9));
+    assert_eq!(encoding.b_x, Field::new(0, 18));
+
+    // unspecified fields keep their standard position
+    let encoding = Encoding::parse("a 0 8").unwrap();
+    assert_eq!(encoding.a, Field::new(0, 8));
+    assert_eq!(encoding.b, Encoding::default().b);
+}
+
+#[test]
+fn parse_errors() {
+    for source in [
+        "b 0 16",
+        "c 0 8",
+        "a 0 9",
+        "op 30 6",
+        "d 0 4",
+        "op 0 0",
+        "op 0",
+        "MOVE 1\nLOADK 1",
+        "NOTANOP 1",
+        "MOVE 256",
+    ] {
+        assert!(Encoding::parse(source).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn decode_shuffled() {
+    let encoding = Encoding::parse(SHUFFLED).unwrap();
+    let op = encoding.operation_code;
+
+    // ADD 1, 2, K(5)
+    let bytes = encode(&[
+        (op, 3),
+        (encoding.a, 1),
+        (encoding.b, 2),
+        (encoding.c, 256 + 5),
+    ]);
+    let (_, instruction) = Instruction::parse(&bytes, &encoding).unwrap();
+    assert!(matches!(
+        instruction,
+        Instruction::Add {
+            destination: Register(1),
+            lhs: RegisterOrConstant(Either::Left(Register(2))),
+            rhs: RegisterOrConstant(Either::Right(Constant(5))),
+        }
+    ));
+
+    // MOVE 4, 255
+    let bytes = encode(&[(op, 0), (encoding.a, 4), (encoding.b, 255)]);
+    let (_, instruction) = Instruction::parse(&bytes, &encoding).unwrap();
+    assert!(matches!(
+        instruction,
+        Instruction::Move {
+            destination: Register(4),
+            source: Register(255),
+        }
+    ));
+
+    // LOADK 0, K(70000)
+    let bytes = encode(&[(op, 1), (encoding.b_x, 70000)]);
+    let (_, instruction) = Instruction::parse(&bytes, &encoding).unwrap();
+    assert!(matches!(
+        instruction,
+        Instruction::LoadConstant {
+            destination: Register(0),
+            source: Constant(70000),
+        }
+    ));
+
+    // JMP -3, the offset is biased by the largest signed value of bx's width
+    let bytes = encode(&[(op, 2), (encoding.b_x, (1 << 17) - 1 - 3)]);
+    let (_, instruction) = Instruction::parse(&bytes, &encoding).unwrap();
+    assert!(matches!(instruction, Instruction::Jump(-3)));
+
+    // raw operation codes that aren't listed are rejected
+    let bytes = encode(&[(op, 4)]);
+    assert!(Instruction::parse(&bytes, &encoding).is_err());
+}
@@ -0,0 +1,71 @@
+#![feature(box_patterns)]
+#![feature(let_chains)]
+
+use driver::{DecompileOptions, DecompileResult, Dialect, Frontend, LiftedFunction, OpcodeMap};
+use lifter::Lifter;
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+use lua51_deserializer::{chunk::Chunk, Encoding};
+
+mod lifter;
+
+fn parse_chunk<'a>(bytecode: &'a [u8], encoding: &Encoding) -> Result<Chunk<'a>, String> {
+    Chunk::parse_with_encoding(bytecode, encoding)
+        .map(|(_, chunk)| chunk)
+        .map_err(|e| e.to_string())
+}
+
+pub fn disassemble_bytecode(bytecode: &[u8], encoding: &Encoding) -> Result<String, String> {
+    parse_chunk(bytecode, encoding).map(|chunk| chunk.to_string())
+}
+
+pub struct Lua51Frontend<'a> {
+    bytecode: &'a [u8],
+    opcode_map: &'a OpcodeMap,
+}
+
+impl<'a> Lua51Frontend<'a> {
+    pub fn new(bytecode: &'a [u8], options: &'a DecompileOptions) -> Self {
+        Self {
+            bytecode,
+            opcode_map: &options.opcode_map,
+        }
+    }
+}
+
+impl Frontend for Lua51Frontend<'_> {
+    fn lift(&self) -> Result<Vec<LiftedFunction>, String> {
+        let encoding = match self.opcode_map {
+            OpcodeMap::Key(1) => Encoding::default(),
+            OpcodeMap::Key(_) => {
+                return Err("lua 5.1 operation codes can only be mapped with an encoding".into());
+            }
+            OpcodeMap::Encoding(source) => Encoding::parse(source)?,
+        };
+        let chunk = parse_chunk(self.bytecode, &encoding)?;
+        let mut lifted = Vec::new();
+        let (function, upvalues) = Lifter::lift(&chunk.function, &mut lifted);
+        lifted.push((Arc::<Mutex<_>>::default(), function, upvalues));
+        lifted.reverse();
+        Ok(lifted
+            .into_iter()
+            .map(|(ast_function, function, upvalues)| LiftedFunction {
+                ast_function,
+                function,
+                upvalues,
+            })
+            .collect())
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::Lua51
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    options: &DecompileOptions,
+) -> Result<DecompileResult, String> {
+    driver::decompile(&Lua51Frontend::new(bytecode, options), options)
+}
@@ -40,7 +40,7 @@ impl<'a, 'b> Lifter<'a, 'b> {
         self.locals
             .reserve(self.bytecode.maximum_stack_size as usize);
         for i in 0..self.bytecode.maximum_stack_size {
-            let local = RcLocal::default();
+            let local = RcLocal::new(ast::Local::register(i as usize));
             if i < self.bytecode.number_of_parameters {
                 self.function.parameters.push(local.clone());
             }
@@ -892,6 +892,11 @@ impl<'a, 'b> Lifter<'a, 'b> {
         context.create_block_map();
         context.allocate_locals();
         context.lift_blocks();
+        context.function.block_pcs = context
+            .nodes
+            .iter()
+            .map(|(&pc, &node)| (node, pc))
+            .collect();
 
         // TODO: STYLE: instead of naming NodeIndex vars `{}_node`, we should name them
         // `{}_index`, or if it's the corresponding var for `block`, `block_index`
@@ -0,0 +1,198 @@
+use std::thread;
+
+use driver::DecompileOptions;
+
+// just enough of the lua 5.1 format to build a chunk with many functions
+struct Function {
+    code: Vec<u32>,
+    constants: Vec<Constant>,
+    functions: Vec<Function>,
+    parameters: u8,
+    stack_size: u8,
+}
+
+enum Constant {
+    Number(f64),
+    String(String),
+}
+
+fn abc(op: u32, a: u32, b: u32, c: u32) -> u32 {
+    op | a << 6 | c << 14 | b << 23
+}
+
+fn abx(op: u32, a: u32, bx: u32) -> u32 {
+    op | a << 6 | bx << 14
+}
+
+fn asbx(op: u32, a: u32, sbx: i32) -> u32 {
+    abx(op, a, (sbx + 131071) as u32)
+}
+
+const K: u32 = 256;
+
+fn write_string(out: &mut Vec<u8>, string: &str) {
+    out.extend((string.len() as u32 + 1).to_le_bytes());
+    out.extend(string.as_bytes());
+    out.push(0);
+}
+
+fn write_function(out: &mut Vec<u8>, function: &Function) {
+    write_string(out, "=test");
+    out.extend(0u32.to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend([0, function.parameters, 0, function.stack_size]);
+    out.extend((function.code.len() as u32).to_le_bytes());
+    for instruction in &function.code {
+        out.extend(instruction.to_le_bytes());
+    }
+    out.extend((function.constants.len() as u32).to_le_bytes());
+    for constant in &function.constants {
+        match constant {
+            Constant::Number(number) => {
+                out.push(3);
+                out.extend(number.to_le_bytes());
+            }
+            Constant::String(string) => {
+                out.push(4);
+                write_string(out, string);
+            }
+        }
+    }
+    out.extend((function.functions.len() as u32).to_le_bytes());
+    for child in &function.functions {
+        write_function(out, child);
+    }
+    // no line info, locals or upvalue names
+    out.extend([0; 12]);
+}
+
+const VARIABLES: u32 = 16;
+
+// function(n)
+//     local v1, v2, ..., v16 = 1, 2, ..., 16
+//     for i = 1, n do
+//         if i % 2 == 0 then
+//             v1, v2, ..., v16 = v2, v3, ..., v1
+//         elseif i % 3 == 0 then
+//             v1, v3 = v3, v1
+//         end
+//     end
+//     print(v1, v2, ..., v16, index)
+// end
+// the loop has a block parameter for every variable, hash maps with that many locals
+// iterate in an order that depends on their addresses
+fn child(index: usize) -> Function {
+    const FOR: u32 = VARIABLES + 1;
+    const TEMPORARY: u32 = FOR + 4;
+    const ONE: u32 = 0;
+    const TWO: u32 = 1;
+    const THREE: u32 = 2;
+    const ZERO: u32 = VARIABLES;
+    let mov = |a, b| abc(0, a, b, 0);
+
+    let mut code = (0..VARIABLES)
+        .map(|variable| abx(1, 1 + variable, variable))
+        .collect::<Vec<_>>();
+    code.extend([abx(1, FOR, ONE), mov(FOR + 1, 0), abx(1, FOR + 2, ONE)]);
+    let for_prep = code.len();
+    code.push(0);
+    let body = code.len();
+    code.extend([
+        abc(16, TEMPORARY, FOR + 3, K + TWO),
+        abc(23, 0, TEMPORARY, K + ZERO),
+    ]);
+    let jump_else = code.len();
+    code.push(0);
+    code.extend((0..VARIABLES).map(|v| mov(TEMPORARY + v, 1 + (v + 1) % VARIABLES)));
+    code.extend((0..VARIABLES).map(|v| mov(1 + v, TEMPORARY + v)));
+    let jump_end = code.len();
+    code.push(0);
+    let r#else = code.len();
+    code.extend([
+        abc(16, TEMPORARY, FOR + 3, K + THREE),
+        abc(23, 0, TEMPORARY, K + ZERO),
+    ]);
+    let jump_skip = code.len();
+    code.push(0);
+    code.extend([mov(TEMPORARY, 3), mov(3, 1), mov(1, TEMPORARY)]);
+    let end = code.len();
+    let offset = |from: usize, to: usize| to as i32 - from as i32 - 1;
+    code.push(asbx(31, FOR, offset(end, body)));
+    code[for_prep] = asbx(32, FOR, offset(for_prep, end));
+    code[jump_else] = asbx(22, 0, offset(jump_else, r#else));
+    code[jump_end] = asbx(22, 0, offset(jump_end, end));
+    code[jump_skip] = asbx(22, 0, offset(jump_skip, end));
+    code.push(abx(5, FOR, VARIABLES + 1));
+    code.extend((0..VARIABLES).map(|v| mov(FOR + 1 + v, 1 + v)));
+    code.extend([
+        abx(1, FOR + 1 + VARIABLES, VARIABLES + 2),
+        abc(28, FOR, VARIABLES + 2, 1),
+        abc(30, 0, 1, 0),
+    ]);
+
+    let mut constants = (1..=VARIABLES)
+        .map(|number| Constant::Number(number as f64))
+        .collect::<Vec<_>>();
+    constants.extend([
+        Constant::Number(0.0),
+        Constant::String("print".to_string()),
+        Constant::Number(index as f64),
+    ]);
+    Function {
+        code,
+        constants,
+        functions: Vec::new(),
+        parameters: 1,
+        stack_size: (VARIABLES * 2 + 5) as u8,
+    }
+}
+
+// a main function that assigns every child to a global
+fn chunk(functions: usize) -> Vec<u8> {
+    let mut code = (0..functions as u32)
+        .flat_map(|index| [abx(36, 0, index), abx(7, 0, index)])
+        .collect::<Vec<_>>();
+    code.push(abc(30, 0, 1, 0));
+    let main = Function {
+        code,
+        constants: (0..functions)
+            .map(|index| Constant::String(format!("f{}", index)))
+            .collect(),
+        functions: (0..functions).map(child).collect(),
+        parameters: 0,
+        stack_size: 2,
+    };
+    let mut bytecode = b"\x1bLua\x51\x00\x01\x04\x04\x04\x08\x00".to_vec();
+    write_function(&mut bytecode, &main);
+    bytecode
+}
+
+fn decompile(bytecode: &[u8], threads: usize) -> String {
+    lua51_lifter::decompile_bytecode(bytecode, &DecompileOptions::default().threads(threads))
+        .unwrap()
+        .source
+}
+
+#[test]
+fn identical_across_runs_and_threads() {
+    let bytecode = chunk(32);
+    let expected = decompile(&bytecode, 1);
+    assert!(expected.contains("function f31("));
+    for run in 0..8 {
+        // so that the locals of every run are allocated at different addresses
+        let _allocations = (0..run * 16)
+            .map(|size| vec![0u8; size])
+            .collect::<Vec<_>>();
+        assert_eq!(decompile(&bytecode, 1), expected);
+    }
+    let bytecode = &bytecode;
+    thread::scope(|scope| {
+        let handles = [0, 2, 4, 8]
+            .into_iter()
+            .map(|threads| scope.spawn(move || decompile(bytecode, threads)))
+            .collect::<Vec<_>>();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), expected);
+        }
+    });
+}
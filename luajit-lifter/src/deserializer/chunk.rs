@@ -0,0 +1,81 @@
+use nom::{
+    bytes::complete::{tag, take},
+    error::{Error, ErrorKind, ParseError},
+    number::complete::le_u8,
+    Err, IResult,
+};
+use nom_leb128::{leb128_u32, leb128_usize};
+
+use super::{function::Function, parse_string};
+
+pub const FLAG_BIG_ENDIAN: u32 = 0x01;
+pub const FLAG_STRIP: u32 = 0x02;
+pub const FLAG_FR2: u32 = 0x08;
+
+#[derive(Debug)]
+pub struct Chunk {
+    pub version: u8,
+    pub flags: u32,
+    pub name: Option<Vec<u8>>,
+    // in dump order, children always come before their parent
+    pub functions: Vec<Function>,
+    pub main: usize,
+}
+
+impl Chunk {
+    // 2.1 with LJ_GC64 uses an extra frame slot for calls
+    pub fn is_fr2(&self) -> bool {
+        self.flags & FLAG_FR2 != 0
+    }
+
+    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, _) = tag("\x1bLJ")(input)?;
+        let (input, version) = le_u8(input)?;
+        if !(1..=2).contains(&version) {
+            return Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Switch,
+            )));
+        }
+        let (input, flags) = leb128_u32(input)?;
+        let (mut input, name) = if flags & FLAG_STRIP == 0 {
+            let (input, name) = parse_string(input)?;
+            (input, Some(name))
+        } else {
+            (input, None)
+        };
+
+        let mut functions = Vec::new();
+        let mut stack = Vec::new();
+        // the terminating zero is optional
+        while !input.is_empty() {
+            let length;
+            (input, length) = leb128_usize(input)?;
+            if length == 0 {
+                break;
+            }
+            let function_input;
+            (input, function_input) = take(length)(input)?;
+            let (_, function) = Function::parse(function_input, version, flags, &mut stack)?;
+            stack.push(functions.len());
+            functions.push(function);
+        }
+
+        match stack[..] {
+            [main] => Ok((
+                input,
+                Self {
+                    version,
+                    flags,
+                    name,
+                    functions,
+                    main,
+                },
+            )),
+            _ => Err(Err::Failure(Error::from_error_kind(
+                input,
+                ErrorKind::Verify,
+            ))),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use nom::{
+    error::{Error, ErrorKind, ParseError},
+    number::complete::le_u8,
+    Err, IResult,
+};
+use nom_leb128::leb128_u32;
+
+use super::parse_string_of_length;
+
+const KGC_CHILD: u32 = 0;
+const KGC_TABLE: u32 = 1;
+const KGC_INT64: u32 = 2;
+const KGC_UINT64: u32 = 3;
+const KGC_COMPLEX: u32 = 4;
+const KGC_STRING: u32 = 5;
+
+const KTAB_NIL: u32 = 0;
+const KTAB_FALSE: u32 = 1;
+const KTAB_TRUE: u32 = 2;
+const KTAB_INT: u32 = 3;
+const KTAB_NUM: u32 = 4;
+const KTAB_STRING: u32 = 5;
+
+fn parse_u64(input: &[u8]) -> IResult<&[u8], u64> {
+    let (input, lo) = leb128_u32(input)?;
+    let (input, hi) = leb128_u32(input)?;
+    Ok((input, (hi as u64) << 32 | lo as u64))
+}
+
+// the lowest bit of the first byte is used as a flag, the rest is a normal uleb128
+fn parse_uleb128_33(input: &[u8]) -> IResult<&[u8], u32> {
+    let (mut input, first) = le_u8(input)?;
+    let mut value = (first >> 1) as u32;
+    if value >= 0x40 {
+        value &= 0x3f;
+        let mut shift = 6;
+        loop {
+            let byte;
+            (input, byte) = le_u8(input)?;
+            value |= ((byte & 0x7f) as u32).wrapping_shl(shift);
+            shift += 7;
+            if byte < 0x80 {
+                break;
+            }
+        }
+    }
+    Ok((input, value))
+}
+
+#[derive(Debug, Clone)]
+pub enum NumberConstant {
+    Integer(i32),
+    Number(f64),
+}
+
+impl NumberConstant {
+    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let is_number = input.first().is_some_and(|b| b & 1 != 0);
+        let (input, lo) = parse_uleb128_33(input)?;
+        if is_number {
+            let (input, hi) = leb128_u32(input)?;
+            Ok((
+                input,
+                NumberConstant::Number(f64::from_bits((hi as u64) << 32 | lo as u64)),
+            ))
+        } else {
+            Ok((input, NumberConstant::Integer(lo as i32)))
+        }
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            NumberConstant::Integer(value) => value as f64,
+            NumberConstant::Number(value) => value,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TableValue {
+    Nil,
+    Boolean(bool),
+    Integer(i32),
+    Number(f64),
+    String(Vec<u8>),
+}
+
+impl TableValue {
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, tag) = leb128_u32(input)?;
+        match tag {
+            KTAB_NIL => Ok((input, TableValue::Nil)),
+            KTAB_FALSE => Ok((input, TableValue::Boolean(false))),
+            KTAB_TRUE => Ok((input, TableValue::Boolean(true))),
+            KTAB_INT => {
+                let (input, value) = leb128_u32(input)?;
+                Ok((input, TableValue::Integer(value as i32)))
+            }
+            KTAB_NUM => {
+                let (input, value) = parse_u64(input)?;
+                Ok((input, TableValue::Number(f64::from_bits(value))))
+            }
+            _ => {
+                let (input, value) = parse_string_of_length(input, (tag - KTAB_STRING) as usize)?;
+                Ok((input, TableValue::String(value)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableConstant {
+    pub array: Vec<TableValue>,
+    pub hash: Vec<(TableValue, TableValue)>,
+}
+
+impl TableConstant {
+    fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+        let (input, array_len) = leb128_u32(input)?;
+        let (mut input, hash_len) = leb128_u32(input)?;
+        let mut array = Vec::with_capacity(array_len as usize);
+        for _ in 0..array_len {
+            let value;
+            (input, value) = TableValue::parse(input)?;
+            array.push(value);
+        }
+        let mut hash = Vec::with_capacity(hash_len as usize);
+        for _ in 0..hash_len {
+            let (key, value);
+            (input, key) = TableValue::parse(input)?;
+            (input, value) = TableValue::parse(input)?;
+            hash.push((key, value));
+        }
+        Ok((input, Self { array, hash }))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    // index into `Chunk::functions`
+    Child(usize),
+    Table(TableConstant),
+    Int64(i64),
+    UInt64(u64),
+    Complex(f64, f64),
+    String(Vec<u8>),
+}
+
+impl Constant {
+    // children are dumped before their parent, the first child constant refers to the
+    // most recently dumped function
+    pub(crate) fn parse<'a>(input: &'a [u8], children: &mut Vec<usize>) -> IResult<&'a [u8], Self> {
+        let (input, tag) = leb128_u32(input)?;
+        match tag {
+            KGC_CHILD => match children.pop() {
+                Some(child) => Ok((input, Constant::Child(child))),
+                None => Err(Err::Failure(Error::from_error_kind(
+                    input,
+                    ErrorKind::Verify,
+                ))),
+            },
+            KGC_TABLE => {
+                let (input, table) = TableConstant::parse(input)?;
+                Ok((input, Constant::Table(table)))
+            }
+            KGC_INT64 => {
+                let (input, value) = parse_u64(input)?;
+                Ok((input, Constant::Int64(value as i64)))
+            }
+            KGC_UINT64 => {
+                let (input, value) = parse_u64(input)?;
+                Ok((input, Constant::UInt64(value)))
+            }
+            KGC_COMPLEX => {
+                let (input, real) = parse_u64(input)?;
+                let (input, imaginary) = parse_u64(input)?;
+                Ok((
+                    input,
+                    Constant::Complex(f64::from_bits(real), f64::from_bits(imaginary)),
+                ))
+            }
+            _ => {
+                let (input, value) = parse_string_of_length(input, (tag - KGC_STRING) as usize)?;
+                Ok((input, Constant::String(value)))
+            }
+        }
+    }
+}
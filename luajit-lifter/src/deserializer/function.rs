@@ -0,0 +1,114 @@
+use nom::{
+    bytes::complete::take,
+    error::{Error, ErrorKind, ParseError},
+    multi::count,
+    number::{
+        complete::{le_u8, u16, u32},
+        Endianness,
+    },
+    Err, IResult,
+};
+use nom_leb128::leb128_usize;
+
+use super::{
+    chunk::{FLAG_BIG_ENDIAN, FLAG_STRIP},
+    constant::{Constant, NumberConstant},
+};
+
+use crate::instruction::Instruction;
+
+const PROTO_VARARG: u8 = 0x02;
+
+pub const UPVALUE_LOCAL: u16 = 0x8000;
+pub const UPVALUE_IMMUTABLE: u16 = 0x4000;
+
+#[derive(Debug)]
+pub struct Function {
+    pub flags: u8,
+    pub num_parameters: u8,
+    pub frame_size: u8,
+    // UPVALUE_LOCAL set: register in the parent, otherwise upvalue of the parent
+    pub upvalues: Vec<u16>,
+    pub instructions: Vec<Instruction>,
+    // indexed the same way as D operands, i.e. reversed from the dump order
+    pub constants: Vec<Constant>,
+    pub numbers: Vec<NumberConstant>,
+    pub line_defined: Option<usize>,
+    pub num_lines: Option<usize>,
+    pub debug_info: Vec<u8>,
+}
+
+impl Function {
+    pub fn is_vararg(&self) -> bool {
+        self.flags & PROTO_VARARG != 0
+    }
+
+    pub(crate) fn parse<'a>(
+        input: &'a [u8],
+        version: u8,
+        flags: u32,
+        children: &mut Vec<usize>,
+    ) -> IResult<&'a [u8], Self> {
+        let endianness = if flags & FLAG_BIG_ENDIAN != 0 {
+            Endianness::Big
+        } else {
+            Endianness::Little
+        };
+
+        let (input, proto_flags) = le_u8(input)?;
+        let (input, num_parameters) = le_u8(input)?;
+        let (input, frame_size) = le_u8(input)?;
+        let (input, num_upvalues) = le_u8(input)?;
+        let (input, num_constants) = leb128_usize(input)?;
+        let (input, num_numbers) = leb128_usize(input)?;
+        let (input, num_instructions) = leb128_usize(input)?;
+        let (input, debug_size, line_defined, num_lines) = if flags & FLAG_STRIP == 0 {
+            let (input, debug_size) = leb128_usize(input)?;
+            if debug_size != 0 {
+                let (input, line_defined) = leb128_usize(input)?;
+                let (input, num_lines) = leb128_usize(input)?;
+                (input, debug_size, Some(line_defined), Some(num_lines))
+            } else {
+                (input, 0, None, None)
+            }
+        } else {
+            (input, 0, None, None)
+        };
+
+        let (input, u32_instructions) = count(u32(endianness), num_instructions)(input)?;
+        let instructions = u32_instructions
+            .into_iter()
+            .map(|insn| Instruction::parse(insn, version))
+            .collect::<Result<_, _>>()
+            .map_err(|_| Err::Failure(Error::from_error_kind(input, ErrorKind::Switch)))?;
+        let (input, upvalues) = count(u16(endianness), num_upvalues as usize)(input)?;
+
+        let mut input = input;
+        let mut constants = Vec::with_capacity(num_constants);
+        for _ in 0..num_constants {
+            let constant;
+            (input, constant) = Constant::parse(input, children)?;
+            constants.push(constant);
+        }
+        constants.reverse();
+
+        let (input, numbers) = count(NumberConstant::parse, num_numbers)(input)?;
+        let (input, debug_info) = take(debug_size)(input)?;
+
+        Ok((
+            input,
+            Self {
+                flags: proto_flags,
+                num_parameters,
+                frame_size,
+                upvalues,
+                instructions,
+                constants,
+                numbers,
+                line_defined,
+                num_lines,
+                debug_info: debug_info.to_owned(),
+            },
+        ))
+    }
+}
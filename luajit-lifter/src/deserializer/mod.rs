@@ -0,0 +1,26 @@
+use nom::{bytes::complete::take, IResult};
+use nom_leb128::leb128_usize;
+
+pub mod chunk;
+pub mod constant;
+pub mod function;
+
+fn parse_string_of_length(input: &[u8], length: usize) -> IResult<&[u8], Vec<u8>> {
+    let (input, bytes) = take(length)(input)?;
+    Ok((input, bytes.to_owned()))
+}
+
+fn parse_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+    let (input, length) = leb128_usize(input)?;
+    parse_string_of_length(input, length)
+}
+
+pub fn deserialize(bytecode: &[u8]) -> Result<chunk::Chunk, String> {
+    match chunk::Chunk::parse(bytecode) {
+        Ok((_, chunk)) => Ok(chunk),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,454 @@
+use super::{
+    chunk::{FLAG_BIG_ENDIAN, FLAG_FR2, FLAG_STRIP},
+    constant::{Constant, NumberConstant, TableValue},
+    deserialize,
+};
+use crate::{instruction::Instruction, op_code::OpCode};
+
+fn uleb128(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+// the lowest bit of the first byte is `flag`
+fn uleb128_33(value: u32, flag: bool, out: &mut Vec<u8>) {
+    let first = ((value & 0x3f) << 1) as u8 | flag as u8;
+    if value >= 0x40 {
+        out.push(first | 0x80);
+        uleb128((value >> 6) as u64, out);
+    } else {
+        out.push(first);
+    }
+}
+
+fn ad(op_code: u8, a: u8, d: u16) -> u32 {
+    op_code as u32 | (a as u32) << 8 | (d as u32) << 16
+}
+
+fn abc(op_code: u8, a: u8, b: u8, c: u8) -> u32 {
+    op_code as u32 | (a as u32) << 8 | (c as u32) << 16 | (b as u32) << 24
+}
+
+#[derive(Default)]
+struct Proto {
+    num_parameters: u8,
+    instructions: Vec<u32>,
+    upvalues: Vec<u16>,
+    // already encoded, in dump order
+    constants: Vec<Vec<u8>>,
+    numbers: Vec<Vec<u8>>,
+    // (line defined, number of lines, debug info)
+    debug: Option<(u64, u64, Vec<u8>)>,
+}
+
+impl Proto {
+    fn assemble(&self, flags: u32) -> Vec<u8> {
+        let big_endian = flags & FLAG_BIG_ENDIAN != 0;
+        let mut out = vec![0, self.num_parameters, 8, self.upvalues.len() as u8];
+        uleb128(self.constants.len() as u64, &mut out);
+        uleb128(self.numbers.len() as u64, &mut out);
+        uleb128(self.instructions.len() as u64, &mut out);
+        if flags & FLAG_STRIP == 0 {
+            match &self.debug {
+                Some((line_defined, num_lines, debug_info)) => {
+                    uleb128(debug_info.len() as u64, &mut out);
+                    uleb128(*line_defined, &mut out);
+                    uleb128(*num_lines, &mut out);
+                }
+                None => uleb128(0, &mut out),
+            }
+        }
+        for &instruction in &self.instructions {
+            if big_endian {
+                out.extend(instruction.to_be_bytes());
+            } else {
+                out.extend(instruction.to_le_bytes());
+            }
+        }
+        for &upvalue in &self.upvalues {
+            if big_endian {
+                out.extend(upvalue.to_be_bytes());
+            } else {
+                out.extend(upvalue.to_le_bytes());
+            }
+        }
+        for constant in &self.constants {
+            out.extend(constant);
+        }
+        for number in &self.numbers {
+            out.extend(number);
+        }
+        if let Some((_, _, debug_info)) = self.debug.as_ref().filter(|_| flags & FLAG_STRIP == 0) {
+            out.extend(debug_info);
+        }
+        out
+    }
+}
+
+fn assemble(version: u8, flags: u32, name: &[u8], protos: &[Proto]) -> Vec<u8> {
+    let mut out = b"\x1bLJ".to_vec();
+    out.push(version);
+    uleb128(flags as u64, &mut out);
+    if flags & FLAG_STRIP == 0 {
+        uleb128(name.len() as u64, &mut out);
+        out.extend(name);
+    }
+    for proto in protos {
+        let proto = proto.assemble(flags);
+        uleb128(proto.len() as u64, &mut out);
+        out.extend(proto);
+    }
+    out.push(0);
+    out
+}
+
+fn string_constant(string: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    uleb128(5 + string.len() as u64, &mut out);
+    out.extend(string);
+    out
+}
+
+fn u64_constant(tag: u64, values: &[u64]) -> Vec<u8> {
+    let mut out = Vec::new();
+    uleb128(tag, &mut out);
+    for &value in values {
+        uleb128(value & 0xffff_ffff, &mut out);
+        uleb128(value >> 32, &mut out);
+    }
+    out
+}
+
+fn integer(value: i32) -> Vec<u8> {
+    let mut out = Vec::new();
+    uleb128_33(value as u32, false, &mut out);
+    out
+}
+
+fn number(value: f64) -> Vec<u8> {
+    let bits = value.to_bits();
+    let mut out = Vec::new();
+    uleb128_33(bits as u32, true, &mut out);
+    uleb128(bits >> 32, &mut out);
+    out
+}
+
+#[test]
+fn header_2_0() {
+    // KSHORT and RET0 are 39 and 71 in 2.0, 2.1 inserted ISTYPE, ISNUM, TGETR and TSETR
+    let bytecode = assemble(
+        1,
+        0,
+        b"=test",
+        &[Proto {
+            instructions: vec![ad(39, 0, 5), abc(30, 1, 0, 0), ad(71, 0, 1)],
+            debug: Some((3, 4, vec![1, 2, 3])),
+            ..Default::default()
+        }],
+    );
+    let chunk = deserialize(&bytecode).unwrap();
+    assert_eq!(chunk.version, 1);
+    assert!(!chunk.is_fr2());
+    assert_eq!(chunk.name.as_deref(), Some(&b"=test"[..]));
+    assert_eq!(chunk.main, 0);
+    let function = &chunk.functions[0];
+    assert_eq!(function.line_defined, Some(3));
+    assert_eq!(function.num_lines, Some(4));
+    assert_eq!(function.debug_info, [1, 2, 3]);
+    assert!(matches!(
+        function.instructions[..],
+        [
+            Instruction::AD {
+                op_code: OpCode::BC_KSHORT,
+                a: 0,
+                d: 5
+            },
+            Instruction::ABC {
+                op_code: OpCode::BC_ADDVV,
+                a: 1,
+                b: 0,
+                c: 0
+            },
+            Instruction::AD {
+                op_code: OpCode::BC_RET0,
+                a: 0,
+                d: 1
+            }
+        ]
+    ));
+}
+
+#[test]
+fn header_2_1() {
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP | FLAG_FR2,
+        b"",
+        &[Proto {
+            num_parameters: 2,
+            instructions: vec![abc(32, 2, 0, 1), ad(76, 2, 2)],
+            upvalues: vec![0xc001],
+            ..Default::default()
+        }],
+    );
+    let chunk = deserialize(&bytecode).unwrap();
+    assert_eq!(chunk.version, 2);
+    assert!(chunk.is_fr2());
+    assert_eq!(chunk.name, None);
+    let function = &chunk.functions[0];
+    assert_eq!(function.num_parameters, 2);
+    assert_eq!(function.upvalues, [0xc001]);
+    assert_eq!(function.line_defined, None);
+    assert!(matches!(
+        function.instructions[..],
+        [
+            Instruction::ABC {
+                op_code: OpCode::BC_ADDVV,
+                a: 2,
+                b: 0,
+                c: 1
+            },
+            Instruction::AD {
+                op_code: OpCode::BC_RET1,
+                a: 2,
+                d: 2
+            }
+        ]
+    ));
+}
+
+#[test]
+fn big_endian() {
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP | FLAG_BIG_ENDIAN,
+        b"",
+        &[Proto {
+            instructions: vec![ad(41, 3, 0x1234), ad(75, 0, 1)],
+            upvalues: vec![0x8002],
+            ..Default::default()
+        }],
+    );
+    let function = &deserialize(&bytecode).unwrap().functions[0];
+    assert_eq!(function.upvalues, [0x8002]);
+    assert!(matches!(
+        function.instructions[0],
+        Instruction::AD {
+            op_code: OpCode::BC_KSHORT,
+            a: 3,
+            d: 0x1234
+        }
+    ));
+}
+
+#[test]
+fn constants() {
+    let mut table = Vec::new();
+    // 5 array entries, 1 hash entry
+    uleb128(5, &mut table);
+    uleb128(1, &mut table);
+    table.extend([0, 2, 3, 7, 4]);
+    uleb128(0.5f64.to_bits() & 0xffff_ffff, &mut table);
+    uleb128(0.5f64.to_bits() >> 32, &mut table);
+    table.extend([5 + 1, b's', 5 + 1, b'k', 1]);
+
+    let child = Proto {
+        instructions: vec![ad(75, 0, 1)],
+        ..Default::default()
+    };
+    let main = Proto {
+        instructions: vec![ad(75, 0, 1)],
+        constants: vec![
+            string_constant(b"abc"),
+            u64_constant(2, &[(-2i64) as u64]),
+            u64_constant(3, &[u64::MAX]),
+            u64_constant(4, &[0f64.to_bits(), 2f64.to_bits()]),
+            [vec![1], table].concat(),
+            vec![0],
+        ],
+        numbers: vec![integer(5), integer(-1), integer(1000), number(0.5)],
+        ..Default::default()
+    };
+    let chunk = deserialize(&assemble(2, FLAG_STRIP, b"", &[child, main])).unwrap();
+    assert_eq!(chunk.main, 1);
+    let function = &chunk.functions[1];
+
+    // D operands index constants from the end
+    let constants = &function.constants;
+    assert!(matches!(constants[0], Constant::Child(0)));
+    let Constant::Table(table) = &constants[1] else {
+        panic!("{:?}", constants[1]);
+    };
+    assert!(matches!(
+        table.array[..],
+        [
+            TableValue::Nil,
+            TableValue::Boolean(true),
+            TableValue::Integer(7),
+            TableValue::Number(n),
+            TableValue::String(_),
+        ] if n == 0.5
+    ));
+    assert!(matches!(
+        &table.hash[..],
+        [(TableValue::String(key), TableValue::Boolean(false))] if key == b"k"
+    ));
+    assert!(matches!(constants[2], Constant::Complex(r, i) if r == 0.0 && i == 2.0));
+    assert!(matches!(constants[3], Constant::UInt64(u64::MAX)));
+    assert!(matches!(constants[4], Constant::Int64(-2)));
+    assert!(matches!(&constants[5], Constant::String(string) if string == b"abc"));
+
+    assert!(matches!(
+        function.numbers[..],
+        [
+            NumberConstant::Integer(5),
+            NumberConstant::Integer(-1),
+            NumberConstant::Integer(1000),
+            NumberConstant::Number(n),
+        ] if n == 0.5
+    ));
+}
+
+#[test]
+fn invalid_op_code() {
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP,
+        b"",
+        &[Proto {
+            instructions: vec![ad(200, 0, 0)],
+            ..Default::default()
+        }],
+    );
+    assert!(deserialize(&bytecode).is_err());
+}
+
+#[test]
+fn invalid_version() {
+    let bytecode = assemble(3, FLAG_STRIP, b"", &[Proto::default()]);
+    assert!(deserialize(&bytecode).is_err());
+}
+
+#[test]
+fn imaginary_constant() {
+    // KCDATA 0 0, RET1 0 2
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP,
+        b"",
+        &[Proto {
+            instructions: vec![ad(40, 0, 0), ad(76, 0, 2)],
+            constants: vec![u64_constant(4, &[0f64.to_bits(), 2f64.to_bits()])],
+            ..Default::default()
+        }],
+    );
+    let result = crate::decompile_bytecode(&bytecode, &Default::default()).unwrap();
+    assert_eq!(result.source.trim(), "return 2i");
+}
+
+#[test]
+fn type_check() {
+    // ISTYPE 0 0, RET0 0 1
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP,
+        b"",
+        &[Proto {
+            instructions: vec![ad(16, 0, 0), ad(75, 0, 1)],
+            ..Default::default()
+        }],
+    );
+    assert!(crate::decompile_bytecode(&bytecode, &Default::default()).is_err());
+}
+
+#[test]
+fn raw_access() {
+    // GGET 0 "t", KSHORT 1 1, TGETR 2 0 1, TSETR 2 0 1, RET0 0 1
+    let bytecode = assemble(
+        2,
+        FLAG_STRIP,
+        b"",
+        &[Proto {
+            instructions: vec![
+                ad(54, 0, 0),
+                ad(41, 1, 1),
+                abc(59, 2, 0, 1),
+                abc(64, 2, 0, 1),
+                ad(75, 0, 1),
+            ],
+            constants: vec![string_constant(b"t")],
+            ..Default::default()
+        }],
+    );
+    let result = crate::decompile_bytecode(&bytecode, &Default::default()).unwrap();
+    assert_eq!(
+        result.source.trim(),
+        "local v1 = t\nlocal v2 = 1\nlocal v3 = rawget(v1, v2)\nrawset(v1, v2, v3)"
+    );
+}
+
+#[test]
+fn malformed() {
+    let lift = |instructions: Vec<u32>, constants: Vec<Vec<u8>>| {
+        let bytecode = assemble(
+            2,
+            FLAG_STRIP,
+            b"",
+            &[Proto {
+                instructions,
+                constants,
+                ..Default::default()
+            }],
+        );
+        crate::decompile_bytecode(&bytecode, &Default::default()).unwrap_err()
+    };
+    let ret0 = ad(75, 0, 1);
+    // no instructions
+    assert_eq!(lift(vec![], vec![]), "function has no instructions");
+    // ITERC 1 3 2, the generator would be in register -2
+    assert!(
+        lift(vec![abc(69, 1, 3, 2), ad(82, 1, 0x7fff), ret0], vec![])
+            .starts_with("invalid instruction")
+    );
+    // ITERC 3 1 3, no loop variables
+    assert!(
+        lift(vec![abc(69, 3, 1, 3), ad(82, 3, 0x7fff), ret0], vec![])
+            .starts_with("invalid instruction")
+    );
+    // CAT 0 1 1
+    assert!(lift(vec![abc(38, 0, 1, 1), ret0], vec![]).starts_with("invalid instruction"));
+    // RET 0 0
+    assert!(lift(vec![ad(74, 0, 0)], vec![]).starts_with("invalid instruction"));
+    // FNEW 0 0 of a string
+    assert!(lift(vec![ad(51, 0, 0), ret0], vec![string_constant(b"a")])
+        .starts_with("invalid instruction"));
+    // KSTR 0 0 without constants
+    assert!(lift(vec![ad(39, 0, 0), ret0], vec![]).starts_with("constant 0 isn't a string"));
+    // KNUM 0 0 without numbers
+    assert_eq!(
+        lift(vec![ad(42, 0, 0), ret0], vec![]),
+        "number constant 0 doesn't exist"
+    );
+    // KPRI 0 3
+    assert_eq!(
+        lift(vec![ad(43, 0, 3), ret0], vec![]),
+        "primitive 3 doesn't exist"
+    );
+    // UGET 0 0 without upvalues
+    assert_eq!(
+        lift(vec![ad(45, 0, 0), ret0], vec![]),
+        "upvalue 0 doesn't exist"
+    );
+    // JMP 0 past the end
+    assert_eq!(
+        lift(vec![ad(88, 0, 0x8005), ret0], vec![]),
+        "jump to 6, which isn't an instruction"
+    );
+}
@@ -0,0 +1,48 @@
+use num_enum::TryFromPrimitiveError;
+
+use crate::op_code::OpCode;
+
+// instructions are 32 bits wide, the opcode and A are always in the low 16 bits
+// ABC: B:8 C:8 A:8 OP:8
+// AD:  D:16    A:8 OP:8
+#[derive(Debug, Clone, Copy)]
+pub enum Instruction {
+    ABC {
+        op_code: OpCode,
+        a: u8,
+        b: u8,
+        c: u8,
+    },
+    AD {
+        op_code: OpCode,
+        a: u8,
+        d: u16,
+    },
+}
+
+impl Instruction {
+    pub fn parse(insn: u32, version: u8) -> Result<Self, TryFromPrimitiveError<OpCode>> {
+        let op_code = OpCode::from_version(insn as u8, version)?;
+        let a = (insn >> 8) as u8;
+        if op_code.is_abc() {
+            Ok(Instruction::ABC {
+                op_code,
+                a,
+                b: (insn >> 24) as u8,
+                c: (insn >> 16) as u8,
+            })
+        } else {
+            Ok(Instruction::AD {
+                op_code,
+                a,
+                d: (insn >> 16) as u16,
+            })
+        }
+    }
+
+    pub fn op_code(&self) -> OpCode {
+        match *self {
+            Instruction::ABC { op_code, .. } | Instruction::AD { op_code, .. } => op_code,
+        }
+    }
+}
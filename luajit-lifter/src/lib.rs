@@ -0,0 +1,59 @@
+mod deserializer;
+mod instruction;
+mod lifter;
+mod op_code;
+
+use driver::{DecompileOptions, DecompileResult, Dialect, Frontend, LiftedFunction, OpcodeMap};
+use lifter::Lifter;
+
+use parking_lot::Mutex;
+use triomphe::Arc;
+
+pub struct LuaJitFrontend<'a> {
+    bytecode: &'a [u8],
+    opcode_map: &'a OpcodeMap,
+}
+
+impl<'a> LuaJitFrontend<'a> {
+    pub fn new(bytecode: &'a [u8], options: &'a DecompileOptions) -> Self {
+        Self {
+            bytecode,
+            opcode_map: &options.opcode_map,
+        }
+    }
+}
+
+// LuaJIT has no namecall, `a:b()` is a self assignment followed by an index,
+// so the default method call structuring applies
+impl Frontend for LuaJitFrontend<'_> {
+    fn lift(&self) -> Result<Vec<LiftedFunction>, String> {
+        if self.opcode_map != &OpcodeMap::default() {
+            return Err("luajit operation codes can't be mapped".to_string());
+        }
+        let chunk = deserializer::deserialize(self.bytecode)?;
+        let mut lifted = Vec::new();
+        let mut stack = vec![(Arc::<Mutex<ast::Function>>::default(), chunk.main)];
+        while let Some((ast_function, func_id)) = stack.pop() {
+            let (function, upvalues, child_functions) =
+                Lifter::lift(&chunk.functions, chunk.is_fr2(), func_id)?;
+            lifted.push(LiftedFunction {
+                ast_function,
+                function,
+                upvalues,
+            });
+            stack.extend(child_functions.into_iter().map(|(a, f)| (a.0, f)));
+        }
+        Ok(lifted)
+    }
+
+    fn dialect(&self) -> Dialect {
+        Dialect::LuaJit
+    }
+}
+
+pub fn decompile_bytecode(
+    bytecode: &[u8],
+    options: &DecompileOptions,
+) -> Result<DecompileResult, String> {
+    driver::decompile(&LuaJitFrontend::new(bytecode, options), options)
+}
@@ -0,0 +1,1102 @@
+use by_address::ByAddress;
+
+use indexmap::IndexMap;
+use itertools::Itertools;
+use parking_lot::Mutex;
+use petgraph::{stable_graph::NodeIndex, visit::EdgeRef, Direction};
+
+use rustc_hash::FxHashMap;
+use triomphe::Arc;
+
+use super::{
+    deserializer::{
+        constant::{Constant as BytecodeConstant, NumberConstant, TableValue},
+        function::{Function as BytecodeFunction, UPVALUE_IMMUTABLE, UPVALUE_LOCAL},
+    },
+    instruction::Instruction,
+    op_code::OpCode,
+};
+use cfg::{
+    block::{BlockEdge, BranchType},
+    function::Function,
+};
+
+// jump offsets are stored in D with a bias
+const JUMP_BIAS: isize = 0x8000;
+
+// the statements of a block and the edges leaving it
+type LiftedBlock = (Vec<ast::Statement>, Vec<(NodeIndex, BlockEdge)>);
+
+pub struct Lifter<'a> {
+    function_list: &'a Vec<BytecodeFunction>,
+    fr2: bool,
+    blocks: FxHashMap<usize, NodeIndex>,
+    insert_between: FxHashMap<NodeIndex, (NodeIndex, ast::Statement)>,
+    function: Function,
+    // in the order the closures appear, so functions are lifted in the same order every run
+    child_functions: IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+    register_map: FxHashMap<usize, ast::RcLocal>,
+    upvalues: Vec<ast::RcLocal>,
+}
+
+impl<'a> Lifter<'a> {
+    pub fn lift(
+        function_list: &'a Vec<BytecodeFunction>,
+        fr2: bool,
+        function_id: usize,
+    ) -> Result<
+        (
+            Function,
+            Vec<ast::RcLocal>,
+            IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+        ),
+        String,
+    > {
+        // type checks aren't lifted, dropping them would hide the errors they raise
+        if let Some(instruction) = function_list[function_id]
+            .instructions
+            .iter()
+            .find(|i| matches!(i.op_code(), OpCode::BC_ISTYPE | OpCode::BC_ISNUM))
+        {
+            return Err(format!(
+                "unsupported instruction {:?}",
+                instruction.op_code()
+            ));
+        }
+
+        let mut context = Self {
+            function_list,
+            fr2,
+            blocks: FxHashMap::default(),
+            insert_between: FxHashMap::default(),
+            function: Function::new(function_id),
+            child_functions: IndexMap::new(),
+            register_map: FxHashMap::default(),
+            upvalues: Vec::new(),
+        };
+
+        context.lift_function()?;
+        Ok((context.function, context.upvalues, context.child_functions))
+    }
+
+    fn bytecode(&self) -> &'a BytecodeFunction {
+        &self.function_list[self.function.id]
+    }
+
+    fn lift_function(&mut self) -> Result<(), String> {
+        if self.bytecode().instructions.is_empty() {
+            return Err("function has no instructions".to_string());
+        }
+        self.discover_blocks();
+
+        let mut blocks = self.blocks.keys().cloned().collect::<Vec<_>>();
+        blocks.sort_unstable();
+
+        let block_ranges = blocks
+            .iter()
+            .cloned()
+            .zip(
+                blocks
+                    .iter()
+                    .skip(1)
+                    .map(|&s| s - 1)
+                    .chain(std::iter::once(self.bytecode().instructions.len() - 1)),
+            )
+            .collect_vec();
+
+        for _ in 0..self.bytecode().upvalues.len() {
+            self.upvalues.push(ast::RcLocal::default());
+        }
+
+        for i in 0..self.bytecode().num_parameters {
+            let parameter = ast::RcLocal::new(ast::Local::register(i as usize));
+            self.function.parameters.push(parameter.clone());
+            self.register_map.insert(i as usize, parameter);
+        }
+
+        self.function.is_variadic = self.bytecode().is_vararg();
+
+        for (start_pc, end_pc) in block_ranges {
+            let node = self.block_to_node(start_pc)?;
+            let (statements, edges) = self.lift_block(start_pc, end_pc)?;
+            let block = self.function.block_mut(node).unwrap();
+            block.0.extend(statements);
+            self.function.set_edges(node, edges);
+        }
+
+        // TODO: same as lua51-lifter, there should be a better way to do this
+        for (node, (successor, stat)) in std::mem::take(&mut self.insert_between) {
+            if self.function.predecessor_blocks(successor).count() == 1 {
+                self.function.block_mut(successor).unwrap().insert(0, stat);
+            } else {
+                let between_node = self.function.new_block();
+                self.function.block_mut(between_node).unwrap().push(stat);
+                self.function.set_edges(
+                    between_node,
+                    vec![(successor, BlockEdge::new(BranchType::Unconditional))],
+                );
+                for edge in self
+                    .function
+                    .graph()
+                    .edges_directed(node, Direction::Outgoing)
+                    .filter(|e| e.target() == successor)
+                    .map(|e| e.id())
+                    .collect::<Vec<_>>()
+                {
+                    let edge = self.function.graph_mut().remove_edge(edge).unwrap();
+                    self.function.graph_mut().add_edge(node, between_node, edge);
+                }
+            }
+        }
+
+        self.function.block_pcs = self.blocks.iter().map(|(&pc, &node)| (node, pc)).collect();
+
+        let entry_node = self.function.new_block();
+        self.function.set_edges(
+            entry_node,
+            vec![(
+                self.block_to_node(0)?,
+                BlockEdge::new(BranchType::Unconditional),
+            )],
+        );
+        self.function.set_entry(entry_node);
+        Ok(())
+    }
+
+    fn add_block(&mut self, insn_index: usize) {
+        if insn_index < self.bytecode().instructions.len() {
+            self.blocks
+                .entry(insn_index)
+                .or_insert_with(|| self.function.new_block());
+        }
+    }
+
+    fn discover_blocks(&mut self) {
+        self.add_block(0);
+        for (insn_index, &insn) in self.bytecode().instructions.iter().enumerate() {
+            match insn {
+                Instruction::AD { op_code, d, .. } => match op_code {
+                    // followed by a jump
+                    OpCode::BC_ISLT
+                    | OpCode::BC_ISGE
+                    | OpCode::BC_ISLE
+                    | OpCode::BC_ISGT
+                    | OpCode::BC_ISEQV
+                    | OpCode::BC_ISNEV
+                    | OpCode::BC_ISEQS
+                    | OpCode::BC_ISNES
+                    | OpCode::BC_ISEQN
+                    | OpCode::BC_ISNEN
+                    | OpCode::BC_ISEQP
+                    | OpCode::BC_ISNEP
+                    | OpCode::BC_ISTC
+                    | OpCode::BC_ISFC
+                    | OpCode::BC_IST
+                    | OpCode::BC_ISF => {
+                        self.add_block(insn_index + 1);
+                        self.add_block(insn_index + 2);
+                    }
+                    OpCode::BC_JMP | OpCode::BC_UCLO | OpCode::BC_ISNEXT => {
+                        self.add_block(insn_index + 1);
+                        self.add_block(Self::jump_target(insn_index, d));
+                    }
+                    OpCode::BC_FORI => {
+                        let exit_index = Self::jump_target(insn_index, d);
+                        self.add_block(insn_index + 1);
+                        self.add_block(exit_index);
+                        // FORL, targets out of range are rejected when the block is lifted
+                        self.add_block(exit_index.wrapping_sub(1));
+                    }
+                    OpCode::BC_FORL | OpCode::BC_IFORL => {
+                        self.add_block(insn_index);
+                        self.add_block(insn_index + 1);
+                        self.add_block(Self::jump_target(insn_index, d));
+                    }
+                    OpCode::BC_ITERL | OpCode::BC_IITERL => {
+                        self.add_block(insn_index + 1);
+                        self.add_block(Self::jump_target(insn_index, d));
+                    }
+                    OpCode::BC_RETM
+                    | OpCode::BC_RET
+                    | OpCode::BC_RET0
+                    | OpCode::BC_RET1
+                    | OpCode::BC_CALLMT
+                    | OpCode::BC_CALLT => {
+                        self.add_block(insn_index + 1);
+                    }
+                    _ => {}
+                },
+                Instruction::ABC { op_code, .. } => {
+                    // the following ITERL is lifted together with ITERC/ITERN
+                    if matches!(op_code, OpCode::BC_ITERC | OpCode::BC_ITERN) {
+                        self.add_block(insn_index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn lift_block(&mut self, block_start: usize, block_end: usize) -> Result<LiftedBlock, String> {
+        let mut statements = Vec::with_capacity((block_start..=block_end).count());
+        let mut edges = Vec::new();
+
+        let mut top: Option<(ast::RValue, usize)> = None;
+
+        let mut iter = self.bytecode().instructions[block_start..=block_end]
+            .iter()
+            .enumerate();
+
+        while let Some((index, instruction)) = iter.next() {
+            let index = block_start + index;
+            match *instruction {
+                Instruction::ABC { op_code, a, b, c } => {
+                    let (a, b, c) = (a as usize, b as usize, c as usize);
+                    match op_code {
+                        OpCode::BC_ADDVN
+                        | OpCode::BC_SUBVN
+                        | OpCode::BC_MULVN
+                        | OpCode::BC_DIVVN
+                        | OpCode::BC_MODVN => {
+                            let target = self.register(a);
+                            let left = self.register(b);
+                            let right = self.number(c)?;
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::Binary::new(
+                                        left.into(),
+                                        right.into(),
+                                        Self::arithmetic_operation(op_code),
+                                    )
+                                    .into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_ADDNV
+                        | OpCode::BC_SUBNV
+                        | OpCode::BC_MULNV
+                        | OpCode::BC_DIVNV
+                        | OpCode::BC_MODNV => {
+                            let target = self.register(a);
+                            let left = self.number(c)?;
+                            let right = self.register(b);
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::Binary::new(
+                                        left.into(),
+                                        right.into(),
+                                        Self::arithmetic_operation(op_code),
+                                    )
+                                    .into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_ADDVV
+                        | OpCode::BC_SUBVV
+                        | OpCode::BC_MULVV
+                        | OpCode::BC_DIVVV
+                        | OpCode::BC_MODVV
+                        | OpCode::BC_POW => {
+                            let target = self.register(a);
+                            let left = self.register(b);
+                            let right = self.register(c);
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::Binary::new(
+                                        left.into(),
+                                        right.into(),
+                                        Self::arithmetic_operation(op_code),
+                                    )
+                                    .into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_CAT => {
+                            if c <= b {
+                                return Err(Self::invalid(index, instruction));
+                            }
+                            let mut operands = (b..=c)
+                                .map(|r| self.register(r))
+                                .rev()
+                                .collect::<Vec<_>>()
+                                .into_iter();
+                            let right = operands.next().unwrap();
+                            let left = operands.next().unwrap();
+                            let mut concat = ast::Binary::new(
+                                left.into(),
+                                right.into(),
+                                ast::BinaryOperation::Concat,
+                            );
+                            for r in operands {
+                                concat = ast::Binary::new(
+                                    r.into(),
+                                    concat.into(),
+                                    ast::BinaryOperation::Concat,
+                                );
+                            }
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![self.register(a).into()],
+                                    vec![concat.into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_TGETV | OpCode::BC_TGETS | OpCode::BC_TGETB => {
+                            let target = self.register(a);
+                            let table = self.register(b);
+                            let key = self.table_key(op_code, c)?;
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::Index::new(table.into(), key).into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_TSETV | OpCode::BC_TSETS | OpCode::BC_TSETB => {
+                            let value = self.register(a);
+                            let table = self.register(b);
+                            let key = self.table_key(op_code, c)?;
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![ast::Index::new(table.into(), key).into()],
+                                    vec![value.into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        // raw accesses don't call `__index` and `__newindex`
+                        OpCode::BC_TGETR => {
+                            let target = self.register(a);
+                            let call = ast::Call::new(
+                                ast::Global::new(b"rawget".to_vec()).into(),
+                                vec![self.register(b).into(), self.register(c).into()],
+                            );
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::RValue::Select(call.into())],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_TSETR => {
+                            let call = ast::Call::new(
+                                ast::Global::new(b"rawset".to_vec()).into(),
+                                vec![
+                                    self.register(b).into(),
+                                    self.register(c).into(),
+                                    self.register(a).into(),
+                                ],
+                            );
+                            statements.push(call.into());
+                        }
+                        OpCode::BC_CALL | OpCode::BC_CALLM => {
+                            let arguments_start = a + 1 + self.fr2 as usize;
+                            let arguments = if op_code == OpCode::BC_CALL {
+                                (arguments_start..a + c + self.fr2 as usize)
+                                    .map(|r| self.register(r).into())
+                                    .collect()
+                            } else {
+                                let top = top
+                                    .take()
+                                    .ok_or_else(|| Self::invalid(index, instruction))?;
+                                (arguments_start..top.1)
+                                    .map(|r| self.register(r).into())
+                                    .chain(std::iter::once(top.0))
+                                    .collect()
+                            };
+
+                            let call = ast::Call::new(self.register(a).into(), arguments);
+
+                            if b != 0 {
+                                if b == 1 {
+                                    statements.push(call.into());
+                                } else {
+                                    statements.push(
+                                        ast::Assign::new(
+                                            (a..a + b - 1)
+                                                .map(|r| self.register(r).into())
+                                                .collect(),
+                                            vec![ast::RValue::Select(call.into())],
+                                        )
+                                        .into(),
+                                    );
+                                }
+                            } else {
+                                top = Some((call.into(), a));
+                            }
+                        }
+                        OpCode::BC_ITERC | OpCode::BC_ITERN => {
+                            // B is one more than the number of loop variables, there's at least one
+                            let base = a
+                                .checked_sub(3)
+                                .filter(|_| b >= 2)
+                                .ok_or_else(|| Self::invalid(index, instruction))?;
+                            let generator = self.register(base);
+                            let state = self.register(base + 1);
+                            let body_index = match iter.next() {
+                                Some((
+                                    _,
+                                    &Instruction::AD {
+                                        op_code: OpCode::BC_ITERL | OpCode::BC_IITERL,
+                                        d,
+                                        ..
+                                    },
+                                )) => Self::jump_target(index + 1, d),
+                                _ => return Err(Self::invalid(index, instruction)),
+                            };
+                            statements.push(
+                                ast::GenericForNext::new(
+                                    (a..a + b - 1).map(|r| self.register(r)).collect(),
+                                    generator.into(),
+                                    state,
+                                )
+                                .into(),
+                            );
+                            edges.push((
+                                self.block_to_node(body_index)?,
+                                BlockEdge::new(BranchType::Then),
+                            ));
+                            edges.push((
+                                self.block_to_node(index + 2)?,
+                                BlockEdge::new(BranchType::Else),
+                            ));
+                        }
+                        OpCode::BC_VARG => {
+                            let vararg = ast::VarArg {};
+                            if b != 0 {
+                                statements.push(
+                                    ast::Assign::new(
+                                        (a..a + b - 1).map(|r| self.register(r).into()).collect(),
+                                        vec![ast::RValue::Select(vararg.into())],
+                                    )
+                                    .into(),
+                                );
+                            } else {
+                                top = Some((vararg.into(), a));
+                            }
+                        }
+                        _ => return Err(Self::unsupported(index, op_code)),
+                    }
+                }
+                Instruction::AD { op_code, a, d } => {
+                    let a = a as usize;
+                    match op_code {
+                        OpCode::BC_ISLT
+                        | OpCode::BC_ISGE
+                        | OpCode::BC_ISLE
+                        | OpCode::BC_ISGT
+                        | OpCode::BC_ISEQV
+                        | OpCode::BC_ISNEV
+                        | OpCode::BC_ISEQS
+                        | OpCode::BC_ISNES
+                        | OpCode::BC_ISEQN
+                        | OpCode::BC_ISNEN
+                        | OpCode::BC_ISEQP
+                        | OpCode::BC_ISNEP => {
+                            let left = self.register(a).into();
+                            let right: ast::RValue = match op_code {
+                                OpCode::BC_ISEQS | OpCode::BC_ISNES => {
+                                    ast::Literal::String(self.string(d as usize)?).into()
+                                }
+                                OpCode::BC_ISEQN | OpCode::BC_ISNEN => {
+                                    self.number(d as usize)?.into()
+                                }
+                                OpCode::BC_ISEQP | OpCode::BC_ISNEP => {
+                                    Self::primitive(d as usize)?.into()
+                                }
+                                _ => self.register(d as usize).into(),
+                            };
+                            let condition = match op_code {
+                                OpCode::BC_ISLT => {
+                                    ast::Binary::new(left, right, ast::BinaryOperation::LessThan)
+                                        .into()
+                                }
+                                OpCode::BC_ISGE => ast::Unary::new(
+                                    ast::Binary::new(left, right, ast::BinaryOperation::LessThan)
+                                        .into(),
+                                    ast::UnaryOperation::Not,
+                                )
+                                .into(),
+                                OpCode::BC_ISLE => ast::Binary::new(
+                                    left,
+                                    right,
+                                    ast::BinaryOperation::LessThanOrEqual,
+                                )
+                                .into(),
+                                OpCode::BC_ISGT => ast::Unary::new(
+                                    ast::Binary::new(
+                                        left,
+                                        right,
+                                        ast::BinaryOperation::LessThanOrEqual,
+                                    )
+                                    .into(),
+                                    ast::UnaryOperation::Not,
+                                )
+                                .into(),
+                                OpCode::BC_ISEQV
+                                | OpCode::BC_ISEQS
+                                | OpCode::BC_ISEQN
+                                | OpCode::BC_ISEQP => {
+                                    ast::Binary::new(left, right, ast::BinaryOperation::Equal)
+                                        .into()
+                                }
+                                _ => ast::Binary::new(left, right, ast::BinaryOperation::NotEqual)
+                                    .into(),
+                            };
+                            statements.push(
+                                ast::If::new(
+                                    condition,
+                                    ast::Block::default(),
+                                    ast::Block::default(),
+                                )
+                                .into(),
+                            );
+                            edges.extend(self.conditional_edges(index)?);
+                        }
+                        OpCode::BC_ISTC | OpCode::BC_ISFC | OpCode::BC_IST | OpCode::BC_ISF => {
+                            let value: ast::RValue = self.register(d as usize).into();
+                            let condition = match op_code {
+                                OpCode::BC_ISTC | OpCode::BC_IST => value.clone(),
+                                _ => {
+                                    ast::Unary::new(value.clone(), ast::UnaryOperation::Not).into()
+                                }
+                            };
+                            statements.push(
+                                ast::If::new(
+                                    condition,
+                                    ast::Block::default(),
+                                    ast::Block::default(),
+                                )
+                                .into(),
+                            );
+                            // the assignment only happens if the jump is taken
+                            if matches!(op_code, OpCode::BC_ISTC | OpCode::BC_ISFC) {
+                                let assign =
+                                    ast::Assign::new(vec![self.register(a).into()], vec![value]);
+                                self.function
+                                    .block_mut(self.block_to_node(index + 1)?)
+                                    .unwrap()
+                                    .push(assign.into());
+                            }
+                            edges.extend(self.conditional_edges(index)?);
+                        }
+                        OpCode::BC_MOV | OpCode::BC_NOT | OpCode::BC_UNM | OpCode::BC_LEN => {
+                            let target = self.register(a);
+                            let value = self.register(d as usize).into();
+                            let value = match op_code {
+                                OpCode::BC_MOV => value,
+                                OpCode::BC_NOT => {
+                                    ast::Unary::new(value, ast::UnaryOperation::Not).into()
+                                }
+                                OpCode::BC_UNM => {
+                                    ast::Unary::new(value, ast::UnaryOperation::Negate).into()
+                                }
+                                OpCode::BC_LEN => {
+                                    ast::Unary::new(value, ast::UnaryOperation::Length).into()
+                                }
+                                _ => unreachable!(),
+                            };
+                            statements
+                                .push(ast::Assign::new(vec![target.into()], vec![value]).into());
+                        }
+                        OpCode::BC_KSTR
+                        | OpCode::BC_KCDATA
+                        | OpCode::BC_KSHORT
+                        | OpCode::BC_KNUM
+                        | OpCode::BC_KPRI => {
+                            let target = self.register(a);
+                            let value = match op_code {
+                                OpCode::BC_KSTR | OpCode::BC_KCDATA => self.constant(d as usize)?,
+                                OpCode::BC_KSHORT => ast::Literal::Number(d as i16 as f64).into(),
+                                OpCode::BC_KNUM => self.number(d as usize)?.into(),
+                                OpCode::BC_KPRI => Self::primitive(d as usize)?.into(),
+                                _ => unreachable!(),
+                            };
+                            statements
+                                .push(ast::Assign::new(vec![target.into()], vec![value]).into());
+                        }
+                        OpCode::BC_KNIL => {
+                            for register in a..=d as usize {
+                                statements.push(
+                                    ast::Assign::new(
+                                        vec![self.register(register).into()],
+                                        vec![ast::Literal::Nil.into()],
+                                    )
+                                    .into(),
+                                );
+                            }
+                        }
+                        OpCode::BC_UGET => {
+                            let target = self.register(a);
+                            let up = self.upvalue(d as usize)?;
+                            statements.push(
+                                ast::Assign::new(vec![target.into()], vec![up.into()]).into(),
+                            );
+                        }
+                        OpCode::BC_USETV
+                        | OpCode::BC_USETS
+                        | OpCode::BC_USETN
+                        | OpCode::BC_USETP => {
+                            let up = self.upvalue(a)?;
+                            let value: ast::RValue = match op_code {
+                                OpCode::BC_USETV => self.register(d as usize).into(),
+                                OpCode::BC_USETS => {
+                                    ast::Literal::String(self.string(d as usize)?).into()
+                                }
+                                OpCode::BC_USETN => self.number(d as usize)?.into(),
+                                OpCode::BC_USETP => Self::primitive(d as usize)?.into(),
+                                _ => unreachable!(),
+                            };
+                            statements.push(ast::Assign::new(vec![up.into()], vec![value]).into());
+                        }
+                        OpCode::BC_UCLO => {
+                            let locals = (a..self.bytecode().frame_size as usize)
+                                .map(|i| self.register(i))
+                                .collect();
+                            statements.push(ast::Close { locals }.into());
+                            edges.push((
+                                self.block_to_node(Self::jump_target(index, d))?,
+                                BlockEdge::new(BranchType::Unconditional),
+                            ));
+                        }
+                        OpCode::BC_FNEW => {
+                            let dest_local = self.register(a);
+                            let func_index = match self.bytecode().constants.get(d as usize) {
+                                Some(&BytecodeConstant::Child(func_index)) => func_index,
+                                _ => return Err(Self::invalid(index, instruction)),
+                            };
+
+                            let function_list = self.function_list;
+                            let upvalues_passed = function_list[func_index]
+                                .upvalues
+                                .iter()
+                                .map(|&upvalue| {
+                                    Ok(if upvalue & UPVALUE_LOCAL != 0 {
+                                        let local = self.register((upvalue & 0xff) as usize);
+                                        if upvalue & UPVALUE_IMMUTABLE != 0 {
+                                            ast::Upvalue::Copy(local)
+                                        } else {
+                                            ast::Upvalue::Ref(local)
+                                        }
+                                    } else {
+                                        ast::Upvalue::Ref(self.upvalue(upvalue as usize)?)
+                                    })
+                                })
+                                .collect::<Result<_, String>>()?;
+
+                            let function = Arc::<Mutex<_>>::default();
+                            self.child_functions
+                                .insert(ByAddress(function.clone()), func_index);
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![dest_local.into()],
+                                    vec![ast::Closure {
+                                        function: ByAddress(function),
+                                        upvalues: upvalues_passed,
+                                    }
+                                    .into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_TNEW | OpCode::BC_TDUP => {
+                            let table = if op_code == OpCode::BC_TDUP {
+                                self.table(d as usize)?
+                            } else {
+                                ast::Table::default()
+                            };
+                            statements.push(
+                                ast::Assign::new(vec![self.register(a).into()], vec![table.into()])
+                                    .into(),
+                            );
+                        }
+                        OpCode::BC_GGET => {
+                            let target = self.register(a);
+                            let global_name = self.string(d as usize)?;
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![target.into()],
+                                    vec![ast::Global::new(global_name).into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_GSET => {
+                            let value = self.register(a);
+                            let global_name = self.string(d as usize)?;
+                            statements.push(
+                                ast::Assign::new(
+                                    vec![ast::Global::new(global_name).into()],
+                                    vec![value.into()],
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_TSETM => {
+                            // the index is stored in the low 32 bits of a biased double
+                            let start = match self.bytecode().numbers.get(d as usize) {
+                                Some(&NumberConstant::Integer(start)) => start as usize,
+                                Some(&NumberConstant::Number(start)) => {
+                                    start.to_bits() as u32 as usize
+                                }
+                                None => return Err(Self::invalid(index, instruction)),
+                            };
+                            let table = a
+                                .checked_sub(1)
+                                .ok_or_else(|| Self::invalid(index, instruction))?;
+                            let top = top
+                                .take()
+                                .ok_or_else(|| Self::invalid(index, instruction))?;
+                            statements.push(
+                                ast::SetList::new(
+                                    self.register(table),
+                                    start,
+                                    (a..top.1).map(|r| self.register(r).into()).collect(),
+                                    Some(top.0),
+                                )
+                                .into(),
+                            );
+                        }
+                        OpCode::BC_CALLT | OpCode::BC_CALLMT => {
+                            let arguments_start = a + 1 + self.fr2 as usize;
+                            let arguments = if op_code == OpCode::BC_CALLT {
+                                (arguments_start..a + d as usize + self.fr2 as usize)
+                                    .map(|r| self.register(r).into())
+                                    .collect()
+                            } else {
+                                let top = top
+                                    .take()
+                                    .ok_or_else(|| Self::invalid(index, instruction))?;
+                                (arguments_start..top.1)
+                                    .map(|r| self.register(r).into())
+                                    .chain(std::iter::once(top.0))
+                                    .collect()
+                            };
+                            let call = ast::Call::new(self.register(a).into(), arguments);
+                            statements.push(ast::Return::new(vec![call.into()]).into());
+                        }
+                        OpCode::BC_RETM | OpCode::BC_RET | OpCode::BC_RET0 | OpCode::BC_RET1 => {
+                            let values = match op_code {
+                                OpCode::BC_RETM => {
+                                    let (tail, end) = top
+                                        .take()
+                                        .ok_or_else(|| Self::invalid(index, instruction))?;
+                                    (a..end)
+                                        .map(|r| self.register(r).into())
+                                        .chain(std::iter::once(tail))
+                                        .collect()
+                                }
+                                OpCode::BC_RET => {
+                                    let end = (a + d as usize)
+                                        .checked_sub(1)
+                                        .ok_or_else(|| Self::invalid(index, instruction))?;
+                                    (a..end).map(|r| self.register(r).into()).collect()
+                                }
+                                OpCode::BC_RET0 => Vec::new(),
+                                OpCode::BC_RET1 => vec![self.register(a).into()],
+                                _ => unreachable!(),
+                            };
+                            statements.push(ast::Return::new(values).into());
+                        }
+                        OpCode::BC_FORI => {
+                            let counter = self.register(a);
+                            let limit = self.register(a + 1);
+                            let step = self.register(a + 2);
+                            statements.push(ast::NumForInit::new(counter, limit, step).into());
+
+                            // FORI jumps past the FORL of the loop
+                            let loop_index = Self::jump_target(index, d).wrapping_sub(1);
+                            if !matches!(
+                                self.bytecode()
+                                    .instructions
+                                    .get(loop_index)
+                                    .map(|i| i.op_code()),
+                                Some(OpCode::BC_FORL | OpCode::BC_IFORL)
+                            ) {
+                                return Err(Self::invalid(index, instruction));
+                            }
+                            edges.push((
+                                self.block_to_node(loop_index)?,
+                                BlockEdge::new(BranchType::Unconditional),
+                            ));
+                        }
+                        OpCode::BC_FORL | OpCode::BC_IFORL => {
+                            let counter = self.register(a);
+                            let limit = self.register(a + 1);
+                            let step = self.register(a + 2);
+                            let external_counter = self.register(a + 3);
+                            statements.push(
+                                ast::NumForNext::new(counter.clone(), limit.into(), step.into())
+                                    .into(),
+                            );
+
+                            let body_node = self.block_to_node(Self::jump_target(index, d))?;
+                            let node = self.block_to_node(block_start)?;
+                            if self
+                                .insert_between
+                                .insert(
+                                    node,
+                                    (
+                                        body_node,
+                                        ast::Assign::new(
+                                            vec![external_counter.into()],
+                                            vec![counter.into()],
+                                        )
+                                        .into(),
+                                    ),
+                                )
+                                .is_some()
+                            {
+                                return Err(Self::invalid(index, instruction));
+                            }
+                            edges.push((body_node, BlockEdge::new(BranchType::Then)));
+                            edges.push((
+                                self.block_to_node(index + 1)?,
+                                BlockEdge::new(BranchType::Else),
+                            ));
+                        }
+                        OpCode::BC_ISNEXT | OpCode::BC_JMP => {
+                            let target = Self::jump_target(index, d);
+                            if op_code == OpCode::BC_ISNEXT
+                                || self.is_generic_for_prep(index, target)
+                            {
+                                // the registers before the A of ITERC/ITERN
+                                let base = match self.bytecode().instructions.get(target) {
+                                    Some(&Instruction::ABC { a, .. }) => {
+                                        (a as usize).checked_sub(3)
+                                    }
+                                    _ => None,
+                                }
+                                .ok_or_else(|| Self::invalid(index, instruction))?;
+                                let generator = self.register(base);
+                                let state = self.register(base + 1);
+                                let counter = self.register(base + 2);
+                                statements.push(
+                                    ast::GenericForInit::new(generator, state, counter).into(),
+                                );
+                            }
+                            edges.push((
+                                self.block_to_node(target)?,
+                                BlockEdge::new(BranchType::Unconditional),
+                            ));
+                        }
+                        // loop hints
+                        OpCode::BC_LOOP | OpCode::BC_ILOOP => {}
+                        _ => return Err(Self::unsupported(index, op_code)),
+                    }
+                }
+            }
+        }
+
+        if edges.is_empty()
+            && !matches!(
+                self.bytecode().instructions[block_end].op_code(),
+                OpCode::BC_RETM
+                    | OpCode::BC_RET
+                    | OpCode::BC_RET0
+                    | OpCode::BC_RET1
+                    | OpCode::BC_CALLMT
+                    | OpCode::BC_CALLT
+            )
+        {
+            if block_end + 1 == self.bytecode().instructions.len() {
+                statements
+                    .push(ast::Comment::new("warning: block does not return".to_string()).into());
+            } else {
+                edges.push((
+                    self.block_to_node(block_end + 1)?,
+                    BlockEdge::new(BranchType::Unconditional),
+                ));
+            }
+        }
+
+        Ok((statements, edges))
+    }
+
+    fn unsupported(insn_index: usize, op_code: OpCode) -> String {
+        format!("unsupported instruction {:?} at {}", op_code, insn_index)
+    }
+
+    // operands that don't make sense for the instruction, only possible in malformed bytecode
+    fn invalid(insn_index: usize, instruction: &Instruction) -> String {
+        format!("invalid instruction {:?} at {}", instruction, insn_index)
+    }
+
+    // comparisons are followed by a jump that is taken if the condition is true
+    fn conditional_edges(&self, insn_index: usize) -> Result<[(NodeIndex, BlockEdge); 2], String> {
+        Ok([
+            (
+                self.block_to_node(insn_index + 1)?,
+                BlockEdge::new(BranchType::Then),
+            ),
+            (
+                self.block_to_node(insn_index + 2)?,
+                BlockEdge::new(BranchType::Else),
+            ),
+        ])
+    }
+
+    // `for ... in` loops start with a jump to ITERC/ITERN, which is followed by an ITERL
+    // that jumps back to the instruction after the jump
+    fn is_generic_for_prep(&self, insn_index: usize, target: usize) -> bool {
+        let instructions = &self.bytecode().instructions;
+        matches!(
+            instructions.get(target).map(|i| i.op_code()),
+            Some(OpCode::BC_ITERC | OpCode::BC_ITERN)
+        ) && matches!(
+            instructions.get(target + 1),
+            Some(&Instruction::AD {
+                op_code: OpCode::BC_ITERL | OpCode::BC_IITERL,
+                d,
+                ..
+            }) if Self::jump_target(target + 1, d) == insn_index + 1
+        )
+    }
+
+    fn arithmetic_operation(op_code: OpCode) -> ast::BinaryOperation {
+        match op_code {
+            OpCode::BC_ADDVN | OpCode::BC_ADDNV | OpCode::BC_ADDVV => ast::BinaryOperation::Add,
+            OpCode::BC_SUBVN | OpCode::BC_SUBNV | OpCode::BC_SUBVV => ast::BinaryOperation::Sub,
+            OpCode::BC_MULVN | OpCode::BC_MULNV | OpCode::BC_MULVV => ast::BinaryOperation::Mul,
+            OpCode::BC_DIVVN | OpCode::BC_DIVNV | OpCode::BC_DIVVV => ast::BinaryOperation::Div,
+            OpCode::BC_MODVN | OpCode::BC_MODNV | OpCode::BC_MODVV => ast::BinaryOperation::Mod,
+            OpCode::BC_POW => ast::BinaryOperation::Pow,
+            _ => unreachable!(),
+        }
+    }
+
+    fn table_key(&mut self, op_code: OpCode, c: usize) -> Result<ast::RValue, String> {
+        Ok(match op_code {
+            OpCode::BC_TGETS | OpCode::BC_TSETS => ast::Literal::String(self.string(c)?).into(),
+            OpCode::BC_TGETB | OpCode::BC_TSETB => ast::Literal::Number(c as f64).into(),
+            _ => self.register(c).into(),
+        })
+    }
+
+    fn register(&mut self, index: usize) -> ast::RcLocal {
+        self.register_map
+            .entry(index)
+            .or_insert_with(|| ast::RcLocal::new(ast::Local::register(index)))
+            .clone()
+    }
+
+    fn upvalue(&self, index: usize) -> Result<ast::RcLocal, String> {
+        self.upvalues
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("upvalue {} doesn't exist", index))
+    }
+
+    fn string(&self, index: usize) -> Result<Vec<u8>, String> {
+        match self.bytecode().constants.get(index) {
+            Some(BytecodeConstant::String(string)) => Ok(string.clone()),
+            constant => Err(format!("constant {} isn't a string: {:?}", index, constant)),
+        }
+    }
+
+    fn number(&self, index: usize) -> Result<ast::Literal, String> {
+        match self.bytecode().numbers.get(index) {
+            Some(number) => Ok(ast::Literal::Number(number.as_f64())),
+            None => Err(format!("number constant {} doesn't exist", index)),
+        }
+    }
+
+    fn primitive(index: usize) -> Result<ast::Literal, String> {
+        match index {
+            0 => Ok(ast::Literal::Nil),
+            1 => Ok(ast::Literal::Boolean(false)),
+            2 => Ok(ast::Literal::Boolean(true)),
+            _ => Err(format!("primitive {} doesn't exist", index)),
+        }
+    }
+
+    fn constant(&self, index: usize) -> Result<ast::RValue, String> {
+        Ok(match self.bytecode().constants.get(index) {
+            Some(BytecodeConstant::String(string)) => ast::Literal::String(string.clone()).into(),
+            Some(&BytecodeConstant::Int64(value)) => ast::Literal::Int64(value).into(),
+            Some(&BytecodeConstant::UInt64(value)) => ast::Literal::UInt64(value).into(),
+            // the parser only creates these from imaginary literals, `1 + 2i` is an addition
+            Some(&BytecodeConstant::Complex(real, imaginary)) => {
+                if real == 0.0 {
+                    ast::Literal::Imaginary(imaginary).into()
+                } else {
+                    ast::Binary::new(
+                        ast::Literal::Number(real).into(),
+                        ast::Literal::Imaginary(imaginary).into(),
+                        ast::BinaryOperation::Add,
+                    )
+                    .into()
+                }
+            }
+            constant => {
+                return Err(format!(
+                    "constant {} isn't a string or cdata: {:?}",
+                    index, constant
+                ))
+            }
+        })
+    }
+
+    fn table_value(value: &TableValue) -> ast::Literal {
+        match value {
+            TableValue::Nil => ast::Literal::Nil,
+            &TableValue::Boolean(value) => ast::Literal::Boolean(value),
+            &TableValue::Integer(value) => ast::Literal::Number(value as f64),
+            &TableValue::Number(value) => ast::Literal::Number(value),
+            TableValue::String(value) => ast::Literal::String(value.clone()),
+        }
+    }
+
+    // template tables used by TDUP, keys with a non-constant value are nil in the template
+    fn table(&self, index: usize) -> Result<ast::Table, String> {
+        let template = match self.bytecode().constants.get(index) {
+            Some(BytecodeConstant::Table(template)) => template,
+            constant => return Err(format!("constant {} isn't a table: {:?}", index, constant)),
+        };
+        let mut table = ast::Table::default();
+        // slot 0 of the array part is only used for `[0] = ...`
+        let mut sequential = true;
+        for (i, value) in template.array.iter().enumerate() {
+            if matches!(value, TableValue::Nil) {
+                sequential &= i == 0;
+                continue;
+            }
+            let value = Self::table_value(value).into();
+            if i != 0 && sequential {
+                table.0.push((None, value));
+            } else {
+                table
+                    .0
+                    .push((Some(ast::Literal::Number(i as f64).into()), value));
+            }
+        }
+        for (key, value) in &template.hash {
+            if !matches!(value, TableValue::Nil) {
+                table.0.push((
+                    Some(Self::table_value(key).into()),
+                    Self::table_value(value).into(),
+                ));
+            }
+        }
+        Ok(table)
+    }
+
+    fn jump_target(insn_index: usize, d: u16) -> usize {
+        ((insn_index + 1) as isize + d as isize - JUMP_BIAS) as usize
+    }
+
+    fn block_to_node(&self, insn_index: usize) -> Result<NodeIndex, String> {
+        self.blocks
+            .get(&insn_index)
+            .copied()
+            .ok_or_else(|| format!("jump to {}, which isn't an instruction", insn_index))
+    }
+}
@@ -0,0 +1,11 @@
+fn main() {
+    let file_name = std::env::args().nth(1).expect("expected exactly one file");
+    let bytecode = std::fs::read(file_name).expect("failed to read file");
+    match luajit_lifter::decompile_bytecode(&bytecode, &Default::default()) {
+        Ok(result) => println!("{}", result.source),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
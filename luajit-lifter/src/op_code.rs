@@ -0,0 +1,194 @@
+use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
+
+// opcode numbering is the one used by LuaJIT 2.1 (bytecode dump version 2),
+// see `from_version` for 2.0.
+#[repr(u8)]
+#[derive(Debug, TryFromPrimitive, Eq, PartialEq, Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub enum OpCode {
+    // comparisons, always followed by a JMP that is taken if the comparison is true
+    // A: var, D: var/str/num/pri
+    BC_ISLT,
+    BC_ISGE,
+    BC_ISLE,
+    BC_ISGT,
+    BC_ISEQV,
+    BC_ISNEV,
+    BC_ISEQS,
+    BC_ISNES,
+    BC_ISEQN,
+    BC_ISNEN,
+    BC_ISEQP,
+    BC_ISNEP,
+
+    // unary tests, always followed by a JMP
+    // ISTC/ISFC: A = D and jump if D is truthy/falsy
+    // IST/ISF: jump if D is truthy/falsy
+    BC_ISTC,
+    BC_ISFC,
+    BC_IST,
+    BC_ISF,
+    // 2.1 only, emitted for type checks of for loop operands
+    BC_ISTYPE,
+    BC_ISNUM,
+
+    // unary ops, A = op D
+    BC_MOV,
+    BC_NOT,
+    BC_UNM,
+    BC_LEN,
+
+    // binary ops
+    // VN: A = B op num[C], NV: A = num[C] op B, VV: A = B op C
+    BC_ADDVN,
+    BC_SUBVN,
+    BC_MULVN,
+    BC_DIVVN,
+    BC_MODVN,
+    BC_ADDNV,
+    BC_SUBNV,
+    BC_MULNV,
+    BC_DIVNV,
+    BC_MODNV,
+    BC_ADDVV,
+    BC_SUBVV,
+    BC_MULVV,
+    BC_DIVVV,
+    BC_MODVV,
+    BC_POW,
+    // A = B .. ~ .. C
+    BC_CAT,
+
+    // constants, A = D
+    BC_KSTR,
+    BC_KCDATA,
+    BC_KSHORT,
+    BC_KNUM,
+    BC_KPRI,
+    // A, ..., D = nil
+    BC_KNIL,
+
+    // upvalues
+    BC_UGET,
+    BC_USETV,
+    BC_USETS,
+    BC_USETN,
+    BC_USETP,
+    // close upvalues >= A and jump to D
+    BC_UCLO,
+    // A = closure(kgc[D])
+    BC_FNEW,
+
+    // tables
+    BC_TNEW,
+    BC_TDUP,
+    BC_GGET,
+    BC_GSET,
+    BC_TGETV,
+    BC_TGETS,
+    BC_TGETB,
+    BC_TGETR,
+    BC_TSETV,
+    BC_TSETS,
+    BC_TSETB,
+    // (A-1)[num[D]], (A-1)[num[D] + 1], ... = A, A+1, ..., MULTRES
+    BC_TSETM,
+    BC_TSETR,
+
+    // calls and vararg
+    // CALLM/CALL: A, ..., A+B-2 = A(A+1, ..., A+C-1[+MULTRES])
+    BC_CALLM,
+    BC_CALL,
+    // CALLMT/CALLT: return A(A+1, ..., A+D-1[+MULTRES])
+    BC_CALLMT,
+    BC_CALLT,
+    // A, A+1, A+2 = A-3, A-2, A-1; A, ..., A+B-2 = A(A+1, A+2)
+    BC_ITERC,
+    BC_ITERN,
+    // A, ..., A+B-2 = ...
+    BC_VARG,
+    BC_ISNEXT,
+
+    // returns
+    BC_RETM,
+    BC_RET,
+    BC_RET0,
+    BC_RET1,
+
+    // loops and branches
+    BC_FORI,
+    BC_JFORI,
+    BC_FORL,
+    BC_IFORL,
+    BC_JFORL,
+    BC_ITERL,
+    BC_IITERL,
+    BC_JITERL,
+    BC_LOOP,
+    BC_ILOOP,
+    BC_JLOOP,
+    BC_JMP,
+
+    // function headers, these never appear in dumped bytecode
+    BC_FUNCF,
+    BC_IFUNCF,
+    BC_JFUNCF,
+    BC_FUNCV,
+    BC_IFUNCV,
+    BC_JFUNCV,
+    BC_FUNCC,
+    BC_FUNCCW,
+}
+
+impl OpCode {
+    // 2.0 (version 1) lacks ISTYPE, ISNUM, TGETR and TSETR
+    pub fn from_version(op_code: u8, version: u8) -> Result<Self, TryFromPrimitiveError<Self>> {
+        let op_code = if version == 1 {
+            match op_code {
+                0..=15 => op_code,
+                16..=56 => op_code + 2,
+                57..=60 => op_code + 3,
+                _ => op_code.saturating_add(4),
+            }
+        } else {
+            op_code
+        };
+        Self::try_from(op_code)
+    }
+
+    pub fn is_abc(self) -> bool {
+        matches!(
+            self,
+            OpCode::BC_ADDVN
+                | OpCode::BC_SUBVN
+                | OpCode::BC_MULVN
+                | OpCode::BC_DIVVN
+                | OpCode::BC_MODVN
+                | OpCode::BC_ADDNV
+                | OpCode::BC_SUBNV
+                | OpCode::BC_MULNV
+                | OpCode::BC_DIVNV
+                | OpCode::BC_MODNV
+                | OpCode::BC_ADDVV
+                | OpCode::BC_SUBVV
+                | OpCode::BC_MULVV
+                | OpCode::BC_DIVVV
+                | OpCode::BC_MODVV
+                | OpCode::BC_POW
+                | OpCode::BC_CAT
+                | OpCode::BC_TGETV
+                | OpCode::BC_TGETS
+                | OpCode::BC_TGETB
+                | OpCode::BC_TGETR
+                | OpCode::BC_TSETV
+                | OpCode::BC_TSETS
+                | OpCode::BC_TSETB
+                | OpCode::BC_TSETR
+                | OpCode::BC_CALLM
+                | OpCode::BC_CALL
+                | OpCode::BC_ITERC
+                | OpCode::BC_ITERN
+                | OpCode::BC_VARG
+        )
+    }
+}
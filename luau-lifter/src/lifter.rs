@@ -2,7 +2,7 @@ use anyhow::Result;
 
 use by_address::ByAddress;
 
-use itertools::Itertools;
+use indexmap::IndexMap;
 use parking_lot::Mutex;
 use petgraph::stable_graph::NodeIndex;
 
@@ -22,12 +22,23 @@ use cfg::{
     function::Function,
 };
 
+// the function, its upvalues and the closures it creates with the ids of their functions
+type Lifted = (
+    Function,
+    Vec<ast::RcLocal>,
+    IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+);
+
+// the statements of a block and the edges leaving it
+type LiftedBlock = (Vec<ast::Statement>, Vec<(NodeIndex, BlockEdge)>);
+
 pub struct Lifter<'a> {
     function_list: &'a Vec<BytecodeFunction>,
     string_table: &'a Vec<Vec<u8>>,
     blocks: FxHashMap<usize, NodeIndex>,
     function: Function,
-    child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+    // in the order the closures appear, so functions are lifted in the same order every run
+    child_functions: IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
     register_map: FxHashMap<usize, ast::RcLocal>,
     constant_map: FxHashMap<usize, ast::Literal>,
     current_node: Option<NodeIndex>,
@@ -39,28 +50,24 @@ impl<'a> Lifter<'a> {
         f_list: &'a Vec<BytecodeFunction>,
         str_list: &'a Vec<Vec<u8>>,
         function_id: usize,
-    ) -> (
-        Function,
-        Vec<ast::RcLocal>,
-        FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
-    ) {
+    ) -> Result<Lifted, String> {
         let mut context = Self {
             function_list: f_list,
             string_table: str_list,
             blocks: FxHashMap::default(),
             function: Function::new(function_id),
-            child_functions: FxHashMap::default(),
+            child_functions: IndexMap::new(),
             register_map: FxHashMap::default(),
             constant_map: FxHashMap::default(),
             current_node: None,
             upvalues: Vec::new(),
         };
 
-        context.lift_function();
-        (context.function, context.upvalues, context.child_functions)
+        context.lift_function()?;
+        Ok((context.function, context.upvalues, context.child_functions))
     }
 
-    fn lift_function(&mut self) {
+    fn lift_function(&mut self) -> Result<(), String> {
         self.discover_blocks().unwrap();
 
         let mut blocks = self.blocks.keys().cloned().collect::<Vec<_>>();
@@ -96,7 +103,7 @@ impl<'a> Lifter<'a> {
         }
 
         for i in 0..self.function_list[self.function.id].num_parameters {
-            let parameter = ast::RcLocal::default();
+            let parameter = ast::RcLocal::new(ast::Local::register(i as usize));
             self.function.parameters.push(parameter.clone());
             self.register_map.insert(i as usize, parameter);
         }
@@ -105,12 +112,14 @@ impl<'a> Lifter<'a> {
 
         for (start_pc, end_pc) in block_ranges {
             self.current_node = Some(self.block_to_node(start_pc));
-            let (statements, edges) = self.lift_block(start_pc, end_pc);
+            let (statements, edges) = self.lift_block(start_pc, end_pc)?;
             let block = self.function.block_mut(self.current_node.unwrap()).unwrap();
             block.0.extend(statements);
             self.function.set_edges(self.current_node.unwrap(), edges);
         }
 
+        self.function.block_pcs = self.blocks.iter().map(|(&pc, &node)| (node, pc)).collect();
+
         let entry_node = self.function.new_block();
         self.function.set_edges(
             entry_node,
@@ -120,6 +129,7 @@ impl<'a> Lifter<'a> {
             )],
         );
         self.function.set_entry(entry_node);
+        Ok(())
     }
 
     fn discover_blocks(&mut self) -> Result<()> {
@@ -241,11 +251,7 @@ impl<'a> Lifter<'a> {
         Ok(())
     }
 
-    fn lift_block(
-        &mut self,
-        block_start: usize,
-        block_end: usize,
-    ) -> (Vec<ast::Statement>, Vec<(NodeIndex, BlockEdge)>) {
+    fn lift_block(&mut self, block_start: usize, block_end: usize) -> Result<LiftedBlock, String> {
         let mut statements = Vec::with_capacity((block_start..=block_end).count());
         let mut edges = Vec::new();
 
@@ -1103,26 +1109,35 @@ impl<'a> Lifter<'a> {
                             ));
                         }
                     }
+                    // FORNPREP jumps past FORNLOOP if the loop doesn't run at all. the loop is
+                    // entered through FORNLOOP instead, like lua 5.1's FORPREP, which is the same
+                    // once it's structured
                     OpCode::LOP_FORNPREP => {
-                        // TODO: do this properly
                         let limit = self.register(a as _);
                         let step = self.register((a + 1) as _);
                         let counter = self.register((a + 2) as _);
                         statements.push(ast::NumForInit::new(counter, limit, step).into());
-
-                        let loop_node = self
-                            .function
-                            .predecessor_blocks(self.block_to_node(block_start + index + 1))
-                            .filter(|&p| {
-                                self.function
-                                    .block(p)
-                                    .unwrap()
-                                    .last()
-                                    .is_some_and(|s| matches!(s, ast::Statement::NumForNext(_)))
+                        // FORNPREP jumps past the FORNLOOP of the loop
+                        let loop_index =
+                            ((block_start + index + 1) as isize + d as isize - 1) as usize;
+                        if !matches!(
+                            self.function_list[self.function.id]
+                                .instructions
+                                .get(loop_index),
+                            Some(Instruction::AD {
+                                op_code: OpCode::LOP_FORNLOOP,
+                                ..
                             })
-                            .exactly_one()
-                            .unwrap();
-                        edges.push((loop_node, BlockEdge::new(BranchType::Unconditional)));
+                        ) {
+                            return Err(format!(
+                                "FORNPREP at {} doesn't jump past a FORNLOOP",
+                                block_start + index
+                            ));
+                        }
+                        edges.push((
+                            self.block_to_node(loop_index),
+                            BlockEdge::new(BranchType::Unconditional),
+                        ));
                     }
                     OpCode::LOP_FORNLOOP => {
                         let limit = self.register(a as _);
@@ -1141,13 +1156,26 @@ impl<'a> Lifter<'a> {
                             BlockEdge::new(BranchType::Else),
                         ));
                     }
+                    // FORGPREP_NEXT and FORGPREP_INEXT are emitted for `pairs(...)`, `ipairs(...)`
+                    // and `next, t`, the call is still in the bytecode. the vm only takes its fast
+                    // path if the generator is the builtin and errors if it isn't a function,
+                    // so these loops always call their generator.
+                    // FORGPREP runs generalized iteration on anything that isn't a function,
+                    // ex. `for k, v in t do`
                     OpCode::LOP_FORGPREP
                     | OpCode::LOP_FORGPREP_INEXT
                     | OpCode::LOP_FORGPREP_NEXT => {
                         let generator = self.register(a as _);
                         let state = self.register((a + 1) as _);
                         let counter = self.register((a + 2) as _);
-                        statements.push(ast::GenericForInit::new(generator, state, counter).into());
+                        statements.push(
+                            if op_code == OpCode::LOP_FORGPREP {
+                                ast::GenericForInit::generalized(generator, state, counter)
+                            } else {
+                                ast::GenericForInit::new(generator, state, counter)
+                            }
+                            .into(),
+                        );
                         let loop_index = ((block_start + index + 1) as isize + d as isize) as usize;
                         assert!(matches!(
                             self.function_list[self.function.id].instructions[loop_index],
@@ -1161,10 +1189,9 @@ impl<'a> Lifter<'a> {
                             BlockEdge::new(BranchType::Unconditional),
                         ));
                     }
-                    // TODO: i think vm can assume generator is next/inext based on aux,
-                    // so what happens if the generator passed isnt next and the env isnt tainted?
+                    // TODO: the same applies to fastcall, the vm assumes the builtin but what
+                    // happens if the function passed isnt it and the env isnt tainted?
                     // this could be done with some custom bytecode
-                    // same applies to fastcall
                     OpCode::LOP_FORGLOOP => {
                         let generator = self.register(a as _);
                         let state = self.register((a + 1) as _);
@@ -1299,11 +1326,14 @@ impl<'a> Lifter<'a> {
             }
         }
 
-        (statements, edges)
+        Ok((statements, edges))
     }
 
     fn register(&mut self, index: usize) -> ast::RcLocal {
-        self.register_map.entry(index).or_default().clone()
+        self.register_map
+            .entry(index)
+            .or_insert_with(|| ast::RcLocal::new(ast::Local::register(index)))
+            .clone()
     }
 
     fn constant(&mut self, index: usize) -> ast::Literal {
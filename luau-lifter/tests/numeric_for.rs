@@ -0,0 +1,265 @@
+use driver::DecompileOptions;
+
+const LOADN: u32 = 4;
+const MOVE: u32 = 6;
+const GETIMPORT: u32 = 12;
+const CALL: u32 = 21;
+const RETURN: u32 = 22;
+const JUMPIF: u32 = 25;
+const JUMPIFNOT: u32 = 26;
+const FORNPREP: u32 = 56;
+const FORNLOOP: u32 = 57;
+
+// just enough of the luau format to build a chunk with a single function that reads globals.
+// jumps are given as the index of their target in `code`, every instruction is one word
+// except GETIMPORT which has an aux word
+enum Instruction {
+    Abc(u32, u32, u32, u32),
+    Ad(u32, u32, i32),
+    Jump(u32, u32, usize),
+    Import(u32, &'static str),
+}
+
+use Instruction::*;
+
+fn leb128(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn size(instruction: &Instruction) -> usize {
+    match instruction {
+        Import(..) => 2,
+        _ => 1,
+    }
+}
+
+fn chunk(code: &[Instruction]) -> Vec<u8> {
+    let mut strings = Vec::<&str>::new();
+    let mut constants = Vec::new();
+    let pcs = code
+        .iter()
+        .scan(0, |pc, instruction| {
+            let start = *pc;
+            *pc += size(instruction);
+            Some(start)
+        })
+        .collect::<Vec<_>>();
+    let mut words = Vec::new();
+    for (index, instruction) in code.iter().enumerate() {
+        let ad = |op: u32, a: u32, d: i32| op | a << 8 | (d as u32 & 0xffff) << 16;
+        match *instruction {
+            Abc(op, a, b, c) => words.push(op | a << 8 | b << 16 | c << 24),
+            Ad(op, a, d) => words.push(ad(op, a, d)),
+            Jump(op, a, target) => {
+                words.push(ad(op, a, pcs[target] as i32 - pcs[index] as i32 - 1))
+            }
+            Import(a, name) => {
+                if !strings.contains(&name) {
+                    strings.push(name);
+                }
+                let string = strings.iter().position(|&s| s == name).unwrap() + 1;
+                // the name of the global and then the import of it
+                constants.push((3u8, string as u32));
+                let id = 1 << 30 | ((constants.len() - 1) as u32) << 20;
+                constants.push((4, id));
+                words.push(ad(GETIMPORT, a, constants.len() as i32 - 1));
+                words.push(id);
+            }
+        }
+    }
+
+    let mut out = vec![5, 0];
+    leb128(&mut out, strings.len());
+    for string in &strings {
+        leb128(&mut out, string.len());
+        out.extend(string.as_bytes());
+    }
+    leb128(&mut out, 1);
+    out.extend([32, 0, 0, 0, 0]);
+    leb128(&mut out, 0);
+    leb128(&mut out, words.len());
+    for word in words {
+        out.extend(word.to_le_bytes());
+    }
+    leb128(&mut out, constants.len());
+    for (tag, value) in constants {
+        out.push(tag);
+        match tag {
+            3 => leb128(&mut out, value as usize),
+            _ => out.extend(value.to_le_bytes()),
+        }
+    }
+    // no children, line defined, debug name, line info and debug info
+    leb128(&mut out, 0);
+    leb128(&mut out, 0);
+    leb128(&mut out, 0);
+    out.extend([0, 0]);
+    // main
+    leb128(&mut out, 0);
+    out
+}
+
+fn decompile(code: &[Instruction]) -> String {
+    luau_lifter::decompile_bytecode(&chunk(code), &DecompileOptions::default())
+        .unwrap()
+        .source
+}
+
+// limit, step and counter of a loop in registers 0 to 2
+fn prelude(start: i32, limit: i32, step: i32) -> [Instruction; 3] {
+    [Ad(LOADN, 0, limit), Ad(LOADN, 1, step), Ad(LOADN, 2, start)]
+}
+
+// `print(i)` with the counter in register 2
+fn print_counter() -> [Instruction; 3] {
+    [Import(3, "print"), Abc(MOVE, 4, 2, 0), Abc(CALL, 3, 2, 1)]
+}
+
+#[test]
+fn simple() {
+    let [limit, step, start] = prelude(1, 10, 1);
+    let [print, argument, call] = print_counter();
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 8),
+        print,
+        argument,
+        call,
+        Jump(FORNLOOP, 0, 4),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    assert_eq!(decompile(&code), "for v1 = 1, 10 do\n\tprint(v1)\nend");
+}
+
+#[test]
+fn negative_step() {
+    let [limit, step, start] = prelude(1, -10, -1);
+    let [print, argument, call] = print_counter();
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 8),
+        print,
+        argument,
+        call,
+        Jump(FORNLOOP, 0, 4),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    assert_eq!(decompile(&code), "for v1 = 1, -10, -1 do\n\tprint(v1)\nend");
+}
+
+#[test]
+fn zero_trip() {
+    // the body is empty, FORNLOOP jumps back to itself
+    let [limit, step, start] = prelude(1, 0, 1);
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 5),
+        Jump(FORNLOOP, 0, 4),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    assert_eq!(decompile(&code), "for _ = 1, 0 do\n\nend");
+}
+
+#[test]
+fn break_and_continue() {
+    // if g then <break|continue> end print(i)
+    let jump_out = |target| {
+        let [limit, step, start] = prelude(1, 10, 1);
+        let [print, argument, call] = print_counter();
+        [
+            limit,
+            step,
+            start,
+            Jump(FORNPREP, 0, 10),
+            Import(3, "g"),
+            Jump(JUMPIF, 3, target),
+            print,
+            argument,
+            call,
+            Jump(FORNLOOP, 0, 4),
+            Abc(RETURN, 0, 1, 0),
+        ]
+    };
+    assert_eq!(
+        decompile(&jump_out(10)),
+        "for v1 = 1, 10 do\n\tif g then\n\t\tbreak\n\tend\n\tprint(v1)\nend"
+    );
+    assert_eq!(
+        decompile(&jump_out(9)),
+        "for v1 = 1, 10 do\n\tif not g then\n\t\tprint(v1)\n\tend\nend"
+    );
+}
+
+#[test]
+fn body_returns() {
+    // for i = 1, 10 do return i end, FORNLOOP is only reached through FORNPREP
+    let [limit, step, start] = prelude(1, 10, 1);
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 6),
+        Abc(RETURN, 2, 2, 0),
+        Jump(FORNLOOP, 0, 4),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    assert_eq!(decompile(&code), "for v1 = 1, 10 do\n\treturn v1\nend");
+}
+
+#[test]
+fn body_returns_conditionally() {
+    // for i = 1, 10 do if g then return i end print(i) end
+    let [limit, step, start] = prelude(1, 10, 1);
+    let [print, argument, call] = print_counter();
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 11),
+        Import(3, "g"),
+        Jump(JUMPIFNOT, 3, 7),
+        Abc(RETURN, 2, 2, 0),
+        print,
+        argument,
+        call,
+        Jump(FORNLOOP, 0, 4),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    assert_eq!(
+        decompile(&code),
+        "for v1 = 1, 10 do\n\tif g then\n\t\treturn v1\n\tend\n\tprint(v1)\nend"
+    );
+}
+
+#[test]
+fn malformed_prep() {
+    // FORNPREP's target isn't after a FORNLOOP
+    let [limit, step, start] = prelude(1, 10, 1);
+    let code = [
+        limit,
+        step,
+        start,
+        Jump(FORNPREP, 0, 5),
+        Abc(MOVE, 3, 2, 0),
+        Abc(RETURN, 0, 1, 0),
+    ];
+    let result = luau_lifter::decompile_bytecode(&chunk(&code), &DecompileOptions::default());
+    assert_eq!(
+        result.err().unwrap(),
+        "FORNPREP at 3 doesn't jump past a FORNLOOP"
+    );
+}
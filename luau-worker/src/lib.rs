@@ -1,7 +1,10 @@
+use std::time::Duration;
+
 use futures_util::StreamExt;
 extern crate console_error_panic_hook;
 
 use base64::prelude::*;
+use driver::{Budget, DecompileOptions, OpcodeMap};
 use luau_lifter::decompile_bytecode;
 use serde::{Deserialize, Serialize};
 use worker::*;
@@ -18,6 +21,27 @@ struct DecompileMessage {
 struct DecompileResponse {
     id: String,
     decompilation: String,
+    // "ok", "degraded" if some functions weren't decompiled cleanly, or "error" if the
+    // bytecode couldn't be decompiled at all, in which case the decompilation is the reason
+    status: &'static str,
+}
+
+// functions that take longer than this are written with gotos instead of using up the request
+const BUDGET: Budget = Budget {
+    time: Some(Duration::from_secs(2)),
+    iterations: None,
+    blocks: Some(5000),
+};
+
+fn decompile(bytecode: &[u8], encode_key: u8) -> (&'static str, String) {
+    let options = DecompileOptions::default()
+        .opcode_map(OpcodeMap::Key(encode_key))
+        .budget(BUDGET);
+    match decompile_bytecode(bytecode, &options) {
+        Ok(result) if result.is_clean() => ("ok", result.source),
+        Ok(result) => ("degraded", result.source),
+        Err(err) => ("error", err),
+    }
 }
 
 #[event(fetch, respond_with_errors)]
@@ -53,9 +77,11 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
                         let bytecode = BASE64_STANDARD
                             .decode(msg.encoded_bytecode)
                             .expect("bytecode must be base64 encoded");
+                        let (status, decompilation) = decompile(&bytecode, 1);
                         let resp = DecompileResponse {
                             id: msg.id,
-                            decompilation: decompile_bytecode(&bytecode, 1),
+                            decompilation,
+                            status,
                         };
                         server
                             .send_with_str(serde_json::to_string(&resp).unwrap())
@@ -79,7 +105,12 @@ pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Respo
 
             let encoded_bytecode = req.bytes().await?;
             match BASE64_STANDARD.decode(encoded_bytecode) {
-                Ok(bytecode) => Response::ok(decompile_bytecode(&bytecode, 203)),
+                Ok(bytecode) => {
+                    let (status, decompilation) = decompile(&bytecode, 203);
+                    let mut response = Response::ok(decompilation)?;
+                    response.headers_mut().set("X-Decompile-Status", status)?;
+                    Ok(response)
+                }
                 Err(_) => Response::error("invalid bytecode", 400),
             }
         })
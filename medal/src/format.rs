@@ -0,0 +1,112 @@
+use std::fmt;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Lua51,
+    #[clap(name = "luajit")]
+    LuaJit,
+    Luau,
+}
+
+// an unsigned LEB128 number, returns the rest of `bytes` after it
+fn leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut value = 0;
+    for (i, &byte) in bytes.iter().enumerate().take(5) {
+        value |= ((byte & 0x7F) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+// luau bytecode has no signature, so the start of the chunk has to make sense: the version,
+// the types version and the length of the string table and its first string. version 0
+// means the chunk is a compile error message.
+fn is_luau(bytecode: &[u8]) -> bool {
+    let rest = match bytecode {
+        [0, message @ ..] => {
+            return std::str::from_utf8(message).is_ok_and(|message| {
+                !message.is_empty()
+                    && message
+                        .chars()
+                        .all(|c| !c.is_control() || c == '\n' || c == '\t')
+            })
+        }
+        [4..=6, 0..=3, rest @ ..] => rest,
+        _ => return false,
+    };
+    let Some((strings, rest)) = leb128(rest) else {
+        return false;
+    };
+    // every string takes at least a byte for its length
+    if strings > rest.len() {
+        return false;
+    }
+    strings == 0 || leb128(rest).is_some_and(|(length, rest)| length <= rest.len())
+}
+
+impl Format {
+    // lua and luajit chunks start with a signature, luau bytecode with its version
+    pub fn sniff(bytecode: &[u8]) -> Option<Self> {
+        match bytecode {
+            [0x1B, b'L', b'u', b'a', 0x51, ..] => Some(Self::Lua51),
+            [0x1B, b'L', b'J', ..] => Some(Self::LuaJit),
+            _ if is_luau(bytecode) => Some(Self::Luau),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lua51 => write!(f, "Lua 5.1"),
+            Self::LuaJit => write!(f, "LuaJIT"),
+            Self::Luau => write!(f, "Luau"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+
+    #[test]
+    fn sniff() {
+        assert_eq!(Format::sniff(b"\x1bLua\x51\0"), Some(Format::Lua51));
+        assert_eq!(Format::sniff(b"\x1bLJ\x02"), Some(Format::LuaJit));
+        // version 6, types version 3, 2 strings, "print" and "x"
+        assert_eq!(
+            Format::sniff(b"\x06\x03\x02\x05print\x01x\0"),
+            Some(Format::Luau)
+        );
+        assert_eq!(
+            Format::sniff(b"\0:1: Expected identifier"),
+            Some(Format::Luau)
+        );
+    }
+
+    #[test]
+    fn sniff_unrelated() {
+        for bytes in [
+            &b""[..],
+            b"\0",
+            // binary data after a zero byte
+            b"\0\x01\x02\xff",
+            // types version out of range
+            b"\x05\x09\x01\x01a",
+            // more strings than bytes left
+            b"\x05\x00\x7f\x01a",
+            // first string longer than the file
+            b"\x04\x00\x01\x40abc",
+            // unterminated count
+            b"\x04\x00\xff\xff",
+            b"local x = 1",
+        ] {
+            assert_eq!(Format::sniff(bytes), None, "{:?}", bytes);
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    panic,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser},
+    Parser, Subcommand,
+};
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+use cfg::function::Function;
+use driver::{
+    dump::Dump, Budget, DecompileOptions, Dialect, Frontend, FunctionStatus, IndentationMode,
+    Naming, OpcodeMap, TableLayout,
+};
+use lua51_deserializer::Encoding;
+use lua51_lifter::Lua51Frontend;
+use luajit_lifter::LuaJitFrontend;
+use luau_lifter::LuauFrontend;
+
+use format::Format;
+
+mod format;
+
+#[derive(Parser, Debug)]
+#[clap(about, version, author)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+    #[clap(flatten)]
+    options: Options,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Decompile bytecode to source
+    Decompile {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print a listing of the bytecode
+    Disasm {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Write the control flow graph of every function in DOT format
+    Cfg {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print statistics about the lifted functions
+    Stats {
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+impl Command {
+    fn paths(&self) -> &[PathBuf] {
+        match self {
+            Self::Decompile { paths }
+            | Self::Disasm { paths }
+            | Self::Cfg { paths }
+            | Self::Stats { paths } => paths,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Decompile { .. } => "dec.lua",
+            Self::Disasm { .. } => "dis.txt",
+            Self::Cfg { .. } => "dot",
+            Self::Stats { .. } => "stats.txt",
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct Options {
+    /// Input format, detected from the file header if not specified
+    #[clap(short, long, value_enum, global = true)]
+    format: Option<Format>,
+    /// op = op * key % 256
+    /// For Roblox client bytecode, use 203
+    #[clap(short, long, default_value_t = 1, global = true)]
+    key: u8,
+    /// File describing a custom Lua 5.1 operation code and operand layout
+    #[clap(short, long, global = true)]
+    encoding: Option<PathBuf>,
+    /// Number of threads to process files and the functions in them on (0 = automatic)
+    #[clap(short, long, default_value_t = 0, global = true)]
+    threads: usize,
+    /// Output file, or directory if there are multiple inputs. Without it, the output of every
+    /// input is written next to it with the extension of the command
+    #[clap(short, long, global = true)]
+    output: Option<PathBuf>,
+    /// Write to stdout instead of a file
+    #[clap(long, global = true, conflicts_with = "output")]
+    stdout: bool,
+    /// Process every bytecode file in directories, mirroring them in the output directory,
+    /// or writing next to every file if there is no output directory
+    #[clap(short, long, global = true)]
+    recursive: bool,
+    /// Write a line per file with its path, status (ok, degraded or failed), failed functions,
+    /// gotos and time taken in milliseconds
+    #[clap(long, global = true)]
+    summary: Option<PathBuf>,
+    /// Print the status of every file
+    #[clap(short, long, global = true)]
+    verbose: bool,
+    /// Passes to skip while decompiling, useful for finding the pass responsible for bad output
+    #[clap(
+        long,
+        global = true,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(driver::pass::PASSES.iter().map(|pass| pass.name()))
+    )]
+    disable_pass: Vec<String>,
+    /// Stages to write the control flow graph at, as DOT and as a listing
+    #[clap(
+        long,
+        global = true,
+        value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(driver::dump::stages())
+    )]
+    dump_ir: Vec<String>,
+    /// Directory to write IR dumps to, in a subdirectory per input file
+    #[clap(long, global = true, default_value = "dump")]
+    dump_dir: PathBuf,
+    /// Functions to dump, by index in the order they were lifted (0 is main, default: all)
+    #[clap(long, global = true, value_delimiter = ',')]
+    dump_function: Vec<usize>,
+    /// Indent with a tab, or with the given number of spaces
+    #[clap(long, global = true, default_value = "tab", value_parser = parse_indentation)]
+    indentation: IndentationMode,
+    /// Write tables with more entries than this over multiple lines
+    #[clap(long, global = true, default_value_t = TableLayout::default().max_inline_entries)]
+    table_entries: usize,
+    /// Write tables that would be longer than this on one line over multiple lines
+    #[clap(long, global = true)]
+    table_width: Option<usize>,
+    /// Keep tables with keys on one line if they're small enough, instead of an entry per line
+    #[clap(long, global = true)]
+    inline_keyed_tables: bool,
+    /// Array entries on each line of tables written over multiple lines
+    #[clap(long, global = true, default_value_t = TableLayout::default().entries_per_line)]
+    table_entries_per_line: usize,
+    /// Write a comma after the last entry of tables written over multiple lines
+    #[clap(long, global = true)]
+    trailing_commas: bool,
+    /// Wrap argument lists, operator chains and method chains that would make a line longer
+    /// than this
+    #[clap(long, global = true)]
+    line_width: Option<usize>,
+    /// How locals are named: generated, kept from debug info, or stable across small changes
+    #[clap(
+        long,
+        global = true,
+        default_value = "generated",
+        value_parser = PossibleValuesParser::new(Naming::NAMES).map(|name| name.parse::<Naming>().unwrap())
+    )]
+    naming: Naming,
+    /// Write functions that are only ever called as methods with method syntax, renaming their
+    /// first parameter to self
+    #[clap(long, global = true)]
+    infer_methods: bool,
+    /// Comment written at the top of decompiled files
+    #[clap(long, global = true)]
+    header: Option<String>,
+    /// Language the output is meant for, defaults to the input format
+    #[clap(
+        long,
+        global = true,
+        value_parser = PossibleValuesParser::new(Dialect::NAMES).map(|name| name.parse::<Dialect>().unwrap())
+    )]
+    dialect: Option<Dialect>,
+    /// Milliseconds to spend structuring a function before writing the rest of it with gotos
+    #[clap(long, global = true)]
+    time_budget: Option<u64>,
+    /// Structuring iterations per function before writing the rest of it with gotos
+    #[clap(long, global = true)]
+    iteration_budget: Option<usize>,
+    /// Write functions with more blocks than this with gotos instead of structuring them
+    #[clap(long, global = true)]
+    block_budget: Option<usize>,
+}
+
+fn parse_indentation(value: &str) -> Result<IndentationMode, String> {
+    if value == "tab" {
+        Ok(IndentationMode::Tab)
+    } else {
+        value
+            .parse()
+            .map(IndentationMode::Spaces)
+            .map_err(|_| "expected \"tab\" or a number of spaces".to_string())
+    }
+}
+
+struct Input {
+    path: PathBuf,
+    // relative to the directory it was found in
+    relative: Option<PathBuf>,
+}
+
+fn collect_inputs(paths: &[PathBuf], recursive: bool) -> anyhow::Result<Vec<Input>> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            inputs.push(Input {
+                path: path.clone(),
+                relative: None,
+            });
+        } else if recursive {
+            for entry in WalkDir::new(path).sort_by_file_name() {
+                let entry = entry?;
+                if entry.file_type().is_file() {
+                    inputs.push(Input {
+                        path: entry.path().to_path_buf(),
+                        relative: Some(entry.path().strip_prefix(path)?.to_path_buf()),
+                    });
+                }
+            }
+        } else {
+            bail!("{} is a directory, use --recursive", path.display());
+        }
+    }
+    Ok(inputs)
+}
+
+struct Outcome {
+    output: String,
+    functions_failed: usize,
+    gotos: usize,
+    // whether the output is as good as it gets
+    clean: bool,
+    // details about functions that weren't decompiled cleanly
+    notes: Vec<String>,
+}
+
+impl Outcome {
+    fn new(output: String) -> Self {
+        Self {
+            output,
+            functions_failed: 0,
+            gotos: 0,
+            clean: true,
+            notes: Vec::new(),
+        }
+    }
+}
+
+struct Runner<'a> {
+    options: &'a Options,
+    // the contents of the encoding file, if any
+    encoding_source: Option<String>,
+    encoding: Encoding,
+    // shared by every input, the operation code map and dump are set per input
+    decompile_options: DecompileOptions,
+}
+
+impl Runner<'_> {
+    fn decompile_options(&self, format: Format, dump: Dump) -> DecompileOptions {
+        let opcode_map = match format {
+            Format::Lua51 => match &self.encoding_source {
+                Some(source) => OpcodeMap::Encoding(source.clone()),
+                None => OpcodeMap::default(),
+            },
+            Format::LuaJit => OpcodeMap::default(),
+            Format::Luau => OpcodeMap::Key(self.options.key),
+        };
+        self.decompile_options
+            .clone()
+            .opcode_map(opcode_map)
+            .dump(dump)
+    }
+
+    fn frontend<'a>(
+        &self,
+        format: Format,
+        bytecode: &'a [u8],
+        options: &'a DecompileOptions,
+    ) -> Box<dyn Frontend + 'a> {
+        match format {
+            Format::Lua51 => Box::new(Lua51Frontend::new(bytecode, options)),
+            Format::LuaJit => Box::new(LuaJitFrontend::new(bytecode, options)),
+            Format::Luau => Box::new(LuauFrontend::new(bytecode, options)),
+        }
+    }
+
+    // the control flow graphs of every function straight out of the lifter, main first
+    fn lift(
+        &self,
+        format: Format,
+        bytecode: &[u8],
+        options: &DecompileOptions,
+    ) -> anyhow::Result<Vec<Function>> {
+        let lifted = self
+            .frontend(format, bytecode, options)
+            .lift()
+            .map_err(|e| anyhow!(e))?;
+        Ok(lifted.into_iter().map(|lifted| lifted.function).collect())
+    }
+
+    // files found while walking a directory that aren't bytecode are skipped
+    fn process(&self, command: &Command, input: &Input) -> anyhow::Result<Option<Outcome>> {
+        let bytecode = fs::read(&input.path).context("failed to read file")?;
+        let format = match self.options.format.or_else(|| Format::sniff(&bytecode)) {
+            Some(format) => format,
+            None if input.relative.is_some() => return Ok(None),
+            None => bail!("unrecognized bytecode format"),
+        };
+        let dump = Dump {
+            directory: self.options.dump_dir.join(
+                input
+                    .relative
+                    .as_deref()
+                    .unwrap_or_else(|| input.path.file_name().unwrap().as_ref()),
+            ),
+            stages: self.options.dump_ir.clone(),
+            functions: self.options.dump_function.clone(),
+        };
+        let options = self.decompile_options(format, dump);
+        self.run(command, format, &bytecode, &options).map(Some)
+    }
+
+    fn run(
+        &self,
+        command: &Command,
+        format: Format,
+        bytecode: &[u8],
+        options: &DecompileOptions,
+    ) -> anyhow::Result<Outcome> {
+        match command {
+            Command::Decompile { .. } => {
+                let result = driver::decompile(&*self.frontend(format, bytecode, options), options)
+                    .map_err(|e| anyhow!(e))?;
+                let mut notes = Vec::new();
+                for (index, function) in result.functions.iter().enumerate() {
+                    if function.status != FunctionStatus::Ok {
+                        notes.push(format!("function {}: {}", index, function.status));
+                    }
+                    if function.gotos != 0 {
+                        notes.push(format!("function {}: {} gotos", index, function.gotos));
+                    }
+                    for warning in &function.warnings {
+                        notes.push(format!("function {}: {}", index, warning));
+                    }
+                }
+                notes.push(result.timings.to_string());
+                Ok(Outcome {
+                    functions_failed: result.functions_failed(),
+                    gotos: result.gotos(),
+                    clean: result.is_clean(),
+                    notes,
+                    output: result.source + "\n",
+                })
+            }
+            Command::Disasm { .. } => match format {
+                Format::Lua51 => lua51_lifter::disassemble_bytecode(bytecode, &self.encoding)
+                    .map(Outcome::new)
+                    .map_err(|e| anyhow!(e)),
+                _ => bail!("disassembly is not supported for {} yet", format),
+            },
+            Command::Cfg { .. } => {
+                let mut output = Vec::new();
+                for function in self.lift(format, bytecode, options)? {
+                    cfg::dot::render_to(&function, &mut output)?;
+                }
+                Ok(Outcome::new(String::from_utf8(output)?))
+            }
+            Command::Stats { .. } => {
+                let functions = self.lift(format, bytecode, options)?;
+                let counts = functions
+                    .iter()
+                    .map(|function| {
+                        let graph = function.graph();
+                        (
+                            graph.node_count(),
+                            graph.edge_count(),
+                            graph.node_weights().map(|block| block.len()).sum::<usize>(),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut output = String::new();
+                writeln!(output, "format: {}", format)?;
+                writeln!(output, "functions: {}", functions.len())?;
+                writeln!(
+                    output,
+                    "blocks: {}",
+                    counts.iter().map(|c| c.0).sum::<usize>()
+                )?;
+                writeln!(
+                    output,
+                    "edges: {}",
+                    counts.iter().map(|c| c.1).sum::<usize>()
+                )?;
+                writeln!(
+                    output,
+                    "statements: {}",
+                    counts.iter().map(|c| c.2).sum::<usize>()
+                )?;
+                for (index, (blocks, edges, statements)) in counts.into_iter().enumerate() {
+                    writeln!(
+                        output,
+                        "function {}: {} blocks, {} edges, {} statements",
+                        index, blocks, edges, statements
+                    )?;
+                }
+                Ok(Outcome::new(output))
+            }
+        }
+    }
+
+    fn destination(
+        &self,
+        command: &Command,
+        input: &Input,
+        multiple_inputs: bool,
+    ) -> anyhow::Result<PathBuf> {
+        let path = match (&self.options.output, &input.relative) {
+            (Some(output), Some(relative)) => output.join(relative),
+            (Some(output), None) if multiple_inputs || output.is_dir() => {
+                output.join(input.path.file_name().unwrap())
+            }
+            (Some(output), None) => return Ok(output.clone()),
+            (None, _) => input.path.clone(),
+        }
+        .with_extension(command.extension());
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if args.options.threads != 0 {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(args.options.threads)
+            .build_global()?;
+    }
+    let encoding_source = match &args.options.encoding {
+        Some(path) => Some(fs::read_to_string(path).context("failed to read encoding")?),
+        None => None,
+    };
+    let encoding = match &encoding_source {
+        Some(source) => Encoding::parse(source).map_err(|e| anyhow!(e))?,
+        None => Encoding::default(),
+    };
+    let mut decompile_options = DecompileOptions::default()
+        .indentation(args.options.indentation)
+        .table_layout(TableLayout {
+            max_inline_entries: args.options.table_entries,
+            max_inline_width: args.options.table_width,
+            split_keyed: !args.options.inline_keyed_tables,
+            entries_per_line: args.options.table_entries_per_line,
+            trailing_comma: args.options.trailing_commas,
+        })
+        .naming(args.options.naming)
+        .infer_methods(args.options.infer_methods)
+        .budget(Budget {
+            time: args.options.time_budget.map(Duration::from_millis),
+            iterations: args.options.iteration_budget,
+            blocks: args.options.block_budget,
+        });
+    if let Some(line_width) = args.options.line_width {
+        decompile_options = decompile_options.max_line_width(line_width);
+    }
+    if let Some(header) = &args.options.header {
+        decompile_options = decompile_options.header(header);
+    }
+    if let Some(dialect) = args.options.dialect {
+        decompile_options = decompile_options.dialect(dialect);
+    }
+    for pass in &args.options.disable_pass {
+        decompile_options = decompile_options.disable_pass(pass);
+    }
+    let runner = Runner {
+        options: &args.options,
+        encoding_source,
+        encoding,
+        decompile_options,
+    };
+
+    let inputs = collect_inputs(args.command.paths(), args.options.recursive)?;
+    let start = Instant::now();
+    let (succeeded, failed, skipped, functions_failed) = (
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+        AtomicUsize::new(0),
+    );
+    // outputs are written as soon as they're done so they don't pile up in memory,
+    // only the summary lines are kept, in the order of the inputs
+    let summary = inputs
+        .par_iter()
+        .map(|input| {
+            let start = Instant::now();
+            let result = panic::catch_unwind(|| runner.process(&args.command, input))
+                .unwrap_or_else(|_| Err(anyhow!("panicked")))
+                .and_then(|outcome| {
+                    let Some(outcome) = outcome else {
+                        return Ok(None);
+                    };
+                    // stats are meant to be read, not kept
+                    if args.options.stdout
+                        || (args.options.output.is_none()
+                            && matches!(args.command, Command::Stats { .. }))
+                    {
+                        io::stdout().lock().write_all(outcome.output.as_bytes())?;
+                    } else {
+                        let destination =
+                            runner.destination(&args.command, input, inputs.len() > 1)?;
+                        fs::write(destination, outcome.output)?;
+                    }
+                    Ok(Some((
+                        outcome.functions_failed,
+                        outcome.gotos,
+                        outcome.clean,
+                        outcome.notes,
+                    )))
+                });
+            let time = start.elapsed();
+            let (status, file_functions_failed, gotos, notes) = match result {
+                Ok(None) => {
+                    skipped.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+                Ok(Some((count, gotos, clean, notes))) => {
+                    succeeded.fetch_add(1, Ordering::Relaxed);
+                    functions_failed.fetch_add(count, Ordering::Relaxed);
+                    let status = if clean { "ok" } else { "degraded" };
+                    (status.to_string(), count, gotos, notes)
+                }
+                Err(err) => {
+                    eprintln!("{}: {:#}", input.path.display(), err);
+                    failed.fetch_add(1, Ordering::Relaxed);
+                    let status = format!("failed: {:#}", err).replace(['\t', '\n'], " ");
+                    (status, 0, 0, Vec::new())
+                }
+            };
+            if args.options.verbose {
+                let mut message = format!("{}: {} in {:?}\n", input.path.display(), status, time);
+                for note in notes {
+                    writeln!(message, "    {}", note).unwrap();
+                }
+                io::stderr().lock().write_all(message.as_bytes()).unwrap();
+            }
+            Some(format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                input.path.display(),
+                status,
+                file_functions_failed,
+                gotos,
+                time.as_millis()
+            ))
+        })
+        .flatten()
+        .collect::<String>();
+    let (succeeded, failed, skipped, functions_failed) = (
+        succeeded.into_inner(),
+        failed.into_inner(),
+        skipped.into_inner(),
+        functions_failed.into_inner(),
+    );
+
+    if let Some(path) = &args.options.summary {
+        fs::write(path, summary)?;
+    }
+    if inputs.len() > 1 || args.options.verbose {
+        eprintln!(
+            "{} ok, {} failed, {} skipped, {} functions failed in {:?}",
+            succeeded,
+            failed,
+            skipped,
+            functions_failed,
+            start.elapsed()
+        );
+    }
+    if failed != 0 {
+        bail!("{} of {} files failed", failed, succeeded + failed);
+    }
+    Ok(())
+}
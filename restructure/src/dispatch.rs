@@ -0,0 +1,446 @@
+use ast::LocalRw;
+use cfg::block::BranchType;
+use itertools::Itertools;
+use petgraph::{
+    algo::tarjan_scc,
+    stable_graph::NodeIndex,
+    visit::{Dfs, EdgeRef},
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::GraphStructurer;
+
+fn number(value: usize) -> ast::RValue {
+    ast::Literal::Number(value as f64).into()
+}
+
+fn set_state(state: &ast::RcLocal, value: usize) -> ast::Statement {
+    ast::Assign::new(vec![state.clone().into()], vec![number(value)]).into()
+}
+
+// every local read or written in a statement and the blocks nested in it
+fn statement_locals_used(statement: &ast::Statement, locals: &mut FxHashSet<ast::RcLocal>) {
+    locals.extend(statement.values_read().into_iter().cloned());
+    locals.extend(statement.values_written().into_iter().cloned());
+    match statement {
+        ast::Statement::If(r#if) => {
+            locals_used(&r#if.then_block.lock(), locals);
+            locals_used(&r#if.else_block.lock(), locals);
+        }
+        ast::Statement::While(r#while) => locals_used(&r#while.block.lock(), locals),
+        ast::Statement::Repeat(repeat) => locals_used(&repeat.block.lock(), locals),
+        ast::Statement::NumericFor(numeric_for) => locals_used(&numeric_for.block.lock(), locals),
+        ast::Statement::GenericFor(generic_for) => locals_used(&generic_for.block.lock(), locals),
+        _ => {}
+    }
+}
+
+fn locals_used(block: &ast::Block, locals: &mut FxHashSet<ast::RcLocal>) {
+    for statement in block.iter() {
+        statement_locals_used(statement, locals);
+    }
+}
+
+// whether `local` is assigned in `block` before anything else uses it, so the value it had
+// the last time the block ran isn't needed
+fn written_before_use(block: &ast::Block, local: &ast::RcLocal) -> bool {
+    for statement in block.iter() {
+        if let ast::Statement::Assign(assign) = statement
+            && assign
+                .left
+                .iter()
+                .any(|lvalue| lvalue.as_local() == Some(local))
+        {
+            return !assign.values_read().contains(&local);
+        }
+        let mut locals = FxHashSet::default();
+        statement_locals_used(statement, &mut locals);
+        if locals.contains(local) {
+            return false;
+        }
+    }
+    true
+}
+
+fn binary(left: ast::RValue, right: ast::RValue, operation: ast::BinaryOperation) -> ast::RValue {
+    ast::Binary::new(left, right, operation).into()
+}
+
+// the for loop statements of the cfg in plain lua. numeric loops start one step early
+// so that every iteration, including the first, steps and then checks the limit
+impl GraphStructurer {
+    fn lower_num_for_init(num_for_init: ast::NumForInit, block: &mut ast::Block) {
+        let ast::NumForInit {
+            counter,
+            limit,
+            step,
+        } = num_for_init;
+        let counter_local = counter.0.as_local().unwrap().clone();
+        let step_local = step.0.as_local().unwrap().clone();
+        block.push(
+            ast::Assign::new(
+                vec![counter.0, limit.0, step.0],
+                vec![counter.1, limit.1, step.1],
+            )
+            .into(),
+        );
+        block.push(
+            ast::Assign::new(
+                vec![counter_local.clone().into()],
+                vec![binary(
+                    counter_local.into(),
+                    step_local.into(),
+                    ast::BinaryOperation::Sub,
+                )],
+            )
+            .into(),
+        );
+    }
+
+    // the condition of the loop, the counter is stepped before it's checked
+    fn lower_num_for_next(num_for_next: ast::NumForNext, block: &mut ast::Block) -> ast::RValue {
+        let ast::NumForNext {
+            counter,
+            limit,
+            step,
+        } = num_for_next;
+        block.push(
+            ast::Assign::new(
+                vec![counter.0],
+                vec![binary(
+                    counter.1.clone(),
+                    step.clone(),
+                    ast::BinaryOperation::Add,
+                )],
+            )
+            .into(),
+        );
+        let counter = counter.1;
+        let ascending = binary(
+            counter.clone(),
+            limit.clone(),
+            ast::BinaryOperation::LessThanOrEqual,
+        );
+        let descending = binary(counter, limit, ast::BinaryOperation::GreaterThanOrEqual);
+        match step {
+            ast::RValue::Literal(ast::Literal::Number(step)) if step > 0.0 => ascending,
+            ast::RValue::Literal(ast::Literal::Number(_)) => descending,
+            step => binary(
+                binary(
+                    binary(step.clone(), number(0), ast::BinaryOperation::GreaterThan),
+                    ascending,
+                    ast::BinaryOperation::And,
+                ),
+                binary(
+                    binary(step, number(0), ast::BinaryOperation::LessThanOrEqual),
+                    descending,
+                    ast::BinaryOperation::And,
+                ),
+                ast::BinaryOperation::Or,
+            ),
+        }
+    }
+
+    // generalized iteration can't be written as a call, so what the vm does for a generator
+    // that isn't a function is spelled out: `__iter` is called for the generator, state and
+    // control, a value with `__call` is called like a function and anything else is iterated
+    // with `next`. a metatable hidden by `__metatable` isn't seen
+    fn lower_generic_for_init(generic_for_init: ast::GenericForInit, block: &mut ast::Block) {
+        let ast::GenericForInit(assign, generalized) = generic_for_init;
+        let left = assign.left.clone();
+        block.push(assign.into());
+        if generalized {
+            let generator = left[0].as_local().unwrap().clone();
+            let metatable = ast::RcLocal::default();
+            let metamethod = |name: &str| -> ast::RValue {
+                ast::Index::new(
+                    metatable.clone().into(),
+                    ast::Literal::String(name.as_bytes().to_vec()).into(),
+                )
+                .into()
+            };
+            let has_metamethod = |name: &str| {
+                binary(
+                    metatable.clone().into(),
+                    metamethod(name),
+                    ast::BinaryOperation::And,
+                )
+            };
+            let call_iter = ast::Assign::new(
+                left.clone(),
+                vec![ast::Call::new(metamethod("__iter"), vec![generator.clone().into()]).into()],
+            );
+            let use_next = ast::Assign::new(
+                left,
+                vec![
+                    ast::Global::new(b"next".to_vec()).into(),
+                    generator.clone().into(),
+                    ast::Literal::Nil.into(),
+                ],
+            );
+            let not_function = binary(
+                ast::Call::new(
+                    ast::Global::new(b"type".to_vec()).into(),
+                    vec![generator.clone().into()],
+                )
+                .into(),
+                ast::Literal::String(b"function".to_vec()).into(),
+                ast::BinaryOperation::NotEqual,
+            );
+            let get_metatable = ast::Assign::new(
+                vec![metatable.clone().into()],
+                vec![ast::Call::new(
+                    ast::Global::new(b"getmetatable".to_vec()).into(),
+                    vec![generator.into()],
+                )
+                .into()],
+            );
+            let not_callable = ast::Unary::new(has_metamethod("__call"), ast::UnaryOperation::Not);
+            block.push(
+                ast::If::new(
+                    not_function,
+                    vec![
+                        get_metatable.into(),
+                        ast::If::new(
+                            has_metamethod("__iter"),
+                            vec![call_iter.into()].into(),
+                            vec![ast::If::new(
+                                not_callable.into(),
+                                vec![use_next.into()].into(),
+                                ast::Block::default(),
+                            )
+                            .into()]
+                            .into(),
+                        )
+                        .into(),
+                    ]
+                    .into(),
+                    ast::Block::default(),
+                )
+                .into(),
+            );
+        }
+    }
+
+    // the condition of the loop and the statement that carries the control variable over
+    // to the next call, which is only run when the loop continues
+    fn lower_generic_for_next(
+        generic_for_next: ast::GenericForNext,
+        control: ast::RcLocal,
+        block: &mut ast::Block,
+    ) -> (ast::RValue, ast::Statement) {
+        let ast::GenericForNext {
+            res_locals,
+            generator,
+            state,
+        } = generic_for_next;
+        let first = res_locals[0].as_local().unwrap().clone();
+        block.push(
+            ast::Assign::new(
+                res_locals,
+                vec![ast::Call::new(generator, vec![state, control.clone().into()]).into()],
+            )
+            .into(),
+        );
+        (
+            binary(
+                first.clone().into(),
+                ast::Literal::Nil.into(),
+                ast::BinaryOperation::NotEqual,
+            ),
+            ast::Assign::new(vec![control.into()], vec![first.into()]).into(),
+        )
+    }
+
+    // the control variables of the generic for loops initialized in `nodes` by their generator.
+    // generic for loops don't refer to their control variable after they're initialized,
+    // the control variable is found through the generator
+    pub(crate) fn generic_for_controls(
+        &self,
+        nodes: &[NodeIndex],
+    ) -> FxHashMap<ast::RcLocal, ast::RcLocal> {
+        nodes
+            .iter()
+            .flat_map(|&node| self.function.block(node).unwrap().iter())
+            .filter_map(|statement| statement.as_generic_for_init())
+            .filter_map(|init| {
+                Some((
+                    init.0.left[0].as_local()?.clone(),
+                    init.0.left[2].as_local()?.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    // lowers the for loop statements of a block that isn't part of a structured loop. if the
+    // block ends a loop iteration, the condition of the loop and the statement to run when it
+    // continues are returned as well
+    pub(crate) fn lower_for_statements(
+        statements: ast::Block,
+        controls: &FxHashMap<ast::RcLocal, ast::RcLocal>,
+    ) -> (ast::Block, Option<(ast::RValue, Option<ast::Statement>)>) {
+        let mut block = ast::Block::default();
+        let mut next_statement = None;
+        for statement in statements.0 {
+            match statement {
+                ast::Statement::NumForInit(num_for_init) => {
+                    Self::lower_num_for_init(num_for_init, &mut block)
+                }
+                ast::Statement::GenericForInit(generic_for_init) => {
+                    Self::lower_generic_for_init(generic_for_init, &mut block)
+                }
+                ast::Statement::NumForNext(num_for_next) => {
+                    let condition = Self::lower_num_for_next(num_for_next, &mut block);
+                    next_statement = Some((condition, None));
+                }
+                ast::Statement::GenericForNext(generic_for_next) => {
+                    let control = generic_for_next
+                        .generator
+                        .as_local()
+                        .and_then(|generator| controls.get(generator))
+                        .cloned()
+                        .unwrap_or_default();
+                    let (condition, carry) =
+                        Self::lower_generic_for_next(generic_for_next, control, &mut block);
+                    next_statement = Some((condition, Some(carry)));
+                }
+                statement => block.push(statement),
+            }
+        }
+        (block, next_statement)
+    }
+
+    // replaces the rest of the graph with a loop that runs one block after another, a state
+    // variable holds the number of the block to run next. this can express any control flow
+    // without gotos, it's used when the graph couldn't be structured otherwise.
+    // returns the number of blocks in the loop
+    pub(crate) fn dispatch(&mut self) -> usize {
+        let entry = self.function.entry().unwrap();
+        // unreachable blocks are left out
+        let mut nodes = Vec::new();
+        let mut dfs = Dfs::new(self.function.graph(), entry);
+        while let Some(node) = dfs.next(self.function.graph()) {
+            nodes.push(node);
+        }
+        let numbers = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, &node)| (node, index + 1))
+            .collect::<FxHashMap<_, _>>();
+
+        let controls = self.generic_for_controls(&nodes);
+
+        // blocks that can run again after they ran, a local that only one of them uses
+        // might still carry a value from one run to the next
+        let repeated = tarjan_scc(self.function.graph())
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1
+                    || self
+                        .function
+                        .graph()
+                        .contains_edge(component[0], component[0])
+            })
+            .flatten()
+            .collect::<FxHashSet<_>>();
+
+        let state = ast::RcLocal::default();
+        let mut body = ast::Block::default();
+        // the number of blocks every local is used in
+        let mut uses = FxHashMap::default();
+        let mut carried = FxHashSet::default();
+        for &node in &nodes {
+            let targets = match self.function.conditional_edges(node) {
+                Some((then_edge, else_edge)) => {
+                    vec![numbers[&then_edge.target()], numbers[&else_edge.target()]]
+                }
+                None => self
+                    .function
+                    .edges(node)
+                    .inspect(|e| assert_eq!(e.weight().branch_type, BranchType::Unconditional))
+                    .map(|e| numbers[&e.target()])
+                    .collect_vec(),
+            };
+            let statements = std::mem::take(self.function.block_mut(node).unwrap());
+            let (mut block, next_statement) = Self::lower_for_statements(statements, &controls);
+            match targets[..] {
+                [] => {
+                    if !matches!(block.last(), Some(ast::Statement::Return(_))) {
+                        block.push(ast::Break {}.into());
+                    }
+                }
+                [target] => block.push(set_state(&state, target)),
+                [then_target, else_target] => {
+                    let (condition, carry) = match next_statement {
+                        Some(next_statement) => next_statement,
+                        None => (block.pop().unwrap().into_if().unwrap().condition, None),
+                    };
+                    let then_block = carry
+                        .into_iter()
+                        .chain(std::iter::once(set_state(&state, then_target)))
+                        .collect_vec();
+                    block.push(
+                        ast::If::new(
+                            condition,
+                            then_block.into(),
+                            vec![set_state(&state, else_target)].into(),
+                        )
+                        .into(),
+                    );
+                }
+                _ => unreachable!(),
+            }
+            let mut locals = FxHashSet::default();
+            locals_used(&block, &mut locals);
+            for local in locals {
+                if repeated.contains(&node) && !written_before_use(&block, &local) {
+                    carried.insert(local.clone());
+                }
+                *uses.entry(local).or_insert(0) += 1;
+            }
+            // blocks are checked in order, so a block can run right after the one before
+            // it in the same iteration
+            body.push(
+                ast::If::new(
+                    binary(
+                        state.clone().into(),
+                        number(numbers[&node]),
+                        ast::BinaryOperation::Equal,
+                    ),
+                    block,
+                    ast::Block::default(),
+                )
+                .into(),
+            );
+        }
+
+        for node in self.function.graph().node_indices().collect_vec() {
+            self.function.remove_block(node);
+        }
+        let new_entry = self.function.new_block();
+        self.function.set_entry(new_entry);
+        let block = self.function.block_mut(new_entry).unwrap();
+        // locals are declared where they're first written, which doesn't work for locals
+        // that are passed from one block to another, or from one run of a block to the next,
+        // since they're written in the loop
+        let mut shared = uses
+            .into_iter()
+            .filter(|(local, uses)| {
+                (*uses > 1 || carried.contains(local))
+                    && *local != state
+                    && !self.outer_locals.contains(local)
+            })
+            .map(|(local, _)| local)
+            .collect_vec();
+        if !shared.is_empty() {
+            shared.sort();
+            let mut declaration =
+                ast::Assign::new(shared.into_iter().map(Into::into).collect(), Vec::new());
+            declaration.prefix = true;
+            block.push(declaration.into());
+        }
+        block.push(set_state(&state, 1));
+        block.push(ast::While::new(ast::Literal::Boolean(true).into(), body).into());
+        nodes.len()
+    }
+}
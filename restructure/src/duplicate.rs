@@ -0,0 +1,116 @@
+use ast::Traverse;
+use itertools::Itertools;
+use petgraph::{
+    algo::dominators::simple_fast,
+    stable_graph::{EdgeIndex, NodeIndex},
+    visit::{EdgeRef, IntoEdgeReferences},
+};
+
+use crate::GraphStructurer;
+
+// blocks with more statements than this, nested ones included, aren't duplicated
+const MAX_STATEMENTS: usize = 8;
+
+// a deep copy of a block, nested blocks would otherwise be shared by both copies.
+// `budget` is the number of statements that can still be copied. loops aren't copied,
+// and neither are breaks and continues since the copy might not be in the same loop.
+// closures would be linked twice, see `driver::link_upvalues`
+pub(crate) fn copy_block(block: &ast::Block, budget: &mut usize) -> Option<ast::Block> {
+    let mut copy = ast::Block::default();
+    for statement in block.iter() {
+        *budget = budget.checked_sub(1)?;
+        let mut statement = match statement {
+            ast::Statement::If(r#if) => {
+                let then_block = copy_block(&r#if.then_block.lock(), budget)?;
+                let else_block = copy_block(&r#if.else_block.lock(), budget)?;
+                ast::If::new(r#if.condition.clone(), then_block, else_block).into()
+            }
+            ast::Statement::Call(_)
+            | ast::Statement::MethodCall(_)
+            | ast::Statement::Assign(_)
+            | ast::Statement::Return(_)
+            | ast::Statement::Comment(_)
+            | ast::Statement::Empty(_) => statement.clone(),
+            _ => return None,
+        };
+        let mut has_closure = false;
+        statement.traverse_rvalues(&mut |rvalue| {
+            has_closure |= matches!(rvalue, ast::RValue::Closure(_));
+        });
+        if has_closure {
+            return None;
+        }
+        copy.push(statement);
+    }
+    Some(copy)
+}
+
+impl GraphStructurer {
+    // a copy of the block of `node` if it's small enough and can be copied
+    fn duplicate_block(&self, node: NodeIndex) -> Option<ast::Block> {
+        if Some(node) == *self.function.entry()
+            || self.is_loop_header(node)
+            || self.is_for_next(node)
+        {
+            return None;
+        }
+        let mut budget = MAX_STATEMENTS;
+        copy_block(self.function.block(node).unwrap(), &mut budget)
+    }
+
+    // gives the source of `edge` its own copy of the target
+    fn duplicate_for_edge(&mut self, edge: EdgeIndex, block: ast::Block) {
+        let (source, target) = self.function.graph().edge_endpoints(edge).unwrap();
+        let copy = self.function.new_block();
+        *self.function.block_mut(copy).unwrap() = block;
+        let edges = self
+            .function
+            .edges(target)
+            .map(|e| (e.target(), e.weight().clone()))
+            .collect_vec();
+        self.function.set_edges(copy, edges);
+        let edge = self.function.graph_mut().remove_edge(edge).unwrap();
+        self.function.graph_mut().add_edge(source, copy, edge);
+    }
+
+    // duplicates a small block with more than one predecessor, so that the graph can be
+    // structured without a goto. edges that would need a goto are preferred, like in
+    // `collapse`. returns whether a block was duplicated
+    pub(crate) fn try_duplicate(&mut self) -> bool {
+        if self.duplications_left == 0 {
+            return false;
+        }
+        let dominators = simple_fast(self.function.graph(), self.function.entry().unwrap());
+        let edges = self
+            .function
+            .graph()
+            .edge_references()
+            .filter(|e| e.source() != e.target())
+            .filter(|e| {
+                self.function
+                    .predecessor_blocks(e.target())
+                    .unique()
+                    .count()
+                    > 1
+            })
+            .sorted_by_key(|e| {
+                let dominates = |a, b| {
+                    dominators
+                        .dominators(b)
+                        .is_some_and(|mut dominators| dominators.contains(&a))
+                };
+                dominates(e.source(), e.target()) || dominates(e.target(), e.source())
+            })
+            .map(|e| e.id())
+            .collect_vec();
+        for edge in edges {
+            let (_, target) = self.function.graph().edge_endpoints(edge).unwrap();
+            if let Some(block) = self.duplicate_block(target) {
+                self.duplicate_for_edge(edge, block);
+                self.duplications_left -= 1;
+                return true;
+            }
+        }
+        false
+    }
+}
@@ -12,8 +12,11 @@ use petgraph::{
 use tuple::Map;
 
 mod conditional;
+mod dispatch;
+mod duplicate;
 mod jump;
 mod r#loop;
+mod split;
 
 // TODO: REFACTOR: move
 pub fn post_dominators<N: Default, E: Default>(
@@ -32,10 +35,23 @@ pub fn post_dominators<N: Default, E: Default>(
     res
 }
 
+// small functions can still be split a bit more than their size
+const MIN_SPLIT_BUDGET: usize = 16;
+
 struct GraphStructurer {
     pub function: Function,
     loop_headers: FxHashSet<NodeIndex>,
     label_to_node: FxHashMap<ast::Label, NodeIndex>,
+    // whether gotos can be used for edges that can't be structured. if they can't,
+    // small blocks are duplicated and what's left is written as a state machine
+    allow_gotos: bool,
+    // parameters and upvalues, they're declared outside of the function body
+    outer_locals: FxHashSet<ast::RcLocal>,
+    duplications_left: usize,
+    // the number of statements that can still be copied to make loops reducible
+    split_budget: usize,
+    // blocks in the state machine
+    dispatched: usize,
 }
 
 impl GraphStructurer {
@@ -51,11 +67,23 @@ impl GraphStructurer {
             },
         );
     }
-    fn new(function: Function) -> Self {
+    fn new(function: Function, outer_locals: FxHashSet<ast::RcLocal>, allow_gotos: bool) -> Self {
+        let duplications_left = function.graph().node_count();
+        // the function can at most double in size
+        let split_budget = function
+            .blocks()
+            .map(|(_, block)| block.len() + 1)
+            .sum::<usize>()
+            .max(MIN_SPLIT_BUDGET);
         let mut this = Self {
             function,
             loop_headers: FxHashSet::default(),
             label_to_node: FxHashMap::default(),
+            allow_gotos,
+            outer_locals,
+            duplications_left,
+            split_budget,
+            dispatched: 0,
         };
         this.find_loop_headers();
         this
@@ -121,6 +149,10 @@ impl GraphStructurer {
 
         let mut changed = false;
         while let Some(node) = dfs_postorder.next(self.function.graph()) {
+            // the traversal can still hold blocks that a previous match removed
+            if !self.function.has_block(node) {
+                continue;
+            }
             // println!("matching {:?}", node);
             let matched = self.try_match_pattern(node, &dominators, &post_dom);
             if matched {
@@ -176,13 +208,7 @@ impl GraphStructurer {
             self.function.block_mut(source).unwrap().extend(block.0);
             self.function.set_edges(source, edges);
         } else {
-            // TODO: make label an Rc and have a global counter for block name
-            let label = ast::Label(format!("l{}", target.index()));
-            let target_block = self.function.block_mut(target).unwrap();
-            if target_block.first().and_then(|s| s.as_label()).is_none() {
-                self.label_to_node.insert(label.clone(), target);
-                target_block.insert(0, label.clone().into());
-            }
+            let label = self.label(target);
             let goto_block = self.function.new_block();
             self.function
                 .block_mut(goto_block)
@@ -194,6 +220,63 @@ impl GraphStructurer {
         }
     }
 
+    // the label at the start of `target`, it's inserted if there isn't one yet
+    fn label(&mut self, target: NodeIndex) -> ast::Label {
+        // TODO: make label an Rc and have a global counter for block name
+        let label = ast::Label(format!("l{}", target.index()));
+        let target_block = self.function.block_mut(target).unwrap();
+        if target_block.first().and_then(|s| s.as_label()).is_none() {
+            self.label_to_node.insert(label.clone(), target);
+            target_block.insert(0, label.clone().into());
+        }
+        label
+    }
+
+    // replaces every remaining edge with a goto, for when structuring had to stop early
+    fn insert_gotos(&mut self) {
+        let nodes = self.function.graph().node_indices().collect_vec();
+        let controls = self.generic_for_controls(&nodes);
+        for node in nodes {
+            let targets = match self.function.conditional_edges(node) {
+                Some((then_edge, else_edge)) => vec![then_edge.target(), else_edge.target()],
+                None => self.function.successor_blocks(node).collect_vec(),
+            };
+            self.function.remove_edges(node);
+            let gotos = targets
+                .into_iter()
+                .map(|target| ast::Goto::new(self.label(target)))
+                .collect_vec();
+            // for loops that weren't structured are written as the steps they take
+            let statements = std::mem::take(self.function.block_mut(node).unwrap());
+            let (mut block, next_statement) = Self::lower_for_statements(statements, &controls);
+            match (&gotos[..], next_statement) {
+                ([then_goto, else_goto], Some((condition, carry))) => {
+                    let then_block = carry
+                        .into_iter()
+                        .chain(std::iter::once(then_goto.clone().into()))
+                        .collect_vec();
+                    block.push(
+                        ast::If::new(
+                            condition,
+                            then_block.into(),
+                            vec![else_goto.clone().into()].into(),
+                        )
+                        .into(),
+                    );
+                }
+                ([then_goto, else_goto], None)
+                    if matches!(block.last(), Some(ast::Statement::If(_))) =>
+                {
+                    let r#if = block.last_mut().unwrap().as_if_mut().unwrap();
+                    r#if.then_block.lock().push(then_goto.clone().into());
+                    r#if.else_block.lock().push(else_goto.clone().into());
+                }
+                _ => block.extend(gotos.into_iter().map(Into::into)),
+            }
+            *self.function.block_mut(node).unwrap() = block;
+        }
+    }
+
     fn remove_last_return(block: ast::Block) -> ast::Block {
         if let Some(ast::Statement::Return(last_statement)) = block.last() {
             if last_statement.values.is_empty() {
@@ -204,12 +287,28 @@ impl GraphStructurer {
         block
     }
 
-    fn collapse(&mut self) {
+    // `should_stop` is called before every attempt to match the graph, collapsing stops
+    // as soon as it returns a reason
+    fn collapse(&mut self, should_stop: &mut dyn FnMut() -> Option<String>) -> Option<String> {
         loop {
-            while self.match_blocks() {}
+            loop {
+                if let Some(reason) = should_stop() {
+                    return Some(reason);
+                }
+                if !self.match_blocks() {
+                    break;
+                }
+            }
             if self.function.graph().node_count() == 1 {
                 break;
             }
+            if !self.allow_gotos {
+                if !self.try_duplicate() {
+                    self.dispatched = self.dispatch();
+                    break;
+                }
+                continue;
+            }
             // last resort refinement
             let edges = self.function.graph().edge_indices().collect::<Vec<_>>();
             // https://edmcman.github.io/papers/usenix13.pdf
@@ -238,6 +337,9 @@ impl GraphStructurer {
                     continue;
                 }
 
+                if let Some(reason) = should_stop() {
+                    return Some(reason);
+                }
                 self.insert_goto_for_edge(edge);
                 self.find_loop_headers();
                 changed = self.match_blocks();
@@ -253,6 +355,9 @@ impl GraphStructurer {
                     if self.function.graph().edge_weight(edge).is_none() {
                         continue;
                     }
+                    if let Some(reason) = should_stop() {
+                        return Some(reason);
+                    }
                     self.insert_goto_for_edge(edge);
                     self.find_loop_headers();
                     changed = self.match_blocks();
@@ -265,11 +370,21 @@ impl GraphStructurer {
                 }
             }
         }
+        None
     }
 
-    fn structure(mut self) -> ast::Block {
-        self.collapse();
-        if self.function.graph().node_count() != 1 {
+    fn structure(mut self, should_stop: &mut dyn FnMut() -> Option<String>) -> Structured {
+        self.split_irreducible_loops();
+        self.find_loop_headers();
+        let stopped = self.collapse(should_stop);
+        if stopped.is_some() {
+            if self.allow_gotos {
+                self.insert_gotos();
+            } else {
+                self.dispatched = self.dispatch();
+            }
+        }
+        let block = if self.function.graph().node_count() != 1 {
             let mut res_block = ast::Block::default();
             let entry = self.function.entry().unwrap();
             let mut stack = vec![entry];
@@ -351,10 +466,30 @@ impl GraphStructurer {
                     .remove_block(self.function.entry().unwrap())
                     .unwrap(),
             )
+        };
+        Structured {
+            block,
+            stopped,
+            dispatched: self.dispatched,
         }
     }
 }
 
-pub fn lift(function: cfg::function::Function) -> ast::Block {
-    GraphStructurer::new(function).structure()
+pub struct Structured {
+    pub block: ast::Block,
+    // the reason `should_stop` returned, see `lift`
+    pub stopped: Option<String>,
+    // blocks that had to be written as a state machine because gotos weren't allowed
+    pub dispatched: usize,
+}
+
+// `should_stop` can end structuring early, the rest of the function is then written with
+// gotos, or as a state machine if they aren't allowed
+pub fn lift(
+    function: cfg::function::Function,
+    outer_locals: FxHashSet<ast::RcLocal>,
+    allow_gotos: bool,
+    mut should_stop: impl FnMut() -> Option<String>,
+) -> Structured {
+    GraphStructurer::new(function, outer_locals, allow_gotos).structure(&mut should_stop)
 }
@@ -88,9 +88,12 @@ impl GraphStructurer {
                 }
 
                 let else_successors = self.function.successor_blocks(else_node).collect_vec();
-                if !(!then_successors.is_empty() && then_successors[0] == else_node)
-                    && !(else_successors.len() == 1 && then_successors[0] == else_successors[0])
-                    && !(then_successors[0] == header && else_node == init_block)
+                // a body without successors returns in its first iteration,
+                // ex. `for i = 1, 10 do return i end`
+                if let Some(&then_successor) = then_successors.first()
+                    && then_successor != else_node
+                    && !(else_successors.len() == 1 && then_successor == else_successors[0])
+                    && !(then_successor == header && else_node == init_block)
                 {
                     return false;
                 }
@@ -521,6 +524,8 @@ impl GraphStructurer {
                 .successor_blocks(body)
                 .exactly_one()
                 .is_ok_and(|s| s == header)
+            // the body can't be removed if the loop can be entered through it
+            && self.function.predecessor_blocks(body).all(|p| p == header)
         {
             let block = self.function.remove_block(body).unwrap();
 
@@ -0,0 +1,126 @@
+use cfg::function::IrreducibleLoop;
+use itertools::Itertools;
+use petgraph::{
+    stable_graph::NodeIndex,
+    visit::{Dfs, EdgeRef, Walker},
+    Direction,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::{duplicate::copy_block, GraphStructurer};
+
+impl GraphStructurer {
+    // the blocks of the loop that can be reached from `entry` without going through `header`
+    fn reached_before_header(
+        &self,
+        irreducible_loop: &IrreducibleLoop,
+        header: NodeIndex,
+        entry: NodeIndex,
+    ) -> Vec<NodeIndex> {
+        let mut reached = FxHashSet::default();
+        let mut stack = vec![entry];
+        while let Some(node) = stack.pop() {
+            if node == header || !reached.insert(node) {
+                continue;
+            }
+            stack.extend(
+                self.function
+                    .successor_blocks(node)
+                    .filter(|s| irreducible_loop.nodes.contains(s)),
+            );
+        }
+        reached.into_iter().sorted_unstable().collect()
+    }
+
+    // gives the predecessors of `entry` that are outside of the loop a copy of the blocks
+    // they can reach before the header, so that the loop is only entered through the header.
+    // returns false if the blocks can't be copied or there's not enough budget left
+    fn split_entry(
+        &mut self,
+        irreducible_loop: &IrreducibleLoop,
+        header: NodeIndex,
+        entry: NodeIndex,
+    ) -> bool {
+        let nodes = self.reached_before_header(irreducible_loop, header, entry);
+        let mut budget = self.split_budget;
+        let mut blocks = Vec::with_capacity(nodes.len());
+        for &node in &nodes {
+            // empty blocks still cost something, otherwise splitting might never stop
+            let Some(left) = budget.checked_sub(1) else {
+                return false;
+            };
+            budget = left;
+            let Some(block) = copy_block(self.function.block(node).unwrap(), &mut budget) else {
+                return false;
+            };
+            blocks.push(block);
+        }
+        self.split_budget = budget;
+
+        let copies = nodes
+            .iter()
+            .zip(blocks)
+            .map(|(&node, block)| {
+                let copy = self.function.new_block();
+                *self.function.block_mut(copy).unwrap() = block;
+                (node, copy)
+            })
+            .collect::<FxHashMap<_, _>>();
+        for (&node, &copy) in copies.iter().sorted_unstable() {
+            let edges = self
+                .function
+                .edges(node)
+                .map(|e| {
+                    let target = copies.get(&e.target()).copied().unwrap_or(e.target());
+                    (target, e.weight().clone())
+                })
+                .collect_vec();
+            self.function.set_edges(copy, edges);
+        }
+        let outside = self
+            .function
+            .graph()
+            .edges_directed(entry, Direction::Incoming)
+            .filter(|e| !irreducible_loop.nodes.contains(&e.source()))
+            .map(|e| e.id())
+            .collect_vec();
+        for edge in outside {
+            let (source, _) = self.function.graph().edge_endpoints(edge).unwrap();
+            let weight = self.function.graph_mut().remove_edge(edge).unwrap();
+            self.function
+                .graph_mut()
+                .add_edge(source, copies[&entry], weight);
+        }
+        true
+    }
+
+    // loops with more than one entry can't be collapsed, they're made reducible by giving
+    // every entry other than the first one its own copy of the loop body up to the header.
+    // copying stops once `split_budget` is used up, the rest is left to `collapse`
+    pub(crate) fn split_irreducible_loops(&mut self) {
+        let entry = self.function.entry().unwrap();
+        'split: loop {
+            let preorder = Dfs::new(self.function.graph(), entry)
+                .iter(self.function.graph())
+                .enumerate()
+                .map(|(index, node)| (node, index))
+                .collect::<FxHashMap<_, _>>();
+            for irreducible_loop in self.function.irreducible_loops() {
+                let header = *irreducible_loop
+                    .entries
+                    .iter()
+                    .min_by_key(|e| preorder[e])
+                    .unwrap();
+                for &loop_entry in &irreducible_loop.entries {
+                    if loop_entry != header
+                        && self.split_entry(&irreducible_loop, header, loop_entry)
+                    {
+                        // the graph changed, the loops have to be found again
+                        continue 'split;
+                    }
+                }
+            }
+            break;
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use cfg::{function::Function, listing};
+
+// collapsing stops right away, so the whole graph is written with gotos
+fn structure_with_gotos(function: Function) -> String {
+    restructure::lift(function, Default::default(), true, || {
+        Some("stopped".to_string())
+    })
+    .block
+    .to_string()
+}
+
+#[test]
+fn unstructured_numeric_for() {
+    let function = listing::parse(
+        r#"function 0
+params n
+
+block 0 entry
+    numforinit i, l, s = 1, n, 1
+    -> u 1
+
+block 1
+    numfornext i = i, l, s
+    -> t 2
+    -> e 3
+
+block 2
+    f = @print
+    f(i)
+    -> u 1
+
+block 3
+    return
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        structure_with_gotos(function),
+        r#"-- block 0
+i, l, s = 1, n, 1
+i = i - s
+::l1::
+i = i + s
+if s > 0 and i <= l or s <= 0 and i >= l then
+	goto l2
+else
+	goto l3
+end
+::l3::
+return
+::l2::
+f = print
+f(i)
+goto l1"#
+    );
+}
+
+#[test]
+fn unstructured_generic_for() {
+    let function = listing::parse(
+        r#"function 0
+params t
+
+block 0 entry
+    genericforinit g, s, c = @pairs, t, nil
+    -> u 1
+
+block 1
+    genericfornext k, v = g, s
+    -> t 2
+    -> e 3
+
+block 2
+    f = @print
+    f(k, v)
+    -> u 1
+
+block 3
+    return
+"#,
+    )
+    .unwrap();
+    // the control variable is carried over only when the loop continues
+    assert_eq!(
+        structure_with_gotos(function),
+        r#"-- block 0
+g, s, c = pairs, t, nil
+::l1::
+k, v = g(s, c)
+if k ~= nil then
+	c = k
+	goto l2
+else
+	goto l3
+end
+::l3::
+return
+::l2::
+f = print
+f(k, v)
+goto l1"#
+    );
+}
+
+#[test]
+fn unstructured_generalized_for() {
+    let function = listing::parse(
+        r#"function 0
+params t
+
+block 0 entry
+    genericforinit generalized g, s, c = t, nil, nil
+    -> u 1
+
+block 1
+    genericfornext k, v = g, s
+    -> t 2
+    -> e 3
+
+block 2
+    f = @print
+    f(k, v)
+    -> u 1
+
+block 3
+    return
+"#,
+    )
+    .unwrap();
+    // `for k, v in t do` can't be written as a call, so what the vm does is spelled out
+    let output = structure_with_gotos(function);
+    let metatable = output
+        .lines()
+        .nth(3)
+        .unwrap()
+        .split(" = ")
+        .next()
+        .unwrap()
+        .trim();
+    assert_eq!(
+        output.replace(metatable, "mt"),
+        r#"-- block 0
+g, s, c = t, nil, nil
+if type(g) ~= "function" then
+	mt = getmetatable(g)
+	if mt and mt.__iter then
+		g, s, c = mt.__iter(g)
+	elseif not (mt and mt.__call) then
+		g, s, c = next, g, nil
+	end
+end
+::l1::
+k, v = g(s, c)
+if k ~= nil then
+	c = k
+	goto l2
+else
+	goto l3
+end
+::l3::
+return
+::l2::
+f = print
+f(k, v)
+goto l1"#
+    );
+}
@@ -0,0 +1,42 @@
+use cfg::listing;
+
+#[test]
+fn split_irreducible_loop() {
+    // block 2 jumps into the middle of the loop formed by blocks 1 and 2, the entry gets
+    // its own copy of block 2 so the loop is only entered through block 1
+    let input = r#"function 0
+params a, f, g
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    f()
+    -> u 2
+
+block 2
+    if g()
+    -> t 3
+    -> e 1
+
+block 3
+    return
+"#;
+    let expected = r#"if not a then
+	if g() then
+		return
+	end
+end
+while true do
+	f()
+	if g() then
+		return
+	end
+end"#;
+    let function = listing::parse(input).unwrap();
+    let structured = restructure::lift(function, Default::default(), false, || None);
+    assert_eq!(structured.dispatched, 0);
+    assert_eq!(structured.block.to_string(), expected);
+}
@@ -0,0 +1,197 @@
+use cfg::listing;
+use restructure::Structured;
+
+fn structure_without_gotos(input: &str) -> Structured {
+    let function = listing::parse(input).unwrap();
+    restructure::lift(function, Default::default(), false, || None)
+}
+
+#[test]
+fn duplicate_small_block() {
+    // `h()` is reached from both branches of `a`, a copy of it avoids the goto
+    let structured = structure_without_gotos(
+        r#"function 0
+params a, b, f, g, h
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    f()
+    -> u 3
+
+block 2
+    if b
+    -> t 3
+    -> e 4
+
+block 3
+    h()
+    -> u 5
+
+block 4
+    g()
+    -> u 5
+
+block 5
+    return
+"#,
+    );
+    assert_eq!(structured.dispatched, 0);
+    assert_eq!(
+        structured.block.to_string(),
+        r#"if a then
+	f()
+	h()
+elseif b then
+	h()
+else
+	g()
+end"#
+    );
+}
+
+#[test]
+fn dispatch_large_blocks() {
+    // both targets are reached from both conditions and are too large to be copied
+    let input = r#"function 0
+params a, b, c, g, h
+
+block 0 entry
+    if a
+    -> t 1
+    -> e 2
+
+block 1
+    if b
+    -> t 3
+    -> e 4
+
+block 2
+    if c
+    -> t 3
+    -> e 4
+
+block 3
+H    -> u 5
+
+block 4
+G    -> u 5
+
+block 5
+    return
+"#
+    .replace("H", &"    h()\n".repeat(9))
+    .replace("G", &"    g()\n".repeat(9));
+    let structured = structure_without_gotos(&input);
+    assert_eq!(structured.dispatched, 5);
+    let output = structured.block.to_string();
+    assert!(!output.contains("goto"));
+    assert!(output.contains("while true do\n"));
+    // every block is run once, the calls aren't copied
+    assert_eq!(output.matches("h()").count(), 9);
+    assert_eq!(output.matches("g()").count(), 9);
+}
+
+#[test]
+fn dispatch_carried_local() {
+    // the state machine is built right away, block 1 is a state that loops to itself and
+    // reads the `x` from its last run, so `x` is declared outside of the loop. `y` is
+    // written before it's read, so it's declared where it's written
+    let function = listing::parse(
+        r#"function 0
+params f, g
+
+block 0 entry
+    -> u 1
+
+block 1
+    x = f(x)
+    y = g()
+    g(y)
+    if x
+    -> t 1
+    -> e 2
+
+block 2
+    return
+"#,
+    )
+    .unwrap();
+    let outer_locals = function.parameters.iter().cloned().collect();
+    let structured = restructure::lift(function, outer_locals, false, || {
+        Some("stopped".to_string())
+    });
+    assert_eq!(structured.dispatched, 3);
+    let output = structured.block.to_string();
+    assert!(output.starts_with("local x\n"), "{}", output);
+}
+
+#[test]
+fn structured_generalized_for() {
+    // a structured loop keeps the generalized iteration, `__iter` and `__call` are left to the vm
+    let structured = structure_without_gotos(
+        r#"function 0
+params t
+
+block 0 entry
+    genericforinit generalized g, s, c = t, nil, nil
+    -> u 1
+
+block 1
+    genericfornext k, v = g, s
+    -> t 2
+    -> e 3
+
+block 2
+    f = @print
+    f(k, v)
+    -> u 1
+
+block 3
+    return
+"#,
+    );
+    assert_eq!(structured.dispatched, 0);
+    assert_eq!(
+        structured.block.to_string(),
+        r#"for k, v in t do
+	f = print
+	f(k, v)
+end"#
+    );
+}
+
+#[test]
+fn numeric_for_body_returns() {
+    // the body has no successors, the loop never reaches its next iteration
+    let structured = structure_without_gotos(
+        r#"function 0
+params n
+
+block 0 entry
+    numforinit i, l, s = 1, n, 1
+    -> u 1
+
+block 1
+    numfornext i = i, l, s
+    -> t 2
+    -> e 3
+
+block 2
+    return i
+
+block 3
+    return
+"#,
+    );
+    assert_eq!(structured.dispatched, 0);
+    assert_eq!(
+        structured.block.to_string(),
+        r#"for i = 1, n do
+	return i
+end"#
+    );
+}